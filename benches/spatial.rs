@@ -0,0 +1,75 @@
+//! Compares the spatial indices with `cargo bench`, for teapots spread evenly and bunched up in a few places
+
+// Its tests are built here too without a test harness to run them
+#[allow(dead_code, unused_imports)]
+#[path = "../src/spatial.rs"]
+mod spatial;
+
+use std::{hint::black_box, time::Instant};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use spatial::Kind;
+
+/// Teapots are about this big, and spread between -RANGE and RANGE
+const RADIUS: f32 = 1.0;
+const RANGE: f32 = 100.0;
+const QUERIES: usize = 1000;
+
+fn spread(count: usize, rng: &mut StdRng) -> Vec<[f32; 3]> {
+    (0..count).map(|_| [0; 3].map(|_| rng.gen_range(-RANGE..RANGE))).collect()
+}
+
+fn bunched(count: usize, rng: &mut StdRng) -> Vec<[f32; 3]> {
+    let clumps = spread(8, rng);
+    (0..count).map(|i| clumps[i % clumps.len()].map(|v| v + rng.gen_range(-RANGE..RANGE) * 0.05)).collect()
+}
+
+/// Milliseconds taken by `run`
+fn time(run: impl FnOnce()) -> f64 {
+    let start = Instant::now();
+    run();
+    start.elapsed().as_secs_f64() * 1000.0
+}
+
+fn main() {
+    println!("{:<8} {:<7} {:>8} {:>10} {:>10} {:>10} {:>10} {:>10}", "teapots", "index", "layout", "build ms", "move ms", "within ms", "nearest ms", "ray ms");
+    for count in [1_000, 10_000, 100_000] {
+        for (layout, place) in [("spread", spread as fn(usize, &mut StdRng) -> Vec<[f32; 3]>), ("bunched", bunched)] {
+            for kind in [Kind::Grid, Kind::Octree, Kind::Bvh] {
+                let mut rng = StdRng::seed_from_u64(1);
+                let mut positions = place(count, &mut rng);
+                let points = spread(QUERIES, &mut rng);
+                let mut index = spatial::new(kind, 2.0 * RADIUS);
+
+                let build = time(|| for (id, &position) in positions.iter().enumerate() {
+                    index.update(id, position, RADIUS);
+                });
+                // A frame of every teapot floating along a little, like follow mode
+                for position in &mut positions {
+                    *position = position.map(|v| v + rng.gen_range(-0.1..0.1));
+                }
+                let moved = time(|| for (id, &position) in positions.iter().enumerate() {
+                    index.update(id, position, RADIUS);
+                });
+                let within = time(|| for &point in &points {
+                    black_box(index.within(point, 5.0 * RADIUS));
+                });
+                let nearest = time(|| for &point in &points {
+                    black_box(index.nearest(point, 8));
+                });
+                let ray = time(|| for &point in &points {
+                    let direction = point.map(|v| -v);
+                    let length = direction.iter().map(|v| v * v).sum::<f32>().sqrt();
+                    let direction = direction.map(|v| v / length);
+                    black_box(index.ray(point, direction, &mut |id| {
+                        let to = [0, 1, 2].map(|i| positions[id][i] - point[i]);
+                        let along: f32 = (0..3).map(|i| to[i] * direction[i]).sum();
+                        let away = (0..3).map(|i| to[i] * to[i]).sum::<f32>() - along * along;
+                        (along > 0.0 && away <= RADIUS * RADIUS).then_some(along)
+                    }));
+                });
+                println!("{:<8} {:<7} {:>8} {:>10.2} {:>10.2} {:>10.2} {:>10.2} {:>10.2}", count, format!("{:?}", kind), layout, build, moved, within, nearest, ray);
+            }
+        }
+    }
+}
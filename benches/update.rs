@@ -0,0 +1,106 @@
+//! Times a frame of moving and drawing teapots with `cargo bench`, on one thread and on all of them
+
+// Their tests are built here too without a test harness to run them
+#[allow(dead_code, unused_imports)]
+#[path = "../src/colour.rs"]
+mod colour;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/field.rs"]
+mod field;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/font.rs"]
+mod font;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/formation.rs"]
+mod formation;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/instance.rs"]
+mod instance;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/lighting.rs"]
+mod lighting;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/ply.rs"]
+mod ply;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/positions.rs"]
+mod positions;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/shoot.rs"]
+mod shoot;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/spin.rs"]
+mod spin;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/update.rs"]
+mod update;
+
+use std::{hint::black_box, time::Instant};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use field::Field;
+use instance::Instance;
+use positions::{Teapot, Teapots};
+
+const FRAMES: usize = 5;
+
+fn teapots(count: usize) -> Teapots {
+    let mut rng = StdRng::seed_from_u64(1);
+    (0..count).map(|id| {
+        let position = [0; 3].map(|_| rng.gen_range(-64.0..64.0));
+        Teapot {
+            id,
+            velocity: [0; 3].map(|_| rng.gen_range(-1.0..1.0)),
+            gravity: rng.gen_bool(0.1).then_some([0.0, -9.81, 0.0]),
+            ..Teapot::new(position, [1.0; 3])
+        }
+    }).collect::<Vec<_>>().into()
+}
+
+fn fields() -> Vec<Field> {
+    ["attract:camera:0.5:20", "vortex:0,0,0:0.3:40", "turbulence:0,0,0:2:8", "wind:0,0,0:1::1,0,0"]
+        .map(|spec| field::parse(spec).unwrap())
+        .to_vec()
+}
+
+/// Milliseconds per frame of `run` on `threads` threads
+fn time(threads: usize, mut run: impl FnMut(f32) + Send) -> f64 {
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+    let start = Instant::now();
+    pool.install(|| for frame in 0..FRAMES {
+        run(frame as f32 / 60.0);
+    });
+    start.elapsed().as_secs_f64() * 1000.0 / FRAMES as f64
+}
+
+fn main() {
+    let threads = rayon::current_num_threads();
+    let fields = fields();
+    println!("{:>9} {:>14} {:>14} {:>14} {:>14}", "teapots", "advance 1 ms", format!("advance {} ms", threads), "instances 1 ms", format!("instances {} ms", threads));
+    for count in [10_000, 100_000, 1_000_000] {
+        let mut row = vec![count.to_string()];
+        for thread_count in [1, threads] {
+            let mut teapots = teapots(count);
+            row.push(format!("{:.2}", time(thread_count, |time| {
+                update::advance(&mut teapots, None, &fields, [0.0; 3], time, 1.0 / 60.0, false);
+            })));
+        }
+        for thread_count in [1, threads] {
+            let teapots = teapots(count);
+            row.push(format!("{:.2}", time(thread_count, |time| {
+                black_box(update::instances(&teapots, |i| {
+                    let (position, rotation) = update::between_steps(&teapots, i, 0.5);
+                    let details = &teapots.details[i];
+                    Instance {
+                        world_position: position,
+                        instance_colour: details.colour,
+                        instance_scale: details.scale,
+                        instance_rotation: spin::multiply(rotation, details.spin.rotation(time)),
+                    }
+                }));
+            })));
+        }
+        println!("{:>9} {:>14} {:>14} {:>14} {:>14}", row[0], row[1], row[2], row[3], row[4]);
+    }
+}
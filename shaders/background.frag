@@ -0,0 +1,10 @@
+#version 150
+
+#include "sky.glsl"
+
+in vec3 v_direction;
+out vec4 color;
+
+void main() {
+    color = vec4(sky_colour(v_direction), 1.0);
+}
@@ -0,0 +1,16 @@
+#version 150
+
+out vec3 v_direction;
+
+uniform mat4 perspective;
+uniform mat4 view;
+
+// Draws a single triangle covering the whole screen, no vertex buffer needed
+void main() {
+    vec2 corner = vec2(float((gl_VertexID << 1) & 2), float(gl_VertexID & 2)) * 2.0 - 1.0;
+    // Only rotation, the sky is infinitely far away
+    mat4 rotation = mat4(mat3(view));
+    vec4 far_point = inverse(perspective * rotation) * vec4(corner, 1.0, 1.0);
+    v_direction = far_point.xyz / far_point.w;
+    gl_Position = vec4(corner, 1.0, 1.0);
+}
@@ -1,14 +1,12 @@
-#version 130
+#version 150
+
+#include "lighting.glsl"
 
 in vec3 v_normal;
+in vec3 v_position;
+in vec3 v_colour;
 out vec4 color;
 
-uniform vec3 u_light;
-uniform vec3 colour;
-
 void main() {
-    float brightness = dot(normalize(v_normal), normalize(u_light));
-    vec3 dark_color = colour*0.6;
-    vec3 regular_color = colour;
-    color = vec4(mix(dark_color, regular_color, brightness), 1.0);
-}
\ No newline at end of file
+    color = vec4(shade(v_position, normalize(v_normal), v_colour), 1.0);
+}
@@ -0,0 +1,10 @@
+#version 150
+
+in vec2 position;
+
+uniform vec2 resolution;
+
+// Positions are in pixels from the top left corner
+void main() {
+    gl_Position = vec4(position / resolution * vec2(2.0, -2.0) + vec2(-1.0, 1.0), 0.0, 1.0);
+}
@@ -0,0 +1,23 @@
+#version 150
+
+#include "lighting.glsl"
+
+in vec2 v_uv;
+in vec3 v_position;
+in vec3 v_colour;
+out vec4 color;
+
+uniform sampler2D u_impostor;
+uniform mat4 view;
+
+void main() {
+    vec4 baked = texture(u_impostor, v_uv);
+    if (baked.a < 0.5) {
+        discard;
+    }
+
+    // Back from the camera space of the bake to world space, the billboard faces the camera so its axes are the camera's
+    vec3 normal = normalize(baked.xyz * 2.0 - 1.0);
+    normal = transpose(mat3(view)) * normal;
+    color = vec4(shade(v_position, normalize(normal), v_colour), 1.0);
+}
@@ -0,0 +1,33 @@
+#version 150
+
+#define PI 3.1415926535
+
+in vec2 corner; // -1 to 1
+#include "instance.glsl"
+
+out vec2 v_uv;
+out vec3 v_position;
+out vec3 v_colour;
+
+uniform mat4 perspective;
+uniform mat4 view;
+uniform vec3 u_camera;
+uniform float u_impostor_radius;
+uniform int u_impostor_frames;
+
+// A square facing the camera, showing whichever baked view of the teapot is closest to the angle it is seen from
+void main() {
+    vec3 right = vec3(view[0][0], view[1][0], view[2][0]);
+    vec3 up = vec3(view[0][1], view[1][1], view[2][1]);
+
+    // Tilted teapots still use the views baked around the upright teapot
+    vec4 inverse_rotation = vec4(-instance_rotation.xyz, instance_rotation.w);
+    vec3 to_camera = rotate(inverse_rotation, u_camera - world_position);
+    float frame_angle = 2.0 * PI / float(u_impostor_frames);
+    int frame = int(mod(round(atan(to_camera.z, to_camera.x) / frame_angle), float(u_impostor_frames)));
+    v_uv = vec2((corner.x * 0.5 + 0.5 + float(frame)) / float(u_impostor_frames), corner.y * 0.5 + 0.5);
+
+    v_position = world_position + (right * corner.x + up * corner.y) * u_impostor_radius * instance_scale;
+    v_colour = instance_colour;
+    gl_Position = perspective * view * vec4(v_position, 1.0);
+}
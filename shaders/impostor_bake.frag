@@ -0,0 +1,12 @@
+#version 150
+
+in vec3 v_normal;
+out vec4 color;
+
+uniform mat4 view;
+
+// Stores the normal as seen from the camera, so the impostor can be lit like the real teapot
+void main() {
+    vec3 normal = mat3(view) * normalize(v_normal);
+    color = vec4(normal * 0.5 + 0.5, 1.0);
+}
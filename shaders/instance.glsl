@@ -0,0 +1,15 @@
+// Per instance attributes of the teapots, see instance.rs
+
+in vec3 world_position;
+in vec3 instance_colour;
+in float instance_scale;
+in vec4 instance_rotation; // Quaternion, including the spin
+
+vec3 rotate(vec4 q, vec3 v) {
+    return v + 2.0 * cross(q.xyz, cross(q.xyz, v) + q.w * v);
+}
+
+// From the model, after the model matrix, to the world
+vec3 instance_to_world(vec3 position) {
+    return rotate(instance_rotation, position) * instance_scale + world_position;
+}
@@ -0,0 +1,114 @@
+// Lighting, shadows, reflections and fog, shared by the teapots, the ground and the teapot impostors
+
+// Has to match MAX_LIGHTS in lighting.rs
+#define MAX_LIGHTS 8
+// Has to match MAX_CASCADES in shadow.rs
+#define MAX_CASCADES 4
+
+#include "sky.glsl"
+
+struct Light {
+    vec4 vector; // xyz is the direction towards a directional light or the position of a point light, w is 1.0 for point lights
+    vec4 colour; // rgb is colour multiplied by intensity, a is the range of a point light
+};
+
+uniform Light u_lights[MAX_LIGHTS];
+uniform int u_light_count;
+uniform float u_ambient;
+uniform float u_specular;
+uniform float u_shininess;
+uniform vec3 u_camera;
+uniform float reflectivity; // 0.0 for plastic, 1.0 for a perfect mirror
+uniform float metallic; // How much reflections are tinted by the colour
+
+uniform int u_shadow_light; // -1 when there are no shadows
+uniform int u_cascade_count;
+uniform float u_cascade_radii[MAX_CASCADES];
+uniform mat4 u_shadow_matrices[MAX_CASCADES];
+uniform float u_shadow_texel;
+uniform sampler2DShadow u_shadow_map_0;
+uniform sampler2DShadow u_shadow_map_1;
+uniform sampler2DShadow u_shadow_map_2;
+uniform sampler2DShadow u_shadow_map_3;
+
+float sample_shadow_map(int cascade, vec3 coord) {
+    if (cascade == 0) return texture(u_shadow_map_0, coord);
+    if (cascade == 1) return texture(u_shadow_map_1, coord);
+    if (cascade == 2) return texture(u_shadow_map_2, coord);
+    return texture(u_shadow_map_3, coord);
+}
+
+// 1.0 when fully lit, 0.0 when fully in shadow
+float shadow(vec3 position, vec3 normal) {
+    float distance_to_camera = distance(position, u_camera);
+    int cascade = 0;
+    while (cascade < u_cascade_count && distance_to_camera > u_cascade_radii[cascade]) {
+        cascade++;
+    }
+    if (cascade >= u_cascade_count) {
+        return 1.0;
+    }
+
+    // Push the position out along the normal a bit, bigger cascades have bigger texels so need a bigger push
+    float texel_size = 2.0 * u_cascade_radii[cascade] * u_shadow_texel;
+    vec4 light_space = u_shadow_matrices[cascade] * vec4(position + normal * texel_size * 1.5, 1.0);
+    vec3 coord = light_space.xyz * 0.5 + 0.5;
+
+    // 3x3 percentage closer filtering, softens the edges of the shadows
+    float lit = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            lit += sample_shadow_map(cascade, coord + vec3(x, y, 0.0) * u_shadow_texel);
+        }
+    }
+    return lit / 9.0;
+}
+
+// Colour of a surface at `position` facing `normal`, faded into the fog
+vec3 shade(vec3 position, vec3 normal, vec3 colour) {
+    vec3 to_camera = normalize(u_camera - position);
+
+    vec3 diffuse = vec3(0.0);
+    vec3 specular = vec3(0.0);
+
+    for (int i = 0; i < u_light_count; i++) {
+        vec3 to_light;
+        float attenuation = 1.0;
+        if (u_lights[i].vector.w > 0.5) {
+            vec3 offset = u_lights[i].vector.xyz - position;
+            float range = u_lights[i].colour.a;
+            to_light = normalize(offset);
+            attenuation = 1.0 / (1.0 + 3.0 * dot(offset, offset) / (range * range));
+        } else {
+            to_light = normalize(u_lights[i].vector.xyz);
+        }
+
+        float lambert = max(dot(normal, to_light), 0.0);
+        if (i == u_shadow_light && lambert > 0.0) {
+            attenuation *= shadow(position, normal);
+        }
+
+        vec3 light_colour = u_lights[i].colour.rgb * attenuation;
+        diffuse += light_colour * lambert;
+
+        // Blinn-Phong, uses the half vector between the light and the camera
+        if (lambert > 0.0) {
+            vec3 half_vector = normalize(to_light + to_camera);
+            specular += light_colour * pow(max(dot(normal, half_vector), 0.0), u_shininess) * u_specular;
+        }
+    }
+
+    vec3 lit = colour * (u_ambient + diffuse) + specular;
+
+    // Reflect whatever is in the background, the skybox or the sky
+    if (reflectivity > 0.0) {
+        vec3 reflected = sky_colour(reflect(-to_camera, normal)) * mix(vec3(1.0), colour, metallic);
+        // Schlick's approximation, everything gets more reflective at grazing angles
+        float fresnel = reflectivity + (1.0 - reflectivity) * pow(1.0 - max(dot(normal, to_camera), 0.0), 5.0);
+        lit = mix(lit, reflected + specular, fresnel);
+    }
+
+    // Fade into whatever is behind the teapot in the distance
+    vec3 view_offset = position - u_camera;
+    return mix(lit, sky_colour(view_offset), fog_amount(length(view_offset)));
+}
@@ -0,0 +1,17 @@
+#version 150
+
+in vec3 position;
+in vec3 normal;
+#include "instance.glsl"
+
+uniform mat4 perspective;
+uniform mat4 view;
+uniform mat4 model;
+uniform float width;
+
+// Pushes the surface out along its normals, only the back faces are drawn so just the edge shows around the teapot
+void main() {
+    vec3 world_normal = normalize(rotate(instance_rotation, transpose(inverse(mat3(model))) * normal));
+    vec3 world = instance_to_world((model * vec4(position, 1.0)).xyz) + world_normal * width * instance_scale;
+    gl_Position = perspective * view * vec4(world, 1.0);
+}
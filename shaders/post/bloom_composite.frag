@@ -0,0 +1,13 @@
+#version 150
+
+in vec2 v_uv;
+out vec4 color;
+
+uniform sampler2D u_texture;
+uniform sampler2D u_bloom;
+uniform float u_bloom_intensity;
+
+void main() {
+    vec3 colour = texture(u_texture, v_uv).rgb + texture(u_bloom, v_uv).rgb * u_bloom_intensity;
+    color = vec4(colour, 1.0);
+}
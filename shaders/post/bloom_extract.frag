@@ -0,0 +1,18 @@
+#version 150
+
+in vec2 v_uv;
+out vec4 color;
+
+uniform sampler2D u_texture;
+uniform float u_bloom_threshold;
+
+// Keeps only the parts brighter than the threshold, with a soft knee so it doesn't flicker
+void main() {
+    vec3 colour = texture(u_texture, v_uv).rgb;
+    float brightness = max(colour.r, max(colour.g, colour.b));
+    float knee = u_bloom_threshold * 0.5;
+    float soft = clamp(brightness - u_bloom_threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee + 0.0001);
+    float contribution = max(soft, brightness - u_bloom_threshold) / max(brightness, 0.0001);
+    color = vec4(colour * contribution, 1.0);
+}
@@ -0,0 +1,18 @@
+#version 150
+
+in vec2 v_uv;
+out vec4 color;
+
+uniform sampler2D u_texture;
+uniform vec2 u_direction; // (1, 0) for horizontal, (0, 1) for vertical
+
+// 9 tap gaussian blur using linear filtering to sample between texels
+void main() {
+    vec2 texel = u_direction / vec2(textureSize(u_texture, 0));
+    vec3 result = texture(u_texture, v_uv).rgb * 0.2270270270;
+    result += texture(u_texture, v_uv + texel * 1.3846153846).rgb * 0.3162162162;
+    result += texture(u_texture, v_uv - texel * 1.3846153846).rgb * 0.3162162162;
+    result += texture(u_texture, v_uv + texel * 3.2307692308).rgb * 0.0702702703;
+    result += texture(u_texture, v_uv - texel * 3.2307692308).rgb * 0.0702702703;
+    color = vec4(result, 1.0);
+}
@@ -0,0 +1,17 @@
+#version 150
+
+in vec2 v_uv;
+out vec4 color;
+
+uniform sampler2D u_texture;
+uniform vec2 u_resolution;
+uniform float u_chromatic_aberration;
+
+// Splits red and blue apart towards the edges of the screen like a cheap lens, u_chromatic_aberration is the offset in pixels at the corners
+void main() {
+    vec2 offset = (v_uv - 0.5) * 2.0 * u_chromatic_aberration / u_resolution;
+    float red = texture(u_texture, v_uv + offset).r;
+    float green = texture(u_texture, v_uv).g;
+    float blue = texture(u_texture, v_uv - offset).b;
+    color = vec4(red, green, blue, 1.0);
+}
@@ -0,0 +1,12 @@
+#version 150
+
+in vec2 v_uv;
+out vec4 color;
+
+uniform sampler2D u_texture;
+uniform float u_gamma;
+
+void main() {
+    vec3 colour = max(texture(u_texture, v_uv).rgb, vec3(0.0));
+    color = vec4(pow(colour, vec3(1.0 / u_gamma)), 1.0);
+}
@@ -0,0 +1,10 @@
+#version 150
+
+out vec2 v_uv;
+
+// Draws a single triangle covering the whole screen, no vertex buffer needed
+void main() {
+    vec2 corner = vec2(float((gl_VertexID << 1) & 2), float(gl_VertexID & 2));
+    v_uv = corner;
+    gl_Position = vec4(corner * 2.0 - 1.0, 0.0, 1.0);
+}
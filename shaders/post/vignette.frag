@@ -0,0 +1,15 @@
+#version 150
+
+in vec2 v_uv;
+out vec4 color;
+
+uniform sampler2D u_texture;
+uniform vec2 u_resolution;
+uniform float u_vignette;
+
+// Darkens the corners, u_vignette is how dark they get
+void main() {
+    vec2 centred = (v_uv - 0.5) * vec2(u_resolution.x / u_resolution.y, 1.0);
+    float amount = smoothstep(0.3, 1.0, length(centred)) * u_vignette;
+    color = vec4(texture(u_texture, v_uv).rgb * (1.0 - amount), 1.0);
+}
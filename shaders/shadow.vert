@@ -0,0 +1,12 @@
+#version 150
+
+in vec3 position;
+#include "instance.glsl"
+
+uniform mat4 light_matrix;
+uniform mat4 model;
+
+void main() {
+    vec4 world = model * vec4(position, 1.0);
+    gl_Position = light_matrix * vec4(instance_to_world(world.xyz), 1.0);
+}
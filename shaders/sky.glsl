@@ -0,0 +1,62 @@
+// Shared by the background and anything that fades into it with fog, so the fog colour always matches what is behind
+
+uniform int u_sky_mode; // 0 solid, 1 gradient, 2 procedural sky, 3 skybox
+uniform samplerCube u_environment;
+uniform vec3 u_background;
+uniform vec3 u_background_top;
+uniform vec4 u_sun; // xyz is the direction towards the sun, w is 1.0 if there is a sun
+
+uniform int u_fog_mode; // 0 none, 1 linear, 2 exponential, 3 exponential squared
+uniform float u_fog_start;
+uniform float u_fog_end;
+uniform float u_fog_density;
+
+vec3 sky_colour(vec3 direction) {
+    direction = normalize(direction);
+
+    if (u_sky_mode == 3) {
+        // Cubemaps are left handed, flipping z stops the skybox from being mirrored
+        return texture(u_environment, vec3(direction.xy, -direction.z)).rgb;
+    }
+
+    if (u_sky_mode == 1) {
+        return mix(u_background, u_background_top, direction.y * 0.5 + 0.5);
+    }
+
+    if (u_sky_mode == 2) {
+        vec3 zenith = vec3(0.22, 0.42, 0.82);
+        vec3 horizon = vec3(0.72, 0.82, 0.95);
+        vec3 ground = vec3(0.32, 0.30, 0.28);
+
+        vec3 colour;
+        if (direction.y > 0.0) {
+            colour = mix(horizon, zenith, pow(direction.y, 0.5));
+        } else {
+            colour = mix(horizon, ground, pow(-direction.y, 0.3));
+        }
+
+        if (u_sun.w > 0.5) {
+            float sun_amount = max(dot(direction, normalize(u_sun.xyz)), 0.0);
+            colour += vec3(1.0, 0.8, 0.5) * pow(sun_amount, 8.0) * 0.3; // glow
+            colour += vec3(1.0, 0.95, 0.85) * smoothstep(0.9995, 0.9998, sun_amount); // disc
+        }
+        return colour;
+    }
+
+    return u_background;
+}
+
+// How much of the sky colour covers something this far away, from 0.0 to 1.0
+float fog_amount(float distance) {
+    if (u_fog_mode == 1) {
+        return clamp((distance - u_fog_start) / (u_fog_end - u_fog_start), 0.0, 1.0);
+    }
+    if (u_fog_mode == 2) {
+        return 1.0 - exp(-u_fog_density * distance);
+    }
+    if (u_fog_mode == 3) {
+        float amount = u_fog_density * distance;
+        return 1.0 - exp(-amount * amount);
+    }
+    return 0.0;
+}
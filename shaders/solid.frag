@@ -0,0 +1,9 @@
+#version 150
+
+out vec4 color;
+
+uniform vec3 colour;
+
+void main() {
+    color = vec4(colour, 1.0);
+}
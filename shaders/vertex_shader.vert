@@ -2,17 +2,21 @@
 
 in vec3 position;
 in vec3 normal;
+#include "instance.glsl"
 
 out vec3 v_normal;
+out vec3 v_position;
+out vec3 v_colour;
 
 uniform mat4 perspective;
 uniform mat4 view;
 uniform mat4 model;
-uniform mat4 yaw_matrix;
 
 
 void main() {
-    mat4 modelview = view * model * yaw_matrix;
-    v_normal = transpose(inverse(mat3(modelview))) * normal;
-    gl_Position = perspective * modelview * vec4(position, 1.0);
+    // Lighting is done in world space, so only the model transforms are applied to the normal
+    v_normal = rotate(instance_rotation, transpose(inverse(mat3(model))) * normal);
+    v_position = instance_to_world((model * vec4(position, 1.0)).xyz);
+    v_colour = instance_colour;
+    gl_Position = perspective * view * vec4(v_position, 1.0);
 }
@@ -0,0 +1,76 @@
+use rand::{rngs::StdRng, Rng};
+
+use crate::positions::Details;
+
+/// Seconds a teapot lives for, picked evenly in a range for each teapot
+pub fn lifetime(range: Option<(f32, f32)>, rng: &mut StdRng) -> Option<f32> {
+    range.map(|(min, max)| rng.gen_range(min..=max))
+}
+
+/// Starts despawning teapots that have outlived their lifetime
+pub fn expire(teapots: &mut [Details], time: f32) {
+    for teapot in teapots.iter_mut().filter(|teapot| teapot.dying.is_none()) {
+        if let Some(lifetime) = teapot.lifetime.filter(|&lifetime| time - teapot.spawned >= lifetime) {
+            teapot.dying = Some(teapot.spawned + lifetime);
+        }
+    }
+}
+
+/// Changes how teapots look as they get older
+#[derive(Copy, Clone, Debug)]
+pub struct Aging {
+    /// Colour teapots turn at the end of their life
+    pub colour: Option<[f32; 3]>,
+    /// Size at the end of their life, 1 keeps them the same size
+    pub scale: f32,
+    /// Seconds counted as a whole life for teapots without a lifetime
+    pub span: f32,
+}
+
+impl Aging {
+    /// How far through its life `teapot` is at `time`, from 0 to 1
+    pub fn life(&self, teapot: &Details, time: f32) -> f32 {
+        let span = teapot.lifetime.unwrap_or(self.span);
+        if span <= 0.0 {
+            return 1.0;
+        }
+        ((time - teapot.spawned) / span).clamp(0.0, 1.0)
+    }
+
+    pub fn colour(&self, teapot: &Details, time: f32) -> [f32; 3] {
+        match self.colour {
+            Some(old) => {
+                let t = self.life(teapot, time);
+                [0, 1, 2].map(|i| teapot.colour[i] + (old[i] - teapot.colour[i]) * t)
+            },
+            None => teapot.colour,
+        }
+    }
+
+    /// Multiplies the size of `teapot`
+    pub fn scale(&self, teapot: &Details, time: f32) -> f32 {
+        1.0 + (self.scale - 1.0) * self.life(teapot, time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::positions::{Teapot, Teapots};
+
+    #[test]
+    fn teapots_expire_at_the_end_of_their_lifetime() {
+        let mut teapots = Teapots::from(vec![
+            Teapot { spawned: 1.0, lifetime: Some(2.0), ..Teapot::new([0.0; 3], [0.0; 3]) },
+            Teapot { spawned: 1.0, lifetime: Some(5.0), ..Teapot::new([0.0; 3], [0.0; 3]) },
+            Teapot { spawned: 1.0, ..Teapot::new([0.0; 3], [0.0; 3]) },
+        ]).details;
+        expire(&mut teapots, 3.5);
+        let dying: Vec<_> = teapots.iter().map(|teapot| teapot.dying).collect();
+        assert_eq!(dying, [Some(3.0), None, None]);
+
+        let aging = Aging { colour: Some([1.0, 1.0, 1.0]), scale: 3.0, span: 10.0 };
+        assert_eq!(aging.colour(&teapots[1], 3.5), [0.5, 0.5, 0.5]);
+        assert_eq!(aging.scale(&teapots[2], 6.0), 2.0);
+    }
+}
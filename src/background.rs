@@ -0,0 +1,123 @@
+use glium::{uniforms::{UniformValue, Uniforms}, Surface};
+
+use crate::{shader, skybox};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum SkyMode {
+    /// A single colour
+    Solid,
+    /// Fades from the background colour at the bottom to the top colour straight up
+    Gradient,
+    /// Blue sky with a sun where the first directional light is
+    Sky,
+    /// Set by loading a skybox with --skybox
+    #[value(skip)]
+    Skybox,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum FogMode {
+    None,
+    /// Fades in between the fog start and end distances
+    Linear,
+    /// Gets thicker with distance, controlled by the fog density
+    Exp,
+    /// Like exp but stays clear for longer before thickening quickly
+    Exp2,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Fog {
+    pub mode: FogMode,
+    /// Distance where linear fog starts
+    pub start: f32,
+    /// Distance where linear fog completely hides things
+    pub end: f32,
+    /// Thickness of exponential fog
+    pub density: f32,
+}
+
+/// What is drawn behind the teapots, and the fog that fades them into it
+pub struct Background {
+    pub sky: SkyMode,
+    pub colour: [f32; 3],
+    pub top_colour: [f32; 3],
+    pub fog: Fog,
+    /// The skybox, also used for reflections
+    environment: glium::texture::Cubemap,
+    program: glium::Program,
+}
+
+impl Background {
+    /// Uses the skybox as the sky if one is given
+    pub fn new(display: &impl glium::backend::Facade, sky: SkyMode, colour: [f32; 3], top_colour: [f32; 3], fog: Fog, skybox: Option<glium::texture::Cubemap>) -> Background {
+        Background {
+            sky: if skybox.is_some() { SkyMode::Skybox } else { sky },
+            colour,
+            top_colour,
+            fog,
+            environment: skybox.unwrap_or_else(|| skybox::empty(display)),
+            program: shader::program(display, include_str!("../shaders/background.vert"), include_str!("../shaders/background.frag")),
+        }
+    }
+
+    /// Clears the target and draws the sky, `sun` is the direction towards the first directional light
+    pub fn draw(&self, target: &mut impl Surface, view: [[f32; 4]; 4], perspective: [[f32; 4]; 4], sun: Option<[f32; 3]>) {
+        target.clear_color_and_depth((self.colour[0], self.colour[1], self.colour[2], 1.0), 1.0);
+
+        if self.sky != SkyMode::Solid {
+            target.draw(glium::vertex::EmptyVertexAttributes { len: 3 }, glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+                        &self.program, &self.uniforms(sun, glium::uniform! { view: view, perspective: perspective }),
+                        &Default::default()).unwrap();
+        }
+    }
+
+    /// Adds the sky and fog settings to other uniforms
+    pub fn uniforms<U: Uniforms>(&self, sun: Option<[f32; 3]>, rest: U) -> BackgroundUniforms<'_, U> {
+        BackgroundUniforms { background: self, sun, rest }
+    }
+}
+
+pub struct BackgroundUniforms<'a, U> {
+    background: &'a Background,
+    sun: Option<[f32; 3]>,
+    rest: U,
+}
+
+impl<U: Uniforms> Uniforms for BackgroundUniforms<'_, U> {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut output: F) {
+        let background = self.background;
+        let sky_mode = match background.sky {
+            SkyMode::Solid => 0,
+            SkyMode::Gradient => 1,
+            SkyMode::Sky => 2,
+            SkyMode::Skybox => 3,
+        };
+        let fog_mode = match background.fog.mode {
+            FogMode::None => 0,
+            FogMode::Linear => 1,
+            FogMode::Exp => 2,
+            FogMode::Exp2 => 3,
+        };
+        let sun = match self.sun {
+            Some([x, y, z]) => [x, y, z, 1.0],
+            None => [0.0; 4],
+        };
+
+        output("u_sky_mode", UniformValue::SignedInt(sky_mode));
+        output("u_background", UniformValue::Vec3(background.colour));
+        output("u_background_top", UniformValue::Vec3(background.top_colour));
+        output("u_sun", UniformValue::Vec4(sun));
+        let behavior = glium::uniforms::SamplerBehavior {
+            minify_filter: glium::uniforms::MinifySamplerFilter::Linear,
+            magnify_filter: glium::uniforms::MagnifySamplerFilter::Linear,
+            .. Default::default()
+        };
+        output("u_environment", UniformValue::Cubemap(&background.environment, Some(behavior)));
+        output("u_fog_mode", UniformValue::SignedInt(fog_mode));
+        output("u_fog_start", UniformValue::Float(background.fog.start));
+        output("u_fog_end", UniformValue::Float(background.fog.end));
+        output("u_fog_density", UniformValue::Float(background.fog.density));
+        self.rest.visit_values(output);
+    }
+}
@@ -0,0 +1,166 @@
+use crate::mesh::Mesh;
+
+/// Control points of the Utah teapot, from Martin Newell's original data, with z up
+const CONTROL_POINTS: [[f32; 3]; 127] = [
+    [0.2, 0.0, 2.7], [0.2, -0.112, 2.7], [0.112, -0.2, 2.7], [0.0, -0.2, 2.7],
+    [1.3375, 0.0, 2.53125], [1.3375, -0.749, 2.53125], [0.749, -1.3375, 2.53125], [0.0, -1.3375, 2.53125],
+    [1.4375, 0.0, 2.53125], [1.4375, -0.805, 2.53125], [0.805, -1.4375, 2.53125], [0.0, -1.4375, 2.53125],
+    [1.5, 0.0, 2.4], [1.5, -0.84, 2.4], [0.84, -1.5, 2.4], [0.0, -1.5, 2.4],
+    [1.75, 0.0, 1.875], [1.75, -0.98, 1.875], [0.98, -1.75, 1.875], [0.0, -1.75, 1.875],
+    [2.0, 0.0, 1.35], [2.0, -1.12, 1.35], [1.12, -2.0, 1.35], [0.0, -2.0, 1.35],
+    [2.0, 0.0, 0.9], [2.0, -1.12, 0.9], [1.12, -2.0, 0.9], [0.0, -2.0, 0.9],
+    [-2.0, 0.0, 0.9], [2.0, 0.0, 0.45], [2.0, -1.12, 0.45], [1.12, -2.0, 0.45],
+    [0.0, -2.0, 0.45], [1.5, 0.0, 0.225], [1.5, -0.84, 0.225], [0.84, -1.5, 0.225],
+    [0.0, -1.5, 0.225], [1.5, 0.0, 0.15], [1.5, -0.84, 0.15], [0.84, -1.5, 0.15],
+    [0.0, -1.5, 0.15], [-1.6, 0.0, 2.025], [-1.6, -0.3, 2.025], [-1.5, -0.3, 2.25],
+    [-1.5, 0.0, 2.25], [-2.3, 0.0, 2.025], [-2.3, -0.3, 2.025], [-2.5, -0.3, 2.25],
+    [-2.5, 0.0, 2.25], [-2.7, 0.0, 2.025], [-2.7, -0.3, 2.025], [-3.0, -0.3, 2.25],
+    [-3.0, 0.0, 2.25], [-2.7, 0.0, 1.8], [-2.7, -0.3, 1.8], [-3.0, -0.3, 1.8],
+    [-3.0, 0.0, 1.8], [-2.7, 0.0, 1.575], [-2.7, -0.3, 1.575], [-3.0, -0.3, 1.35],
+    [-3.0, 0.0, 1.35], [-2.5, 0.0, 1.125], [-2.5, -0.3, 1.125], [-2.65, -0.3, 0.9375],
+    [-2.65, 0.0, 0.9375], [-2.0, -0.3, 0.9], [-1.9, -0.3, 0.6], [-1.9, 0.0, 0.6],
+    [1.7, 0.0, 1.425], [1.7, -0.66, 1.425], [1.7, -0.66, 0.6], [1.7, 0.0, 0.6],
+    [2.6, 0.0, 1.425], [2.6, -0.66, 1.425], [3.1, -0.66, 0.825], [3.1, 0.0, 0.825],
+    [2.3, 0.0, 2.1], [2.3, -0.25, 2.1], [2.4, -0.25, 2.025], [2.4, 0.0, 2.025],
+    [2.7, 0.0, 2.4], [2.7, -0.25, 2.4], [3.3, -0.25, 2.4], [3.3, 0.0, 2.4],
+    [2.8, 0.0, 2.475], [2.8, -0.25, 2.475], [3.525, -0.25, 2.49375], [3.525, 0.0, 2.49375],
+    [2.9, 0.0, 2.475], [2.9, -0.15, 2.475], [3.45, -0.15, 2.5125], [3.45, 0.0, 2.5125],
+    [2.8, 0.0, 2.4], [2.8, -0.15, 2.4], [3.2, -0.15, 2.4], [3.2, 0.0, 2.4],
+    [0.0, 0.0, 3.15], [0.8, 0.0, 3.15], [0.8, -0.45, 3.15], [0.45, -0.8, 3.15],
+    [0.0, -0.8, 3.15], [0.0, 0.0, 2.85], [1.4, 0.0, 2.4], [1.4, -0.784, 2.4],
+    [0.784, -1.4, 2.4], [0.0, -1.4, 2.4], [0.4, 0.0, 2.55], [0.4, -0.224, 2.55],
+    [0.224, -0.4, 2.55], [0.0, -0.4, 2.55], [1.3, 0.0, 2.55], [1.3, -0.728, 2.55],
+    [0.728, -1.3, 2.55], [0.0, -1.3, 2.55], [1.3, 0.0, 2.4], [1.3, -0.728, 2.4],
+    [0.728, -1.3, 2.4], [0.0, -1.3, 2.4], [0.0, 0.0, 0.0], [1.425, -0.798, 0.0],
+    [1.5, 0.0, 0.075], [1.425, 0.0, 0.0], [0.798, -1.425, 0.0], [0.0, -1.5, 0.075],
+    [0.0, -1.425, 0.0], [1.5, -0.84, 0.075], [0.84, -1.5, 0.075],
+];
+
+/// One quarter (or half, for the handle and spout) of each part of the teapot, the rest is made by mirroring these.
+/// With the mirrored copies this makes the 32 patches of the original teapot
+const PATCHES: [(Part, [usize; 16]); 10] = [
+    (Part::Quarter, [102, 103, 104, 105, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]),
+    (Part::Quarter, [12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27]),
+    (Part::Quarter, [24, 25, 26, 27, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40]),
+    (Part::Quarter, [96, 96, 96, 96, 97, 98, 99, 100, 101, 101, 101, 101, 0, 1, 2, 3]),
+    (Part::Quarter, [0, 1, 2, 3, 106, 107, 108, 109, 110, 111, 112, 113, 114, 115, 116, 117]),
+    (Part::Quarter, [118, 118, 118, 118, 124, 122, 119, 121, 123, 126, 125, 120, 40, 39, 38, 37]),
+    (Part::Half, [41, 42, 43, 44, 45, 46, 47, 48, 49, 50, 51, 52, 53, 54, 55, 56]),
+    (Part::Half, [53, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63, 64, 28, 65, 66, 67]),
+    (Part::Half, [68, 69, 70, 71, 72, 73, 74, 75, 76, 77, 78, 79, 80, 81, 82, 83]),
+    (Part::Half, [80, 81, 82, 83, 84, 85, 86, 87, 88, 89, 90, 91, 92, 93, 94, 95]),
+];
+
+/// Scales the teapot to the same size as the model in teapot.rs
+const SCALE: f32 = 28.4;
+/// Moves the teapot down to the same height as the model in teapot.rs
+const HEIGHT: f32 = 1.4;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Part {
+    /// Round parts, mirrored across both x and y into four copies
+    Quarter,
+    /// The handle and spout, mirrored across y into two copies
+    Half,
+}
+
+/// Cubic Bernstein polynomials and their derivatives at `t`
+fn bernstein(t: f32) -> ([f32; 4], [f32; 4]) {
+    let s = 1.0 - t;
+    (
+        [s * s * s, 3.0 * t * s * s, 3.0 * t * t * s, t * t * t],
+        [-3.0 * s * s, 3.0 * s * s - 6.0 * t * s, 6.0 * t * s - 3.0 * t * t, 3.0 * t * t],
+    )
+}
+
+/// Position and the derivatives along u and v of a bicubic patch
+fn evaluate(points: &[[[f32; 3]; 4]; 4], u: f32, v: f32) -> ([f32; 3], [f32; 3], [f32; 3]) {
+    let (bu, du) = bernstein(u);
+    let (bv, dv) = bernstein(v);
+
+    let mut position = [0.0; 3];
+    let mut tangent_u = [0.0; 3];
+    let mut tangent_v = [0.0; 3];
+    for (i, row) in points.iter().enumerate() {
+        for (j, point) in row.iter().enumerate() {
+            for axis in 0..3 {
+                position[axis] += bu[i] * bv[j] * point[axis];
+                tangent_u[axis] += du[i] * bv[j] * point[axis];
+                tangent_v[axis] += bu[i] * dv[j] * point[axis];
+            }
+        }
+    }
+    (position, tangent_u, tangent_v)
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn normalize(v: [f32; 3]) -> Option<[f32; 3]> {
+    let length = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    (length > 1e-6).then(|| v.map(|x| x / length))
+}
+
+/// Outwards normal of the patch at u, v. Where the patch is squashed to a point (the top of the lid and the middle of the bottom)
+/// the derivatives are parallel, so the normal is taken from just next to it instead
+fn normal(points: &[[[f32; 3]; 4]; 4], u: f32, v: f32) -> [f32; 3] {
+    let (_, tangent_u, tangent_v) = evaluate(points, u, v);
+    normalize(cross(tangent_v, tangent_u)).unwrap_or_else(|| {
+        let nudge = |t: f32| if t < 0.5 { t + 1e-3 } else { t - 1e-3 };
+        let (_, tangent_u, tangent_v) = evaluate(points, nudge(u), nudge(v));
+        normalize(cross(tangent_v, tangent_u)).unwrap_or([0.0, 1.0, 0.0])
+    })
+}
+
+/// Generates the teapot from its Bezier patches with `tessellation` quads along each side of each patch.
+///
+/// Normals are worked out from the patch derivatives, and the UVs go around the teapot once for the round parts,
+/// with v going from one end of each patch to the other.
+pub fn mesh(tessellation: u32) -> Mesh {
+    let tessellation = tessellation.max(1);
+    let mut mesh = Mesh::default();
+
+    for (part, indices) in PATCHES {
+        // Mirroring flips which way the triangles face, so mirrored copies also swap the order of the columns to flip them back
+        let copies: &[([f32; 2], bool)] = match part {
+            Part::Quarter => &[([1.0, 1.0], false), ([1.0, -1.0], true), ([-1.0, -1.0], false), ([-1.0, 1.0], true)],
+            Part::Half => &[([1.0, 1.0], false), ([1.0, -1.0], true)],
+        };
+
+        for (copy, &([mirror_x, mirror_y], reversed)) in copies.iter().enumerate() {
+            let points: [[[f32; 3]; 4]; 4] = std::array::from_fn(|row| std::array::from_fn(|column| {
+                let column = if reversed { 3 - column } else { column };
+                let [x, y, z] = CONTROL_POINTS[indices[row * 4 + column]];
+                [x * mirror_x, y * mirror_y, z]
+            }));
+
+            let first = mesh.positions.len() as u32;
+            for i in 0..=tessellation {
+                for j in 0..=tessellation {
+                    let u = i as f32 / tessellation as f32;
+                    let v = j as f32 / tessellation as f32;
+                    let (position, _, _) = evaluate(&points, u, v);
+                    let normal = normal(&points, u, v);
+
+                    // The data has z up, the teapot is drawn with y up
+                    let [x, y, z] = position;
+                    mesh.positions.push([x * SCALE, (z - HEIGHT) * SCALE, -y * SCALE]);
+                    mesh.normals.push([normal[0], normal[2], -normal[1]]);
+                    let around = if reversed { 1.0 - v } else { v };
+                    mesh.uvs.push([(copy as f32 + around) / copies.len() as f32, 1.0 - u]);
+                }
+            }
+
+            let row = tessellation + 1;
+            for i in 0..tessellation {
+                for j in 0..tessellation {
+                    let corner = first + i * row + j;
+                    mesh.indices.extend([corner, corner + row, corner + row + 1, corner, corner + row + 1, corner + 1]);
+                }
+            }
+        }
+    }
+
+    mesh
+}
@@ -0,0 +1,25 @@
+/// Parses `#RRGGBB` into red, green and blue from 0 to 1
+pub fn hex_to_rgb(hex: &str) -> Result<[f32; 3], &'static str> {
+    if hex.len() != 7 || !hex.starts_with('#') || !hex.is_ascii() {
+        return Err("Invalid hex format");
+    }
+
+    let r = u8::from_str_radix(&hex[1..3], 16).map_err(|_| "Invalid hex value")?;
+    let g = u8::from_str_radix(&hex[3..5], 16).map_err(|_| "Invalid hex value")?;
+    let b = u8::from_str_radix(&hex[5..7], 16).map_err(|_| "Invalid hex value")?;
+
+    Ok([r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typos_are_errors() {
+        assert_eq!(hex_to_rgb("#FF0000"), Ok([1.0, 0.0, 0.0]));
+        for typo in ["FF0000", "#FF000", "#FF00G0", "#FFé00"] {
+            assert!(hex_to_rgb(typo).is_err(), "{}", typo);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use std::ffi::OsString;
+
+/// Turns a TOML config file into command line arguments.
+///
+/// Every key is the long name of a command line option, e.g. `follow_speed = 2.0` or `follow-speed = 2.0` is the same as `--follow-speed 2.0`.
+/// `true` booleans become flags and arrays repeat the option once for every element, which is how several lights can be given.
+/// Options named in `skip` are left out.
+fn config_to_args(text: &str, skip: &[String]) -> Result<Vec<OsString>, String> {
+    let table: toml::Table = text.parse().map_err(|e| format!("{}", e))?;
+    let mut args = Vec::new();
+
+    for (key, value) in table {
+        let name = key.replace('_', "-");
+        if skip.contains(&name) {
+            continue;
+        }
+        let flag = format!("--{}", name);
+        let values = match value {
+            toml::Value::Array(values) => values,
+            value => vec![value],
+        };
+
+        for value in values {
+            match value {
+                toml::Value::Boolean(true) => args.push(flag.clone().into()),
+                toml::Value::Boolean(false) => (),
+                toml::Value::String(string) => args.extend([flag.clone().into(), string.into()]),
+                toml::Value::Integer(_) | toml::Value::Float(_) => args.extend([flag.clone().into(), value.to_string().into()]),
+                _ => return Err(format!("Unsupported value for \"{}\", use a string, number, boolean or an array of them", key)),
+            }
+        }
+    }
+
+    Ok(args)
+}
+
+/// Long names of the options given in `args` that add to a list each time they are given, like `--light`
+fn lists_given(args: &[OsString], command: &clap::Command) -> Vec<String> {
+    args.iter().skip(1)
+        .map(|arg| arg.to_string_lossy())
+        .take_while(|arg| arg != "--")
+        .filter_map(|arg| {
+            let option = match arg.strip_prefix("--") {
+                Some(long) => {
+                    let long = long.split('=').next().unwrap_or(long);
+                    command.get_arguments().find(|option| option.get_long() == Some(long))
+                },
+                None => {
+                    let short = arg.strip_prefix('-')?.chars().next()?;
+                    command.get_arguments().find(|option| option.get_short() == Some(short))
+                },
+            }?;
+            matches!(option.get_action(), clap::ArgAction::Append).then(|| option.get_long().map(str::to_string))?
+        })
+        .collect()
+}
+
+/// The command line arguments with the ones from `--config <file>` inserted before them, so the command line overrides the config file.
+/// A list option like `--light` on the command line replaces the config file's list instead of adding to it
+pub fn args(command: &clap::Command) -> Vec<OsString> {
+    let mut args: Vec<OsString> = std::env::args_os().collect();
+
+    let mut path = None;
+    for (i, arg) in args.iter().enumerate() {
+        let arg = arg.to_string_lossy();
+        if arg == "--config" {
+            path = args.get(i + 1).cloned();
+        } else if let Some(value) = arg.strip_prefix("--config=") {
+            path = Some(value.into());
+        }
+    }
+
+    if let Some(path) = path {
+        let config_args = std::fs::read_to_string(&path)
+            .map_err(|e| format!("{}", e))
+            .and_then(|text| config_to_args(&text, &lists_given(&args, command)));
+
+        match config_args {
+            Ok(config_args) => {
+                args.splice(1..1, config_args);
+            },
+            Err(e) => {
+                eprintln!("Failed to load config {}: {}", path.to_string_lossy(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_on_the_command_line_replace_the_config() {
+        let command = clap::Command::new("teapot")
+            .arg(clap::Arg::new("light").short('l').long("light").action(clap::ArgAction::Append))
+            .arg(clap::Arg::new("range").long("range"));
+        let text = "light = [\"point:0,0,0\"]\nrange = 3";
+        let given = |args: &[&str]| lists_given(&args.iter().map(OsString::from).collect::<Vec<_>>(), &command);
+        assert_eq!(config_to_args(text, &given(&["teapot", "--range", "5"])).unwrap().len(), 4);
+        for args in [["teapot", "--light", "directional:0,1,0"], ["teapot", "-l", "directional:0,1,0"]] {
+            assert_eq!(config_to_args(text, &given(&args)).unwrap(), ["--range", "3"].map(OsString::from));
+        }
+        assert_eq!(given(&["teapot", "--light=point:1,1,1"]), ["light"]);
+    }
+}
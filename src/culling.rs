@@ -0,0 +1,91 @@
+/// The six planes of the camera's view volume, used to skip drawing teapots that are off screen
+pub struct Frustum {
+    /// Each plane is (normal, distance) with the normal pointing inside, so a point p is inside when dot(normal, p) + distance >= 0
+    planes: [[f32; 4]; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes from the combined view and perspective matrices
+    pub fn new(view: &[[f32; 4]; 4], perspective: &[[f32; 4]; 4]) -> Frustum {
+        let matrix = crate::matrices::multiply(perspective, view);
+        let row = |i: usize| [matrix[0][i], matrix[1][i], matrix[2][i], matrix[3][i]];
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        let add = |a: [f32; 4], b: [f32; 4], sign: f32| [0, 1, 2, 3].map(|i| a[i] + b[i] * sign);
+
+        let planes = [
+            add(w, x, 1.0),
+            add(w, x, -1.0),
+            add(w, y, 1.0),
+            add(w, y, -1.0),
+            add(w, z, 1.0),
+            add(w, z, -1.0),
+        ].map(|plane| {
+            let length = (plane[0] * plane[0] + plane[1] * plane[1] + plane[2] * plane[2]).sqrt();
+            plane.map(|v| v / length)
+        });
+
+        Frustum { planes }
+    }
+
+    /// Whether any part of a sphere could be visible.
+    ///
+    /// Spheres near the corners outside of two planes at once can be counted as visible, which only costs drawing them.
+    pub fn contains_sphere(&self, centre: &[f32; 3], radius: f32) -> bool {
+        self.planes.iter().all(|plane| {
+            plane[0] * centre[0] + plane[1] * centre[1] + plane[2] * centre[2] + plane[3] >= -radius
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrices;
+
+    fn frustum() -> Frustum {
+        let view = matrices::view_matrix(&[0.0, 0.0, 0.0], &[1.0, 0.0, 0.0], &[0.0, 1.0, 0.0]);
+        Frustum::new(&view, &matrices::perspective_for_size(800, 600))
+    }
+
+    #[test]
+    fn visible_set_matches_projection() {
+        let view = matrices::view_matrix(&[0.0, 0.0, 0.0], &[1.0, 0.0, 0.0], &[0.0, 1.0, 0.0]);
+        let perspective = matrices::perspective_for_size(800, 600);
+        let matrix = matrices::multiply(&perspective, &view);
+        let frustum = Frustum::new(&view, &perspective);
+
+        // Points (radius 0 spheres) are visible exactly when they land inside clip space
+        let mut visible = 0;
+        for x in -20..=20 {
+            for y in -20..=20 {
+                for z in -20..=20 {
+                    let point = [x as f32 * 3.1, y as f32 * 1.7, z as f32 * 2.3];
+                    let [cx, cy, cz, cw] = matrices::transform(&matrix, &point);
+                    let inside = cx.abs() <= cw && cy.abs() <= cw && cz.abs() <= cw;
+                    // Skip points so close to a plane that rounding could go either way
+                    let margin = [cw - cx.abs(), cw - cy.abs(), cw - cz.abs()].into_iter().fold(f32::MAX, |a, b| a.min(b.abs()));
+                    if margin < 1e-3 {
+                        continue;
+                    }
+                    assert_eq!(frustum.contains_sphere(&point, 0.0), inside, "{:?}", point);
+                    visible += inside as usize;
+                }
+            }
+        }
+        assert!(visible > 0);
+    }
+
+    #[test]
+    fn spheres_crossing_a_plane_are_visible() {
+        let frustum = frustum();
+        // Just behind the camera, but big enough to reach past the near plane
+        assert!(frustum.contains_sphere(&[-0.5, 0.0, 0.0], 1.0));
+        assert!(!frustum.contains_sphere(&[-2.0, 0.0, 0.0], 1.0));
+        // Off to the side, with the edge of the sphere poking into view
+        let edge = 10.0 * (std::f32::consts::PI / 6.0).tan();
+        assert!(frustum.contains_sphere(&[10.0, edge + 0.5, 0.0], 1.0));
+        assert!(!frustum.contains_sphere(&[10.0, edge + 2.0, 0.0], 1.0));
+        // Past the far plane
+        assert!(!frustum.contains_sphere(&[1030.0, 0.0, 0.0], 1.0));
+    }
+}
@@ -0,0 +1,275 @@
+use std::{cmp::Ordering, collections::{BTreeMap, BinaryHeap}};
+
+use crate::mesh::Mesh;
+
+/// How much more moving an open edge costs than moving a surface, keeps holes from growing as the mesh is simplified
+const BOUNDARY_WEIGHT: f64 = 1000.0;
+
+/// Sum of squared distances to a set of planes, stored as the upper triangle of a symmetric 4x4 matrix:
+/// aa ab ac ad bb bc bd cc cd dd
+type Quadric = [f64; 10];
+
+fn plane_quadric([a, b, c]: [f64; 3], d: f64, weight: f64) -> Quadric {
+    [a * a, a * b, a * c, a * d, b * b, b * c, b * d, c * c, c * d, d * d].map(|v| v * weight)
+}
+
+fn add(q: &Quadric, r: &Quadric) -> Quadric {
+    std::array::from_fn(|i| q[i] + r[i])
+}
+
+fn quadric_error(q: &Quadric, [x, y, z]: [f64; 3]) -> f64 {
+    q[0] * x * x + 2.0 * q[1] * x * y + 2.0 * q[2] * x * z + 2.0 * q[3] * x
+        + q[4] * y * y + 2.0 * q[5] * y * z + 2.0 * q[6] * y
+        + q[7] * z * z + 2.0 * q[8] * z
+        + q[9]
+}
+
+/// The point with the smallest error, None if there isn't a single best point (e.g. on a flat area)
+fn optimal_point(q: &Quadric) -> Option<[f64; 3]> {
+    let m = [[q[0], q[1], q[2]], [q[1], q[4], q[5]], [q[2], q[5], q[7]]];
+    let rhs = [-q[3], -q[6], -q[8]];
+    let det = |m: &[[f64; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+
+    let determinant = det(&m);
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+
+    // Cramer's rule
+    Some(std::array::from_fn(|column| {
+        let mut replaced = m;
+        for row in 0..3 {
+            replaced[row][column] = rhs[row];
+        }
+        det(&replaced) / determinant
+    }))
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn length(a: [f64; 3]) -> f64 {
+    dot(a, a).sqrt()
+}
+
+/// Merging vertex `b` into vertex `a`
+struct Collapse {
+    cost: f64,
+    a: usize,
+    b: usize,
+    /// Versions of the two vertices when this was worked out, it is out of date if either has changed since
+    versions: (u32, u32),
+    position: [f64; 3],
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    /// Reversed so the binary heap gives the cheapest collapse first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+            .then_with(|| (other.a, other.b).cmp(&(self.a, self.b)))
+    }
+}
+
+struct Decimator {
+    positions: Vec<[f64; 3]>,
+    normals: Vec<[f32; 3]>,
+    quadrics: Vec<Quadric>,
+    triangles: Vec<[usize; 3]>,
+    removed: Vec<bool>,
+    vertex_triangles: Vec<Vec<usize>>,
+    versions: Vec<u32>,
+}
+
+impl Decimator {
+    fn collapse(&self, a: usize, b: usize) -> Collapse {
+        let q = add(&self.quadrics[a], &self.quadrics[b]);
+        let midpoint = std::array::from_fn(|i| (self.positions[a][i] + self.positions[b][i]) * 0.5);
+
+        let position = optimal_point(&q)
+            // A far away optimum means the surface is nearly flat, where any of the simple choices is as good
+            .filter(|p| length(sub(*p, midpoint)) < length(sub(self.positions[a], self.positions[b])) * 2.0)
+            .unwrap_or_else(|| {
+                [self.positions[a], self.positions[b], midpoint].into_iter()
+                    .min_by(|p, r| quadric_error(&q, *p).total_cmp(&quadric_error(&q, *r)))
+                    .unwrap()
+            });
+
+        Collapse {
+            cost: quadric_error(&q, position),
+            a,
+            b,
+            versions: (self.versions[a], self.versions[b]),
+            position,
+        }
+    }
+
+    fn triangle_normal(&self, [a, b, c]: [usize; 3], moved: usize, position: [f64; 3]) -> [f64; 3] {
+        let point = |v: usize| if v == moved { position } else { self.positions[v] };
+        cross(sub(point(b), point(a)), sub(point(c), point(a)))
+    }
+
+    /// Whether moving `moved` to `position` turns any of its triangles (apart from ones shared with `other`) inside out
+    fn flips(&self, moved: usize, other: usize, position: [f64; 3]) -> bool {
+        self.vertex_triangles[moved].iter()
+            .filter(|t| !self.removed[**t] && !self.triangles[**t].contains(&other))
+            .any(|t| {
+                let triangle = self.triangles[*t];
+                let before = self.triangle_normal(triangle, usize::MAX, position);
+                let after = self.triangle_normal(triangle, moved, position);
+                dot(before, after) <= 0.0
+            })
+    }
+}
+
+/// Simplifies a mesh down to around `target_triangles` by collapsing edges, cheapest first.
+///
+/// Each vertex keeps track of the planes of the triangles around it (Garland and Heckbert's quadric error metric),
+/// so flat areas lose triangles before curved ones and the overall shape is kept.
+pub fn decimate(mesh: &Mesh, target_triangles: usize) -> Mesh {
+    let positions: Vec<[f64; 3]> = mesh.positions.iter().map(|p| p.map(|v| v as f64)).collect();
+    let triangles: Vec<[usize; 3]> = mesh.indices.chunks(3).map(|t| [t[0] as usize, t[1] as usize, t[2] as usize]).collect();
+
+    let mut quadrics = vec![[0.0; 10]; positions.len()];
+    let mut vertex_triangles = vec![Vec::new(); positions.len()];
+    // Every edge and the triangles using it, a BTreeMap keeps the result the same every run
+    let mut edges: BTreeMap<(usize, usize), Vec<usize>> = BTreeMap::new();
+
+    for (t, &[a, b, c]) in triangles.iter().enumerate() {
+        let normal = cross(sub(positions[b], positions[a]), sub(positions[c], positions[a]));
+        let area = length(normal);
+        if area > 0.0 {
+            let normal = normal.map(|v| v / area);
+            let q = plane_quadric(normal, -dot(normal, positions[a]), area * 0.5);
+            for v in [a, b, c] {
+                quadrics[v] = add(&quadrics[v], &q);
+            }
+        }
+
+        for (v, w) in [(a, b), (b, c), (c, a)] {
+            vertex_triangles[v].push(t);
+            edges.entry((v.min(w), v.max(w))).or_default().push(t);
+        }
+    }
+
+    // Open edges get a plane at right angles to the surface through them, so moving away from the edge is expensive
+    for (&(a, b), users) in &edges {
+        if let [t] = users[..] {
+            let [x, y, z] = triangles[t];
+            let surface = cross(sub(positions[y], positions[x]), sub(positions[z], positions[x]));
+            let edge = sub(positions[b], positions[a]);
+            let normal = cross(edge, surface);
+            let len = length(normal);
+            if len > 0.0 {
+                let normal = normal.map(|v| v / len);
+                let q = plane_quadric(normal, -dot(normal, positions[a]), BOUNDARY_WEIGHT * dot(edge, edge));
+                quadrics[a] = add(&quadrics[a], &q);
+                quadrics[b] = add(&quadrics[b], &q);
+            }
+        }
+    }
+
+    let mut decimator = Decimator {
+        normals: mesh.normals.clone(),
+        versions: vec![0; positions.len()],
+        removed: vec![false; triangles.len()],
+        positions,
+        quadrics,
+        triangles,
+        vertex_triangles,
+    };
+
+    let mut heap: BinaryHeap<Collapse> = edges.keys().map(|&(a, b)| decimator.collapse(a, b)).collect();
+    let mut dead = vec![false; decimator.positions.len()];
+    let mut live = decimator.triangles.len();
+
+    while live > target_triangles {
+        let Some(Collapse { a, b, versions, position, .. }) = heap.pop() else {
+            break;
+        };
+        if dead[a] || dead[b] || versions != (decimator.versions[a], decimator.versions[b]) {
+            continue;
+        }
+        if decimator.flips(a, b, position) || decimator.flips(b, a, position) {
+            continue;
+        }
+
+        let d = &mut decimator;
+        d.positions[a] = position;
+        d.quadrics[a] = add(&d.quadrics[a], &d.quadrics[b]);
+        let normal: [f32; 3] = std::array::from_fn(|i| d.normals[a][i] + d.normals[b][i]);
+        let len = (normal[0] * normal[0] + normal[1] * normal[1] + normal[2] * normal[2]).sqrt();
+        if len > 0.0 {
+            d.normals[a] = normal.map(|v| v / len);
+        }
+
+        for t in std::mem::take(&mut d.vertex_triangles[b]) {
+            if d.removed[t] {
+                continue;
+            }
+            let triangle = &mut d.triangles[t];
+            if triangle.contains(&a) {
+                d.removed[t] = true;
+                live -= 1;
+            } else {
+                for v in triangle.iter_mut().filter(|v| **v == b) {
+                    *v = a;
+                }
+                d.vertex_triangles[a].push(t);
+            }
+        }
+        let removed = &d.removed;
+        d.vertex_triangles[a].retain(|t| !removed[*t]);
+        dead[b] = true;
+        d.versions[a] += 1;
+
+        let mut neighbours: Vec<usize> = d.vertex_triangles[a].iter()
+            .flat_map(|t| d.triangles[*t])
+            .filter(|v| *v != a)
+            .collect();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        for neighbour in neighbours {
+            heap.push(decimator.collapse(a, neighbour));
+        }
+    }
+
+    let mut simplified = Mesh {
+        positions: decimator.positions.iter().map(|p| p.map(|v| v as f32)).collect(),
+        normals: decimator.normals,
+        // The kept vertex keeps its UV, stretching the texture a little over the triangles around it
+        uvs: mesh.uvs.clone(),
+        indices: decimator.triangles.iter().zip(&decimator.removed)
+            .filter(|(_, removed)| !**removed)
+            .flat_map(|(triangle, _)| triangle.map(|v| v as u32))
+            .collect(),
+    };
+    simplified.compact();
+    simplified
+}
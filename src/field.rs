@@ -0,0 +1,213 @@
+use crate::lighting::parse_vector;
+
+/// Where a field is centred
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Place {
+    At([f32; 3]),
+    /// Moves with the camera
+    Camera,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Kind {
+    /// Pulls teapots towards the centre at `strength` times their distance per second, or pushes them away with a negative strength
+    Attract,
+    /// Swirls teapots around `axis` through the centre at `strength` radians per second
+    Vortex { axis: [f32; 3] },
+    /// Blows teapots along at `strength` times `direction` per second
+    Wind { direction: [f32; 3] },
+    /// Curl noise that stirs teapots around without bunching them up, with swirls about `size` wide that move with the centre
+    Turbulence { size: f32 },
+}
+
+/// Moves teapots that aren't flying to a formation
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Field {
+    pub kind: Kind,
+    pub place: Place,
+    pub strength: f32,
+    /// Distance from the centre where the field is half as strong, None for a field that is as strong everywhere
+    pub falloff: Option<f32>,
+}
+
+impl Field {
+    /// Pulls teapots towards the camera like the old follow mode
+    pub fn follow(speed: f32) -> Field {
+        Field { kind: Kind::Attract, place: Place::Camera, strength: speed, falloff: None }
+    }
+
+    /// How fast the field moves something at `point`
+    pub fn velocity(&self, point: [f32; 3], camera: [f32; 3], time: f32) -> [f32; 3] {
+        let centre = match self.place {
+            Place::At(centre) => centre,
+            Place::Camera => camera,
+        };
+        let to = [0, 1, 2].map(|i| point[i] - centre[i]);
+        let (velocity, distance) = match self.kind {
+            Kind::Attract => (to.map(|v| -v), length(to)),
+            Kind::Vortex { axis } => {
+                let along: f32 = (0..3).map(|i| to[i] * axis[i]).sum();
+                let around = [0, 1, 2].map(|i| to[i] - axis[i] * along);
+                (cross(axis, to), length(around))
+            },
+            Kind::Wind { direction } => (direction, length(to)),
+            Kind::Turbulence { size } => (curl_noise(to.map(|v| v / size), time), length(to)),
+        };
+        let scale = self.strength * self.falloff.map_or(1.0, |falloff| 1.0 / (1.0 + (distance / falloff).powi(2)));
+        velocity.map(|v| v * scale)
+    }
+}
+
+fn length(v: [f32; 3]) -> f32 {
+    v.iter().map(|v| v * v).sum::<f32>().sqrt()
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+/// Parses a field from the command line, in the format `kind:place:strength[:falloff[:x,y,z]]`, the falloff can be left empty
+///
+/// `kind` is `attract`, `repel`, `vortex` (x, y, z is the axis, up by default), `wind` (x, y, z is the direction, which is needed)
+/// or `turbulence` (with the size of the swirls instead of a falloff). `place` is `x,y,z` or `camera` to move with the camera
+pub fn parse(spec: &str) -> Result<Field, String> {
+    let parts: Vec<&str> = spec.split(':').collect();
+    if parts.len() < 3 {
+        return Err(format!("Expected kind:place:strength in field \"{}\"", spec));
+    }
+    let place = match parts[1] {
+        "camera" => Place::Camera,
+        vector => Place::At(parse_vector(vector)?),
+    };
+    let mut strength: f32 = parts[2].parse().map_err(|_| format!("Invalid field strength \"{}\"", parts[2]))?;
+    // Left empty for a field without a falloff that needs a direction
+    let falloff = parts.get(3).filter(|falloff| !falloff.is_empty())
+        .map(|falloff| falloff.parse::<f32>().map_err(|_| format!("Invalid field falloff \"{}\"", falloff)))
+        .transpose()?;
+    let vector = parts.get(4).map(|vector| parse_vector(vector)).transpose()?;
+    if parts.len() > 5 {
+        return Err(format!("Too many parts in field \"{}\"", spec));
+    }
+    let unit = |v: [f32; 3]| {
+        let length = length(v);
+        if length == 0.0 { Err(format!("Field direction can't be 0 in \"{}\"", spec)) } else { Ok(v.map(|v| v / length)) }
+    };
+
+    let kind = match parts[0] {
+        "attract" => Kind::Attract,
+        "repel" => {
+            strength = -strength;
+            Kind::Attract
+        },
+        "vortex" => Kind::Vortex { axis: unit(vector.unwrap_or([0.0, 1.0, 0.0]))? },
+        "wind" => Kind::Wind { direction: unit(vector.ok_or(format!("Wind needs a direction in \"{}\"", spec))?)? },
+        "turbulence" => {
+            let size = falloff.unwrap_or(8.0);
+            if size <= 0.0 {
+                return Err(format!("Turbulence size must be above 0 in \"{}\"", spec));
+            }
+            return Ok(Field { kind: Kind::Turbulence { size }, place, strength, falloff: None });
+        },
+        _ => return Err(format!("Unknown field kind in \"{}\", expected attract, repel, vortex, wind or turbulence", spec)),
+    };
+    Ok(Field { kind, place, strength, falloff })
+}
+
+/// Moves a teapot for `delta` seconds. With `physics` the fields speed it up instead of moving it directly
+pub fn apply(fields: &[Field], position: &mut [f32; 3], velocity: &mut [f32; 3], camera: [f32; 3], time: f32, delta: f32, physics: bool) {
+    if fields.is_empty() {
+        return;
+    }
+    let speed = fields.iter()
+        .map(|field| field.velocity(*position, camera, time))
+        .fold([0.0; 3], |a, b| [a[0] + b[0], a[1] + b[1], a[2] + b[2]]);
+    let moved = if physics { velocity } else { position };
+    for i in 0..3 {
+        moved[i] += speed[i] * delta;
+    }
+}
+
+/// Perlin noise's gradients, with four repeated to make 16
+const GRADIENTS: [[f32; 3]; 16] = [
+    [1.0, 1.0, 0.0], [-1.0, 1.0, 0.0], [1.0, -1.0, 0.0], [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0], [-1.0, 0.0, 1.0], [1.0, 0.0, -1.0], [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0], [0.0, -1.0, 1.0], [0.0, 1.0, -1.0], [0.0, -1.0, -1.0],
+    [1.0, 1.0, 0.0], [-1.0, 1.0, 0.0], [0.0, -1.0, 1.0], [0.0, -1.0, -1.0],
+];
+
+/// How fast three unrelated smooth random numbers change along each axis. Each is Perlin noise from about -1 to 1 that changes over about 1 unit
+fn noise_slopes(point: [f32; 3]) -> [[f32; 3]; 3] {
+    let cell = point.map(|v| v.floor());
+    let offset = [0, 1, 2].map(|i| point[i] - cell[i]);
+    let fade = offset.map(|t| t * t * t * (t * (t * 6.0 - 15.0) + 10.0));
+    let fade_slope = offset.map(|t| 30.0 * t * t * (t - 1.0) * (t - 1.0));
+    let cell = cell.map(|v| v as i32);
+
+    // A random gradient at each corner of the cube, blended together
+    let mut slopes = [[0.0; 3]; 3];
+    for corner in 0..8 {
+        let corner = [0, 1, 2].map(|i| (corner >> i) & 1);
+        let mut hash = 0x9E37_79B9u32;
+        for i in 0..3 {
+            hash = (hash ^ (cell[i] + corner[i]) as u32).wrapping_mul(0x85EB_CA6B);
+            hash ^= hash >> 13;
+        }
+        let weights = [0, 1, 2].map(|i| if corner[i] == 1 { fade[i] } else { 1.0 - fade[i] });
+        let weight_slopes = [0, 1, 2].map(|i| if corner[i] == 1 { fade_slope[i] } else { -fade_slope[i] });
+        let weight = weights[0] * weights[1] * weights[2];
+        let from = [0, 1, 2].map(|i| offset[i] - corner[i] as f32);
+
+        for (noise, slope) in slopes.iter_mut().enumerate() {
+            // Each noise takes its own 4 bits of the hash
+            let gradient = GRADIENTS[(hash >> (28 - 4 * noise)) as usize & 15];
+            let height: f32 = (0..3).map(|i| gradient[i] * from[i]).sum();
+            for i in 0..3 {
+                let others = weights[(i + 1) % 3] * weights[(i + 2) % 3];
+                slope[i] += weight * gradient[i] + weight_slopes[i] * others * height;
+            }
+        }
+    }
+    slopes
+}
+
+/// Curl of three noises, which flows without sources or sinks. It drifts slowly with `time`
+fn curl_noise(point: [f32; 3], time: f32) -> [f32; 3] {
+    let point = [point[0] + time * 0.1, point[1] - time * 0.07, point[2] + time * 0.05];
+    let [x, y, z] = noise_slopes(point);
+    [z[1] - y[2], x[2] - z[0], y[0] - x[1]]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turbulence_doesnt_bunch_teapots_up() {
+        let field = parse("turbulence:0,0,0:3:4").unwrap();
+        let step = 1e-2;
+        let mut largest = 0.0f32;
+        for i in 0..200 {
+            let point = [i as f32 * 0.37, (i as f32 * 0.61).sin() * 5.0, i as f32 * -0.23];
+            let velocity = field.velocity(point, [0.0; 3], 1.5);
+            largest = largest.max(length(velocity));
+            // Flow in and out of a small box around the point is the same
+            let divergence: f32 = (0..3).map(|axis| {
+                let mut ahead = point;
+                let mut behind = point;
+                ahead[axis] += step;
+                behind[axis] -= step;
+                (field.velocity(ahead, [0.0; 3], 1.5)[axis] - field.velocity(behind, [0.0; 3], 1.5)[axis]) / (2.0 * step)
+            }).sum();
+            assert!(divergence.abs() < 0.05, "divergence {} at {:?}", divergence, point);
+        }
+        assert!(largest > 0.5);
+    }
+
+    #[test]
+    fn following_the_camera_is_an_attractor() {
+        let mut position = [10.0, 0.0, 0.0];
+        apply(&[parse("attract:camera:2").unwrap()], &mut position, &mut [0.0; 3], [0.0, 4.0, 0.0], 0.0, 0.1, false);
+        assert_eq!(position, [8.0, 0.8, 0.0]);
+        assert_eq!(parse("attract:camera:2"), Ok(Field::follow(2.0)));
+    }
+}
@@ -0,0 +1,429 @@
+use std::{f32::consts::PI, path::Path};
+
+use rand::{rngs::StdRng, Rng};
+
+use crate::{font, positions::{Teapot, Teapots}};
+
+/// Where a teapot goes in a formation, and the colour it turns if the formation has one
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Target {
+    pub position: [f32; 3],
+    pub colour: Option<[f32; 3]>,
+}
+
+/// Shapes the teapots can fly into
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Layout {
+    /// Random places in --range, like when the program starts
+    Cloud,
+    Cube,
+    /// The surface of a sphere
+    Sphere,
+    /// A flat square facing the camera
+    Grid,
+    Helix,
+    /// The --text formation
+    Text,
+    /// The --image formation
+    Image,
+}
+
+impl Layout {
+    /// In the order of the number keys that switch to them
+    pub const ALL: [Layout; 7] = [Layout::Cloud, Layout::Cube, Layout::Sphere, Layout::Grid, Layout::Helix, Layout::Text, Layout::Image];
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Easing {
+    Linear,
+    /// Starts slowly
+    EaseIn,
+    /// Slows down at the end
+    EaseOut,
+    /// Starts and ends slowly
+    EaseInOut,
+}
+
+impl Easing {
+    /// Maps how far through a transition we are, from 0 to 1, to how far along their paths the teapots are
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => if t < 0.5 { 4.0 * t * t * t } else { 1.0 - (-2.0 * t + 2.0).powi(3) / 2.0 },
+        }
+    }
+}
+
+/// A layout to switch to at a time in seconds, parsed from `seconds:layout`
+#[derive(Copy, Clone, Debug)]
+pub struct Step {
+    pub time: f32,
+    pub layout: Layout,
+}
+
+pub fn parse_step(text: &str) -> Result<Step, String> {
+    let (time, layout) = text.split_once(':').ok_or("Expected seconds:layout, e.g. 5:sphere")?;
+    Ok(Step {
+        time: time.trim().parse().map_err(|_| format!("Invalid time \"{}\"", time))?,
+        layout: <Layout as clap::ValueEnum>::from_str(layout.trim(), true)?,
+    })
+}
+
+/// Where a teapot flies from and to during a transition
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Move {
+    pub from: [f32; 3],
+    pub to: [f32; 3],
+    pub from_colour: [f32; 3],
+    pub to_colour: [f32; 3],
+}
+
+/// The teapots flying from one layout to the next
+pub struct Transition {
+    pub layout: Layout,
+    start: f32,
+    duration: f32,
+    easing: Easing,
+}
+
+impl Transition {
+    /// Position and colour of a teapot along `path`, `time` is in seconds since the program started
+    pub fn place(&self, path: &Move, time: f32) -> ([f32; 3], [f32; 3]) {
+        let t = self.easing.apply((time - self.start) / self.duration.max(f32::EPSILON));
+        let lerp = |a: [f32; 3], b: [f32; 3]| [0, 1, 2].map(|i| a[i] + (b[i] - a[i]) * t);
+        (lerp(path.from, path.to), lerp(path.from_colour, path.to_colour))
+    }
+
+    pub fn finished(&self, time: f32) -> bool {
+        time >= self.start + self.duration
+    }
+}
+
+/// Builds the targets for each layout
+pub struct Formations {
+    pub text: Vec<Target>,
+    pub image: Vec<Target>,
+    pub spacing: f32,
+    /// Random teapots are placed between -range and range
+    pub range: f32,
+    /// Window size, to move formations far enough from the camera to fit on screen
+    pub screen: (u32, u32),
+    pub duration: f32,
+    pub easing: Easing,
+}
+
+impl Formations {
+    /// Places for `count` teapots, None for text and image layouts that weren't given
+    pub fn targets(&self, layout: Layout, count: usize, rng: &mut StdRng) -> Option<Vec<Target>> {
+        let count = count.max(1);
+        let spacing = self.spacing;
+        let points: Vec<[f32; 3]> = match layout {
+            Layout::Cloud => {
+                let range = -self.range..self.range;
+                return Some((0..count)
+                    .map(|_| Target { position: [0; 3].map(|_| rng.gen_range(range.clone())), colour: None })
+                    .collect());
+            },
+            Layout::Text | Layout::Image => {
+                let targets = if layout == Layout::Text { &self.text } else { &self.image };
+                return (!targets.is_empty()).then(|| targets.clone());
+            },
+            Layout::Cube => {
+                let side = (count as f32).cbrt().ceil() as usize;
+                (0..count).map(|i| [i % side, i / side % side, i / (side * side)].map(|v| v as f32 * spacing)).collect()
+            },
+            Layout::Sphere => {
+                // Fibonacci sphere, big enough that the teapots are about `spacing` apart
+                let radius = spacing * (count as f32 / (4.0 * PI)).sqrt();
+                let golden_angle = PI * (3.0 - 5.0f32.sqrt());
+                (0..count)
+                    .map(|i| {
+                        let y = 1.0 - 2.0 * (i as f32 + 0.5) / count as f32;
+                        let around = (1.0 - y * y).sqrt();
+                        let angle = i as f32 * golden_angle;
+                        [angle.cos() * around * radius, y * radius, angle.sin() * around * radius]
+                    })
+                    .collect()
+            },
+            Layout::Grid => {
+                let side = (count as f32).sqrt().ceil() as usize;
+                (0..count).map(|i| [(i % side) as f32 * spacing, (i / side) as f32 * spacing, 0.0]).collect()
+            },
+            Layout::Helix => {
+                // Teapots `spacing` apart along the spiral, with turns 3 teapots apart and about as tall as it is wide
+                let radius = (spacing * (3.0 * count as f32 / (4.0 * PI)).sqrt()).max(spacing * 2.0);
+                let step = spacing / radius;
+                (0..count)
+                    .map(|i| {
+                        let angle = i as f32 * step;
+                        [angle.cos() * radius, angle / (2.0 * PI) * spacing * 3.0, angle.sin() * radius]
+                    })
+                    .collect()
+            },
+        };
+
+        let mut targets = centred(points.into_iter().map(|position| Target { position, colour: None }).collect());
+        in_front(&mut targets, self.screen.0, self.screen.1);
+        Some(targets)
+    }
+
+    /// Starts the teapots flying to a layout at `time`. Teapots made by `spawn` are added if the layout has more places than there are teapots,
+    /// and each teapot is given the place that keeps the total distance travelled low. Returns None if the layout wasn't given
+    pub fn morph(&self, layout: Layout, teapots: &mut Teapots, time: f32, rng: &mut StdRng, mut spawn: impl FnMut(&mut StdRng) -> Teapot) -> Option<Transition> {
+        let mut targets = self.targets(layout, teapots.len(), rng)?;
+        while teapots.len() < targets.len() {
+            teapots.push(spawn(rng));
+        }
+        // Spare teapots go back to floating around
+        if targets.len() < teapots.len() {
+            targets.extend(self.targets(Layout::Cloud, teapots.len() - targets.len(), rng).unwrap());
+        }
+
+        let to: Vec<[f32; 3]> = targets.iter().map(|target| target.position).collect();
+        let assigned = assign(&teapots.position, &to);
+        for (index, target) in assigned.into_iter().enumerate() {
+            let target = targets[target];
+            let colour = teapots.details[index].colour;
+            teapots.path[index] = Some(Move {
+                from: teapots.position[index],
+                to: target.position,
+                from_colour: colour,
+                to_colour: target.colour.unwrap_or(colour),
+            });
+        }
+
+        Some(Transition { layout, start: time, duration: self.duration, easing: self.easing })
+    }
+}
+
+/// Centres a formation around the origin
+fn centred(mut targets: Vec<Target>) -> Vec<Target> {
+    let min = [0, 1, 2].map(|i| targets.iter().map(|t| t.position[i]).fold(f32::INFINITY, f32::min));
+    let max = [0, 1, 2].map(|i| targets.iter().map(|t| t.position[i]).fold(f32::NEG_INFINITY, f32::max));
+    for target in &mut targets {
+        target.position = [0, 1, 2].map(|i| target.position[i] - (min[i] + max[i]) / 2.0);
+    }
+    targets
+}
+
+/// A teapot for every pixel of the text in the built in font, `spacing` apart. Lines are split at new lines or `\n`
+pub fn text(text: &str, spacing: f32) -> Vec<Target> {
+    let mut points = Vec::new();
+    for (line, text) in text.replace("\\n", "\n").lines().enumerate() {
+        for (index, character) in text.chars().enumerate() {
+            let rows = font::glyph(character);
+            for row in 0..font::HEIGHT {
+                for column in 0..font::WIDTH {
+                    if font::pixel(&rows, column, row) {
+                        // One pixel gaps between characters and lines
+                        let x = index * (font::WIDTH + 1) + column;
+                        let y = line * (font::HEIGHT + 1) + row;
+                        points.push(Target { position: [x as f32 * spacing, -(y as f32) * spacing, 0.0], colour: None });
+                    }
+                }
+            }
+        }
+    }
+    centred(points)
+}
+
+/// A teapot for every pixel of an image coloured like the pixel, leaving out transparent ones.
+/// Images more than `width` pixels wide are shrunk to fit
+pub fn image(path: &Path, width: u32, spacing: f32) -> Result<Vec<Target>, String> {
+    let mut image = image::open(path).map_err(|e| format!("{}: {}", path.display(), e))?.into_rgba8();
+    if image.width() > width {
+        let height = (image.height() as f32 * width as f32 / image.width() as f32).round().max(1.0) as u32;
+        image = image::imageops::resize(&image, width, height, image::imageops::FilterType::Triangle);
+    }
+
+    let points: Vec<_> = image.enumerate_pixels()
+        .filter(|(_, _, pixel)| pixel[3] >= 128)
+        .map(|(x, y, pixel)| Target {
+            position: [x as f32 * spacing, -(y as f32) * spacing, 0.0],
+            colour: Some([0, 1, 2].map(|i| pixel[i] as f32 / 255.0)),
+        })
+        .collect();
+    if points.is_empty() {
+        return Err(format!("{} is completely transparent", path.display()));
+    }
+    Ok(centred(points))
+}
+
+/// Stands a formation centred on the origin, with x going right, y up and z towards the camera, in front of where the camera starts
+/// far enough away to fit on a screen of this size
+pub fn in_front(targets: &mut [Target], width: u32, height: u32) {
+    let size = [0, 1, 2].map(|i| targets.iter().map(|target| target.position[i].abs()).fold(0.0, f32::max) * 2.0);
+    let half_fov = (PI / 6.0).tan();
+    let aspect = width as f32 / height as f32;
+    // Bit of a margin so teapots at the edges aren't cut off
+    let distance = (size[0] / aspect).max(size[1]) / (2.0 * half_fov) * 1.2 + size[2] / 2.0 + 2.0;
+
+    // The camera looks along x with -z to its right
+    for target in targets {
+        let [x, y, z] = target.position;
+        target.position = [distance - z, y, -x];
+    }
+}
+
+/// Which of `to` each of `from` should go to so the total squared distance is as small as possible, both must be the same length.
+/// This is exact for a few hundred teapots, beyond that it is close
+pub fn assign(from: &[[f32; 3]], to: &[[f32; 3]]) -> Vec<usize> {
+    if from.len() <= EXACT_LIMIT {
+        hungarian(from, to)
+    } else {
+        approximate(from, to)
+    }
+}
+
+const EXACT_LIMIT: usize = 300;
+
+fn cost(a: [f32; 3], b: [f32; 3]) -> f32 {
+    (0..3).map(|i| (a[i] - b[i]) * (a[i] - b[i])).sum()
+}
+
+/// The Hungarian algorithm with potentials, O(n³)
+fn hungarian(from: &[[f32; 3]], to: &[[f32; 3]]) -> Vec<usize> {
+    let n = from.len();
+    // 1 based, with 0 standing for "no row" or "no column"
+    let mut row_potential = vec![0.0f64; n + 1];
+    let mut column_potential = vec![0.0f64; n + 1];
+    let mut column_row = vec![0usize; n + 1];
+    let mut way = vec![0usize; n + 1];
+
+    for row in 1..=n {
+        column_row[0] = row;
+        let mut column = 0;
+        let mut min_to = vec![f64::INFINITY; n + 1];
+        let mut used = vec![false; n + 1];
+        loop {
+            used[column] = true;
+            let current_row = column_row[column];
+            let mut delta = f64::INFINITY;
+            let mut next = 0;
+            for j in 1..=n {
+                if !used[j] {
+                    let reduced = cost(from[current_row - 1], to[j - 1]) as f64 - row_potential[current_row] - column_potential[j];
+                    if reduced < min_to[j] {
+                        min_to[j] = reduced;
+                        way[j] = column;
+                    }
+                    if min_to[j] < delta {
+                        delta = min_to[j];
+                        next = j;
+                    }
+                }
+            }
+            for j in 0..=n {
+                if used[j] {
+                    row_potential[column_row[j]] += delta;
+                    column_potential[j] -= delta;
+                } else {
+                    min_to[j] -= delta;
+                }
+            }
+            column = next;
+            if column_row[column] == 0 {
+                break;
+            }
+        }
+        // Flip the augmenting path
+        while column != 0 {
+            let previous = way[column];
+            column_row[column] = column_row[previous];
+            column = previous;
+        }
+    }
+
+    let mut assignment = vec![0; n];
+    for column in 1..=n {
+        assignment[column_row[column] - 1] = column - 1;
+    }
+    assignment
+}
+
+/// Pairs up both sets in the order of a Z-order curve through their bounding boxes, so teapots in one corner of the old layout go to
+/// the same corner of the new one, then swaps targets between teapots that are close in that order whenever it shortens the trip
+fn approximate(from: &[[f32; 3]], to: &[[f32; 3]]) -> Vec<usize> {
+    const WINDOW: usize = 16;
+    const PASSES: usize = 4;
+
+    let order = |points: &[[f32; 3]]| -> Vec<usize> {
+        let min = [0, 1, 2].map(|i| points.iter().map(|p| p[i]).fold(f32::INFINITY, f32::min));
+        let max = [0, 1, 2].map(|i| points.iter().map(|p| p[i]).fold(f32::NEG_INFINITY, f32::max));
+        let mut indices: Vec<usize> = (0..points.len()).collect();
+        indices.sort_by_key(|i| {
+            let cell = [0, 1, 2].map(|axis| ((points[*i][axis] - min[axis]) / (max[axis] - min[axis]).max(f32::EPSILON) * 1023.0) as u64);
+            // Interleaves the bits of the three axes
+            (0..10).fold(0u64, |code, bit| code | (0..3).fold(0, |bits, axis| bits | ((cell[axis] >> bit) & 1) << (bit * 3 + axis)))
+        });
+        indices
+    };
+
+    let from_order = order(from);
+    let to_order = order(to);
+    let mut assignment = vec![0; from.len()];
+    for (teapot, target) in from_order.iter().zip(&to_order) {
+        assignment[*teapot] = *target;
+    }
+
+    for _ in 0..PASSES {
+        let mut improved = false;
+        for a in 0..from_order.len() {
+            for b in a + 1..(a + WINDOW).min(from_order.len()) {
+                let (i, j) = (from_order[a], from_order[b]);
+                let now = cost(from[i], to[assignment[i]]) + cost(from[j], to[assignment[j]]);
+                let swapped = cost(from[i], to[assignment[j]]) + cost(from[j], to[assignment[i]]);
+                if swapped < now {
+                    assignment.swap(i, j);
+                    improved = true;
+                }
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+    assignment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn total(from: &[[f32; 3]], to: &[[f32; 3]], assignment: &[usize]) -> f32 {
+        from.iter().zip(assignment).map(|(a, b)| cost(*a, to[*b])).sum()
+    }
+
+    fn random_points(rng: &mut StdRng, count: usize) -> Vec<[f32; 3]> {
+        (0..count).map(|_| [0; 3].map(|_| rng.gen_range(-10.0..10.0))).collect()
+    }
+
+    #[test]
+    fn shuffled_targets_are_found_exactly() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let from = random_points(&mut rng, 200);
+        let mut to = from.clone();
+        to.reverse();
+
+        let assignment = assign(&from, &to);
+        assert_eq!(total(&from, &to, &assignment), 0.0);
+    }
+
+    #[test]
+    fn every_target_is_used_once() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for count in [50, 2000] {
+            let (from, to) = (random_points(&mut rng, count), random_points(&mut rng, count));
+            let mut assignment = assign(&from, &to);
+            // Much shorter trips than going to the targets in the order they were given
+            let identity: Vec<usize> = (0..count).collect();
+            assert!(total(&from, &to, &assignment) < total(&from, &to, &identity) * 0.5);
+            assignment.sort();
+            assert_eq!(assignment, identity);
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use glium::implement_vertex;
+
+use crate::{instance::Instance, matrices};
+
+/// Half the width of the ground, it follows the camera so this only has to reach the far plane
+const HALF_SIZE: f32 = 1024.0;
+
+#[derive(Copy, Clone)]
+pub struct GroundVertex {
+    position: [f32; 3],
+    normal: [f32; 3],
+}
+
+implement_vertex!(GroundVertex, position, normal);
+
+/// Flat plane for the teapots to cast shadows on, drawn with the same program as the teapots
+pub struct Ground {
+    pub vertices: glium::VertexBuffer<GroundVertex>,
+    pub indices: glium::IndexBuffer<u16>,
+    /// A single instance at the origin, the teapot program is instanced
+    pub instance: glium::VertexBuffer<Instance>,
+    pub height: f32,
+}
+
+impl Ground {
+    pub fn new(display: &impl glium::backend::Facade, height: f32, colour: [f32; 3]) -> Ground {
+        let up = [0.0, 1.0, 0.0];
+        let vertices = [
+            GroundVertex { position: [-1.0, 0.0, -1.0], normal: up },
+            GroundVertex { position: [1.0, 0.0, -1.0], normal: up },
+            GroundVertex { position: [1.0, 0.0, 1.0], normal: up },
+            GroundVertex { position: [-1.0, 0.0, 1.0], normal: up },
+        ];
+
+        Ground {
+            vertices: glium::VertexBuffer::new(display, &vertices).unwrap(),
+            indices: glium::IndexBuffer::new(display, glium::index::PrimitiveType::TrianglesList, &[0u16, 1, 2, 0, 2, 3]).unwrap(),
+            instance: glium::VertexBuffer::new(display, &[Instance::at([0.0, 0.0, 0.0], colour)]).unwrap(),
+            height,
+        }
+    }
+
+    /// Keeps the ground centred under the camera so you can never reach the edge
+    pub fn model(&self, camera: [f32; 3]) -> [[f32; 4]; 4] {
+        matrices::move_and_scale(camera[0], self.height, camera[2], HALF_SIZE)
+    }
+}
@@ -0,0 +1,56 @@
+use crate::{formation::Easing, positions::Details};
+
+/// Teapots grow in when they spawn and shrink away when they are despawned, instead of popping in and out
+#[derive(Copy, Clone, Debug)]
+pub struct Growth {
+    /// Seconds it takes, 0 pops teapots in and out
+    pub duration: f32,
+}
+
+impl Growth {
+    /// How much of its full size `teapot` is at `time`, from 0 to 1
+    pub fn scale(&self, teapot: &Details, time: f32) -> f32 {
+        if self.duration <= 0.0 {
+            return 1.0;
+        }
+        let grown = ((time - teapot.spawned) / self.duration).clamp(0.0, 1.0);
+        let left = teapot.dying.map_or(1.0, |dying| 1.0 - ((time - dying) / self.duration).clamp(0.0, 1.0));
+        Easing::EaseInOut.apply(grown.min(left))
+    }
+
+    /// Whether a dying teapot has finished shrinking and can be removed
+    pub fn dead(&self, teapot: &Details, time: f32) -> bool {
+        teapot.dying.is_some_and(|dying| time - dying >= self.duration)
+    }
+}
+
+/// Starts despawning the oldest `amount` teapots that aren't already dying, returns how many there were
+pub fn despawn(teapots: &mut [Details], amount: usize, time: f32) -> usize {
+    let mut count = 0;
+    for teapot in teapots.iter_mut().filter(|teapot| teapot.dying.is_none()).take(amount) {
+        teapot.dying = Some(time);
+        count += 1;
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::positions::{Teapot, Teapots};
+
+    #[test]
+    fn teapots_grow_in_and_shrink_away() {
+        let growth = Growth { duration: 2.0 };
+        let mut teapots = Teapots::from(vec![Teapot { spawned: 1.0, ..Teapot::new([0.0; 3], [0.0; 3]) }; 3]).details;
+        assert_eq!(growth.scale(&teapots[0], 1.0), 0.0);
+        assert_eq!(growth.scale(&teapots[0], 3.0), 1.0);
+
+        assert_eq!(despawn(&mut teapots, 2, 5.0), 2);
+        assert_eq!(despawn(&mut teapots, 2, 5.0), 1);
+        assert!(growth.scale(&teapots[0], 6.0) < 1.0);
+        assert!(!growth.dead(&teapots[0], 6.0));
+        assert_eq!(growth.scale(&teapots[0], 7.0), 0.0);
+        assert!(growth.dead(&teapots[0], 7.0));
+    }
+}
@@ -0,0 +1,63 @@
+use glium::{implement_vertex, uniform, Surface};
+
+use crate::{font, shader};
+
+#[derive(Copy, Clone)]
+struct HudVertex {
+    position: [f32; 2],
+}
+
+implement_vertex!(HudVertex, position);
+
+/// Size of a font pixel on the screen
+const PIXEL: f32 = 2.0;
+
+/// Lines of text in the top left corner of the screen and a crosshair in the middle, drawn with the built in font
+pub struct Hud {
+    program: glium::Program,
+}
+
+impl Hud {
+    pub fn new(display: &impl glium::backend::Facade) -> Hud {
+        Hud { program: shader::program(display, include_str!("../shaders/hud.vert"), include_str!("../shaders/solid.frag")) }
+    }
+
+    /// Draws `lines` and, if there is one, the crosshair
+    pub fn draw(&self, display: &impl glium::backend::Facade, frame: &mut impl Surface, lines: &[String], crosshair: bool, colour: [f32; 3]) {
+        let (width, height) = frame.get_dimensions();
+        // Two triangles for every filled pixel of every character
+        let mut vertices = Vec::new();
+        for (line, text) in lines.iter().enumerate() {
+            // One pixel gaps between characters and lines, and a margin around the edge
+            text_pixels(text, [2.0 * PIXEL, (2 + line * (font::HEIGHT + 2)) as f32 * PIXEL], &mut vertices);
+        }
+        if crosshair {
+            let centre = [width as f32 / 2.0, height as f32 / 2.0];
+            text_pixels("+", [0, 1].map(|i| (centre[i] - [font::WIDTH, font::HEIGHT][i] as f32 * PIXEL / 2.0).round()), &mut vertices);
+        }
+        if vertices.is_empty() {
+            return;
+        }
+
+        let buffer = glium::VertexBuffer::new(display, &vertices).unwrap();
+        frame.draw(&buffer, glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList), &self.program,
+            &uniform! { resolution: [width as f32, height as f32], colour: colour },
+            &Default::default()).unwrap();
+    }
+}
+
+/// Adds two triangles for every filled pixel of `text` with its top left corner at `corner`
+fn text_pixels(text: &str, corner: [f32; 2], vertices: &mut Vec<HudVertex>) {
+    for (index, character) in text.chars().enumerate() {
+        let rows = font::glyph(character);
+        for row in 0..font::HEIGHT {
+            for column in (0..font::WIDTH).filter(|&column| font::pixel(&rows, column, row)) {
+                let left = corner[0] + (index * (font::WIDTH + 1) + column) as f32 * PIXEL;
+                let top = corner[1] + row as f32 * PIXEL;
+                let [right, bottom] = [left + PIXEL, top + PIXEL];
+                vertices.extend([[left, top], [right, top], [right, bottom], [left, top], [right, bottom], [left, bottom]]
+                    .map(|position| HudVertex { position }));
+            }
+        }
+    }
+}
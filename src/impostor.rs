@@ -0,0 +1,105 @@
+use glium::{framebuffer::{DepthRenderBuffer, SimpleFrameBuffer}, implement_vertex, texture::{DepthFormat, RawImage2d}, uniforms::{MagnifySamplerFilter, MinifySamplerFilter, SamplerWrapFunction, UniformValue, Uniforms}, Rect, Surface};
+
+use crate::{instance::Instance, matrices, mesh::MeshVertex, shader};
+
+/// Views of the teapot around the vertical axis
+const FRAMES: u32 = 8;
+/// Width and height in pixels of each view, impostors are only used when teapots are a few pixels big
+const FRAME_SIZE: u32 = 64;
+
+#[derive(Copy, Clone)]
+pub struct Corner {
+    corner: [f32; 2],
+}
+
+implement_vertex!(Corner, corner);
+
+/// Camera facing squares with pictures of the teapot on them, for teapots too far away for their triangles to matter
+pub struct Impostor {
+    /// The teapot's normals seen from FRAMES angles side by side, alpha is 0 around the teapot
+    texture: glium::Texture2d,
+    corners: glium::VertexBuffer<Corner>,
+    indices: glium::IndexBuffer<u16>,
+    pub program: glium::Program,
+    radius: f32,
+}
+
+impl Impostor {
+    /// Renders the views of the mesh, `radius` is the mesh's bounding radius and `scale` the teapot model scale
+    pub fn new(display: &impl glium::backend::Facade, vertices: &glium::VertexBuffer<MeshVertex>, indices: &glium::IndexBuffer<u32>, radius: f32, scale: f32) -> Impostor {
+        let (width, height) = (FRAMES * FRAME_SIZE, FRAME_SIZE);
+        let target = glium::Texture2d::empty(display, width, height).unwrap();
+        let depth = DepthRenderBuffer::new(display, DepthFormat::F32, width, height).unwrap();
+        let mut framebuffer = SimpleFrameBuffer::with_depth_buffer(display, &target, &depth).unwrap();
+        // A normal of 0 blends in better than black where mipmapping mixes the edges with the background
+        framebuffer.clear_color_and_depth((0.5, 0.5, 0.5, 0.0), 1.0);
+
+        let program = shader::program(display, include_str!("../shaders/vertex_shader.vert"), include_str!("../shaders/impostor_bake.frag"));
+        let instance = glium::VertexBuffer::new(display, &[Instance::at([0.0, 0.0, 0.0], [1.0, 1.0, 1.0])]).unwrap();
+        let projection = matrices::orthographic(-radius, radius, -radius, radius, 0.0, radius * 4.0);
+
+        for frame in 0..FRAMES {
+            let angle = frame as f32 * std::f32::consts::TAU / FRAMES as f32;
+            let direction = [angle.cos(), 0.0, angle.sin()];
+            let eye = direction.map(|v| v * radius * 2.0);
+            let view = matrices::view_matrix(&eye, &direction.map(|v| -v), &[0.0, 1.0, 0.0]);
+
+            let parameters = glium::DrawParameters {
+                depth: glium::Depth {
+                    test: glium::draw_parameters::DepthTest::IfLess,
+                    write: true,
+                    .. Default::default()
+                },
+                viewport: Some(Rect { left: frame * FRAME_SIZE, bottom: 0, width: FRAME_SIZE, height: FRAME_SIZE }),
+                .. Default::default()
+            };
+            framebuffer.draw((vertices, instance.per_instance().unwrap()), indices, &program,
+                             &glium::uniform! { model: matrices::IDENTITY, view: view, perspective: projection },
+                             &parameters).unwrap();
+        }
+
+        // Copied into a new texture so it gets mipmaps, which stop the tiny impostors from flickering
+        let pixels: RawImage2d<u8> = target.read();
+        let texture = glium::Texture2d::new(display, pixels).unwrap();
+
+        let corners = [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]].map(|corner| Corner { corner });
+
+        Impostor {
+            texture,
+            corners: glium::VertexBuffer::new(display, &corners).unwrap(),
+            indices: glium::IndexBuffer::new(display, glium::index::PrimitiveType::TrianglesList, &[0u16, 1, 2, 0, 2, 3]).unwrap(),
+            program: shader::program(display, include_str!("../shaders/impostor.vert"), include_str!("../shaders/impostor.frag")),
+            radius: radius * scale,
+        }
+    }
+
+    /// Draws one impostor per instance, `uniforms` are the same as for the teapots
+    pub fn draw(&self, target: &mut impl Surface, instances: &glium::VertexBuffer<Instance>, uniforms: &impl Uniforms, parameters: &glium::DrawParameters) {
+        let parameters = glium::DrawParameters {
+            backface_culling: glium::draw_parameters::BackfaceCullingMode::CullingDisabled,
+            .. parameters.clone()
+        };
+        target.draw((&self.corners, instances.per_instance().unwrap()), &self.indices, &self.program,
+                    &ImpostorUniforms { impostor: self, rest: uniforms }, &parameters).unwrap();
+    }
+}
+
+struct ImpostorUniforms<'a, U> {
+    impostor: &'a Impostor,
+    rest: &'a U,
+}
+
+impl<U: Uniforms> Uniforms for ImpostorUniforms<'_, U> {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut output: F) {
+        let behavior = glium::uniforms::SamplerBehavior {
+            minify_filter: MinifySamplerFilter::LinearMipmapLinear,
+            magnify_filter: MagnifySamplerFilter::Linear,
+            wrap_function: (SamplerWrapFunction::Clamp, SamplerWrapFunction::Clamp, SamplerWrapFunction::Clamp),
+            .. Default::default()
+        };
+        output("u_impostor", UniformValue::Texture2d(&self.impostor.texture, Some(behavior)));
+        output("u_impostor_radius", UniformValue::Float(self.impostor.radius));
+        output("u_impostor_frames", UniformValue::SignedInt(FRAMES as i32));
+        self.rest.visit_values(output);
+    }
+}
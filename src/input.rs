@@ -0,0 +1,307 @@
+use std::{fmt, fs::File, io::{BufWriter, Write}, path::Path};
+
+use glium::winit::keyboard::KeyCode;
+
+use crate::{formation::Layout, world::World};
+
+/// Keys that change the simulation
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Key {
+    W,
+    A,
+    S,
+    D,
+    Q,
+    E,
+    Escape,
+    /// 1 to 7, switching to a layout
+    Number(u8),
+    Delete,
+    C,
+    L,
+}
+
+/// The simulation's key for a key on the keyboard
+pub fn key(code: KeyCode) -> Option<Key> {
+    Some(match code {
+        KeyCode::KeyW => Key::W,
+        KeyCode::KeyA => Key::A,
+        KeyCode::KeyS => Key::S,
+        KeyCode::KeyD => Key::D,
+        KeyCode::KeyQ => Key::Q,
+        KeyCode::KeyE => Key::E,
+        KeyCode::Escape => Key::Escape,
+        KeyCode::Digit1 => Key::Number(1),
+        KeyCode::Digit2 => Key::Number(2),
+        KeyCode::Digit3 => Key::Number(3),
+        KeyCode::Digit4 => Key::Number(4),
+        KeyCode::Digit5 => Key::Number(5),
+        KeyCode::Digit6 => Key::Number(6),
+        KeyCode::Digit7 => Key::Number(7),
+        KeyCode::Delete => Key::Delete,
+        KeyCode::KeyC => Key::C,
+        KeyCode::KeyL => Key::L,
+        _ => return None,
+    })
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Key::Number(number) => write!(f, "{}", number),
+            key => write!(f, "{}", format!("{:?}", key).to_lowercase()),
+        }
+    }
+}
+
+fn parse_key(text: &str) -> Result<Key, String> {
+    Ok(match text {
+        "w" => Key::W,
+        "a" => Key::A,
+        "s" => Key::S,
+        "d" => Key::D,
+        "q" => Key::Q,
+        "e" => Key::E,
+        "escape" => Key::Escape,
+        "delete" => Key::Delete,
+        "c" => Key::C,
+        "l" => Key::L,
+        number => match number.parse::<u8>() {
+            Ok(number) if number >= 1 && number as usize <= Layout::ALL.len() => Key::Number(number),
+            _ => return Err(format!("Unknown key \"{}\"", text)),
+        },
+    })
+}
+
+/// Something the player did that changes the simulation, applied at the start of the next step
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Input {
+    Press(Key),
+    Release(Key),
+    /// Mouse movement, as the mouse reports it
+    Look { dx: f64, dy: f64 },
+    /// The window gained or lost focus
+    Focus(bool),
+    /// The left mouse button
+    Click,
+    /// The id of the teapot picked with the right mouse button. Picking depends on what was drawn, so the result is kept rather than the click
+    Select(Option<usize>),
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Input::Press(key) => write!(f, "press {}", key),
+            Input::Release(key) => write!(f, "release {}", key),
+            // Printed floats read back exactly
+            Input::Look { dx, dy } => write!(f, "look {} {}", dx, dy),
+            Input::Focus(focused) => write!(f, "focus {}", focused),
+            Input::Click => write!(f, "click"),
+            Input::Select(Some(id)) => write!(f, "select {}", id),
+            Input::Select(None) => write!(f, "select none"),
+        }
+    }
+}
+
+/// Parses an input written by `Display`, e.g. `press w`, `look -3 1.5` or `select none`
+pub fn parse(text: &str) -> Result<Input, String> {
+    let parts: Vec<&str> = text.split_whitespace().collect();
+    let number = |part: &str| part.parse::<f64>().map_err(|_| format!("Invalid number \"{}\" in \"{}\"", part, text));
+    Ok(match parts[..] {
+        ["press", key] => Input::Press(parse_key(key)?),
+        ["release", key] => Input::Release(parse_key(key)?),
+        ["look", dx, dy] => Input::Look { dx: number(dx)?, dy: number(dy)? },
+        ["focus", focused] => Input::Focus(focused.parse().map_err(|_| format!("Expected true or false in \"{}\"", text))?),
+        ["click"] => Input::Click,
+        ["select", "none"] => Input::Select(None),
+        ["select", id] => Input::Select(Some(id.parse().map_err(|_| format!("Invalid teapot id \"{}\"", id))?)),
+        _ => return Err(format!("Unknown input \"{}\"", text)),
+    })
+}
+
+/// Every input of a run with the step it was applied on, which plays the run again with the same --seed and settings
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Recording {
+    /// In order of their steps
+    pub events: Vec<(u64, Input)>,
+    /// Step the run ended on
+    pub end: u64,
+}
+
+impl Recording {
+    /// Reads a recording written by a `Recorder`. A line is a step and an input, or `end` after the last step,
+    /// and lines starting with # are comments. Without an end, e.g. after a crash, it ends with its last input
+    pub fn parse(text: &str) -> Result<Recording, String> {
+        let mut recording = Recording::default();
+        let mut end = None;
+        for (number, line) in text.lines().enumerate().map(|(i, line)| (i + 1, line.trim())) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |e: String| format!("line {}: {}", number, e);
+            let (tick, rest) = line.split_once(' ').unwrap_or((line, ""));
+            let tick: u64 = tick.parse().map_err(|_| error(format!("Invalid step \"{}\"", tick)))?;
+            if end.is_some() || recording.events.last().is_some_and(|&(last, _)| tick < last) {
+                return Err(error("Steps are out of order".to_string()));
+            }
+            if rest == "end" {
+                end = Some(tick);
+            } else {
+                recording.events.push((tick, parse(rest).map_err(error)?));
+            }
+        }
+        recording.end = end.or(recording.events.last().map(|&(tick, _)| tick)).unwrap_or(0);
+        Ok(recording)
+    }
+
+    pub fn load(path: &Path) -> Result<Recording, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Recording::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Inputs applied on step `tick`, in order
+    pub fn inputs(&self, tick: u64) -> Vec<Input> {
+        let start = self.events.partition_point(|&(at, _)| at < tick);
+        self.events[start..].iter().take_while(|&&(at, _)| at == tick).map(|&(_, input)| input).collect()
+    }
+}
+
+/// Writes inputs as they are applied. Flushed after every step with inputs, so a run that crashes is still recorded up to the crash
+pub struct Recorder<W: Write> {
+    out: W,
+}
+
+impl Recorder<BufWriter<File>> {
+    /// Starts a recording at `path`, with `settings` written at the top to know how to play it back
+    pub fn create(path: &Path, settings: &str) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Recorder::new(BufWriter::new(file), settings).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(mut out: W, settings: &str) -> std::io::Result<Self> {
+        writeln!(out, "# Replay with --replay and the same --seed and settings")?;
+        writeln!(out, "# {}", settings)?;
+        Ok(Recorder { out })
+    }
+
+    pub fn write(&mut self, tick: u64, input: Input) -> std::io::Result<()> {
+        writeln!(self.out, "{} {}", tick, input)
+    }
+
+    /// Writes out what has been recorded so far, for a crash to leave behind
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+
+    /// Marks the last step of the run
+    pub fn finish(&mut self, tick: u64) -> std::io::Result<()> {
+        writeln!(self.out, "{} end", tick)?;
+        self.out.flush()
+    }
+}
+
+/// Runs `world` to the end of `recording` as fast as it goes, without a window
+pub fn replay(world: &mut World, recording: &Recording) {
+    while world.clock.tick < recording.end {
+        world.clock.step_now();
+        world.step(&recording.inputs(world.clock.tick));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::{field, formation::{Easing, Formations}, growth::Growth, shoot::Gun, spin::Spinner, timestep::FixedStep, world::{Spawner, Spawning}};
+
+    fn world() -> World {
+        let spawner = Spawner {
+            range: 20.0,
+            scale: 1.0,
+            scale_range: Some((0.5, 2.0)),
+            spinner: Spinner { speed: (1.0, 5.0), axis: None, random_phase: true },
+            lifetime: Some((1.0, 3.0)),
+            colour: [1.0, 0.0, 0.0],
+            physics: false,
+            next_id: 0,
+        };
+        let formations = Formations {
+            text: Vec::new(),
+            image: Vec::new(),
+            spacing: 1.0,
+            range: 20.0,
+            screen: (800, 600),
+            duration: 0.5,
+            easing: Easing::EaseInOut,
+        };
+        let gun = Gun { speed: 30.0, gravity: [0.0, -9.81, 0.0], lifetime: None };
+        let mut world = World::new(FixedStep::new(120.0, 0.25), spawner, formations, Growth { duration: 0.2 }, gun, 7);
+        world.fields = vec![field::parse("turbulence:camera:2:6").unwrap()];
+        world.spawning = Spawning { amount: 3, period: 0.1, ..Spawning::default() };
+        world.spawn(50, 0.0);
+        world
+    }
+
+    /// Runs like the window does, with frames of random lengths and inputs arriving in between them, and records it
+    fn run_live(world: &mut World, frames: usize) -> Recording {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut recorder = Recorder::new(Vec::new(), "test").unwrap();
+        let mut pending = Vec::new();
+        for frame in 0..frames {
+            pending.extend(match frame % 40 {
+                0 => vec![Input::Focus(true), Input::Press(Key::W)],
+                5 => vec![Input::Look { dx: rng.gen_range(-50.0..50.0), dy: rng.gen_range(-20.0..20.0) }],
+                10 => vec![Input::Click, Input::Press(Key::Number(3))],
+                15 => vec![Input::Select(world.teapots.details.get(4).map(|teapot| teapot.id)), Input::Press(Key::C)],
+                20 => vec![Input::Release(Key::W), Input::Press(Key::Delete), Input::Press(Key::Number(1))],
+                _ => Vec::new(),
+            });
+            world.clock.add(rng.gen_range(0.001..0.05));
+            while world.clock.step() {
+                let inputs = std::mem::take(&mut pending);
+                for &input in &inputs {
+                    recorder.write(world.clock.tick, input).unwrap();
+                }
+                world.step(&inputs);
+            }
+        }
+        recorder.finish(world.clock.tick).unwrap();
+        Recording::parse(&String::from_utf8(recorder.out).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn replaying_a_recording_ends_the_same_as_the_run() {
+        let mut live = world();
+        let recording = run_live(&mut live, 200);
+        assert!(recording.events.len() > 20);
+        assert_eq!(recording.end, live.clock.tick);
+
+        let mut replayed = world();
+        replay(&mut replayed, &recording);
+        assert_eq!(replayed.teapots, live.teapots);
+        assert_eq!((replayed.camera, replayed.yaw, replayed.pitch), (live.camera, live.yaw, live.pitch));
+
+        // The inputs made a difference
+        let mut idle = world();
+        replay(&mut idle, &Recording { events: Vec::new(), end: recording.end });
+        assert_ne!(idle.teapots, live.teapots);
+        assert_eq!(idle.camera, [0.0; 3]);
+    }
+
+    #[test]
+    fn inputs_read_back_the_same() {
+        let inputs = [
+            Input::Press(Key::Escape), Input::Release(Key::Number(7)), Input::Look { dx: -0.1 + 0.2, dy: 1e-20 },
+            Input::Focus(false), Input::Click, Input::Select(Some(12)), Input::Select(None),
+        ];
+        for input in inputs {
+            assert_eq!(parse(&input.to_string()), Ok(input));
+        }
+        assert!(parse("press 8").is_err());
+        assert!(Recording::parse("5 click\n3 click").is_err());
+        assert_eq!(Recording::parse("# comment\n2 click\n2 press l\n9 focus true").unwrap().end, 9);
+    }
+}
@@ -0,0 +1,26 @@
+use glium::implement_vertex;
+
+/// Per teapot data, drawn with instancing so every teapot is a single draw call
+#[derive(Copy, Clone)]
+pub struct Instance {
+    pub world_position: [f32; 3],
+    pub instance_colour: [f32; 3],
+    /// Multiplies the size of the model
+    pub instance_scale: f32,
+    /// Quaternion as x, y, z, w, including the spin
+    pub instance_rotation: [f32; 4],
+}
+
+implement_vertex!(Instance, world_position, instance_colour, instance_scale, instance_rotation);
+
+impl Instance {
+    /// An instance that leaves the model as it is
+    pub fn at(world_position: [f32; 3], colour: [f32; 3]) -> Instance {
+        Instance {
+            world_position,
+            instance_colour: colour,
+            instance_scale: 1.0,
+            instance_rotation: [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+}
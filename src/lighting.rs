@@ -0,0 +1,140 @@
+use glium::uniforms::{UniformValue, Uniforms};
+
+use crate::colour;
+
+/// Has to match MAX_LIGHTS in the fragment shader
+pub const MAX_LIGHTS: usize = 8;
+
+/// How far the headlamp reaches before it has faded to a quarter of its brightness
+const HEADLAMP_RANGE: f32 = 32.0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LightKind {
+    /// Infinitely far away light, e.g. the sun. `vector` is the direction pointing towards the light
+    Directional,
+    /// Light that shines in every direction from `vector`, getting dimmer the further away you are
+    Point,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    /// Direction for directional lights, position for point lights. Always in world space
+    pub vector: [f32; 3],
+    pub colour: [f32; 3],
+    pub intensity: f32,
+    /// Distance where a point light is a quarter as bright, unused for directional lights
+    pub range: f32,
+}
+
+impl Light {
+    pub fn directional(direction: [f32; 3], colour: [f32; 3]) -> Light {
+        Light { kind: LightKind::Directional, vector: direction, colour, intensity: 1.0, range: 0.0 }
+    }
+
+    pub fn point(position: [f32; 3], colour: [f32; 3], range: f32) -> Light {
+        Light { kind: LightKind::Point, vector: position, colour, intensity: 1.0, range }
+    }
+}
+
+/// Everything the fragment shader needs to light a teapot
+pub struct Lighting {
+    pub lights: Vec<Light>,
+    /// How much of the teapot colour is visible with no light hitting it
+    pub ambient: f32,
+    /// Strength of the specular highlights
+    pub specular: f32,
+    /// Higher values give smaller, sharper highlights
+    pub shininess: f32,
+    /// Adds a point light that follows the camera around
+    pub headlamp: bool,
+}
+
+impl Lighting {
+    /// Combines the lights with other uniforms, `camera` is used for the specular highlights and the headlamp
+    pub fn uniforms<U: Uniforms>(&self, camera: [f32; 3], rest: U) -> LightUniforms<'_, U> {
+        LightUniforms { lighting: self, camera, rest }
+    }
+
+    /// The first directional light and its index, it casts the shadows and is drawn as the sun in the sky
+    pub fn sun(&self) -> Option<(usize, [f32; 3])> {
+        self.lights.iter()
+            .position(|light| light.kind == LightKind::Directional)
+            .map(|i| (i, self.lights[i].vector))
+    }
+
+    fn active_lights(&self, camera: [f32; 3]) -> impl Iterator<Item = Light> + '_ {
+        let headlamp = self.headlamp.then(|| Light::point(camera, [1.0, 1.0, 1.0], HEADLAMP_RANGE));
+        self.lights.iter().copied().chain(headlamp).take(MAX_LIGHTS)
+    }
+}
+
+pub struct LightUniforms<'a, U> {
+    lighting: &'a Lighting,
+    camera: [f32; 3],
+    rest: U,
+}
+
+impl<U: Uniforms> Uniforms for LightUniforms<'_, U> {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut output: F) {
+        let mut count = 0;
+        for (i, light) in self.lighting.active_lights(self.camera).enumerate() {
+            let colour = light.colour.map(|c| c * light.intensity);
+            let (is_point, range) = match light.kind {
+                LightKind::Directional => (0.0, 0.0),
+                LightKind::Point => (1.0, light.range),
+            };
+            output(&format!("u_lights[{}].vector", i), UniformValue::Vec4([light.vector[0], light.vector[1], light.vector[2], is_point]));
+            output(&format!("u_lights[{}].colour", i), UniformValue::Vec4([colour[0], colour[1], colour[2], range]));
+            count += 1;
+        }
+        output("u_light_count", UniformValue::SignedInt(count));
+        output("u_ambient", UniformValue::Float(self.lighting.ambient));
+        output("u_specular", UniformValue::Float(self.lighting.specular));
+        output("u_shininess", UniformValue::Float(self.lighting.shininess));
+        output("u_camera", UniformValue::Vec3(self.camera));
+        self.rest.visit_values(output);
+    }
+}
+
+pub fn parse_vector(text: &str) -> Result<[f32; 3], String> {
+    let values = text.split(',')
+        .map(|value| value.trim().parse::<f32>().map_err(|_| format!("Invalid number \"{}\"", value)))
+        .collect::<Result<Vec<f32>, String>>()?;
+
+    match values[..] {
+        [x, y, z] => Ok([x, y, z]),
+        _ => Err(format!("Expected 3 numbers separated by commas, got \"{}\"", text)),
+    }
+}
+
+/// Parses a light from the command line, in the format `kind:x,y,z[:#RRGGBB[:intensity[:range]]]`
+///
+/// `kind` is either `directional` (x, y, z is the direction towards the light) or `point` (x, y, z is the position of the light)
+pub fn parse_light(spec: &str) -> Result<Light, String> {
+    let mut parts = spec.split(':');
+
+    let kind = match parts.next() {
+        Some("directional") | Some("dir") => LightKind::Directional,
+        Some("point") => LightKind::Point,
+        _ => return Err(format!("Unknown light kind in \"{}\", expected directional or point", spec)),
+    };
+    let vector = parse_vector(parts.next().ok_or("Missing light direction/position")?)?;
+    let colour = match parts.next() {
+        Some(hex) => colour::hex_to_rgb(hex)?,
+        None => [1.0, 1.0, 1.0],
+    };
+    let intensity = match parts.next() {
+        Some(intensity) => intensity.parse().map_err(|_| format!("Invalid light intensity \"{}\"", intensity))?,
+        None => 1.0,
+    };
+    let range = match parts.next() {
+        Some(range) => range.parse().map_err(|_| format!("Invalid light range \"{}\"", range))?,
+        None => 16.0,
+    };
+    if parts.next().is_some() {
+        return Err(format!("Too many parts in light \"{}\"", spec));
+    }
+
+    Ok(Light { kind, vector, colour, intensity, range })
+}
@@ -0,0 +1,76 @@
+use crate::{decimate, impostor::Impostor, instance::Instance, mesh::{Mesh, MeshVertex}};
+
+/// Fraction of the triangles kept by each level of detail
+const LEVELS: [f32; 4] = [1.0, 0.4, 0.15, 0.06];
+/// Radius on screen in pixels down to which each level is used, below the last one teapots are drawn as impostors
+const MIN_PIXELS: [f32; 4] = [24.0, 10.0, 4.0, 1.5];
+
+pub struct Level {
+    pub vertices: glium::VertexBuffer<MeshVertex>,
+    pub indices: glium::IndexBuffer<u32>,
+}
+
+/// Simpler versions of the teapot for when it is small on screen
+pub struct Lod {
+    /// The first level is the full teapot
+    pub levels: Vec<Level>,
+    /// None without --lod, then the full teapot is always used
+    pub impostor: Option<Impostor>,
+    min_pixels: Vec<f32>,
+    /// Bounding radius of the teapot after scaling
+    radius: f32,
+}
+
+impl Lod {
+    /// `bias` above 1 keeps more detail further away, `scale` is the teapot model scale
+    pub fn new(display: &impl glium::backend::Facade, mesh: &Mesh, enabled: bool, bias: f32, scale: f32) -> Lod {
+        let upload = |mesh: &Mesh| {
+            let (vertices, indices) = mesh.upload(display);
+            Level { vertices, indices }
+        };
+
+        let radius = mesh.bounding_radius();
+        if !enabled {
+            return Lod { levels: vec![upload(mesh)], impostor: None, min_pixels: vec![0.0], radius: radius * scale };
+        }
+
+        // Simplified on load so they always match whatever teapot is being drawn.
+        // Welding first stops gaps opening up between the pieces of the mesh as they are simplified separately
+        let welded = mesh.welded();
+        let levels: Vec<Level> = LEVELS.iter()
+            .map(|fraction| if *fraction < 1.0 {
+                upload(&decimate::decimate(&welded, (mesh.triangles() as f32 * fraction) as usize))
+            } else {
+                upload(mesh)
+            })
+            .collect();
+        let impostor = Impostor::new(display, &levels[0].vertices, &levels[0].indices, radius, scale);
+
+        Lod {
+            levels,
+            impostor: Some(impostor),
+            min_pixels: MIN_PIXELS.iter().map(|pixels| pixels / bias.max(0.01)).collect(),
+            radius: radius * scale,
+        }
+    }
+
+    /// Which level to draw a teapot with given its radius on screen in pixels, levels.len() means the impostor
+    fn level(&self, pixels: f32) -> usize {
+        self.min_pixels.iter()
+            .position(|min| pixels >= *min)
+            .unwrap_or(if self.impostor.is_some() { self.levels.len() } else { self.levels.len() - 1 })
+    }
+
+    /// Splits teapots up by the level they should be drawn with, the last group is for the impostor.
+    ///
+    /// `pixels_per_unit` is how many pixels tall something 1 unit tall is 1 unit away from the camera.
+    pub fn group(&self, instances: &[Instance], camera: [f32; 3], pixels_per_unit: f32) -> Vec<Vec<Instance>> {
+        let mut groups = vec![Vec::new(); self.levels.len() + 1];
+        for instance in instances {
+            let offset = [0, 1, 2].map(|i| instance.world_position[i] - camera[i]);
+            let distance = (offset[0] * offset[0] + offset[1] * offset[1] + offset[2] * offset[2]).sqrt().max(0.001);
+            groups[self.level(self.radius * instance.instance_scale * pixels_per_unit / distance)].push(*instance);
+        }
+        groups
+    }
+}
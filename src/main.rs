@@ -1,13 +1,50 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
-use std::{io::Cursor, time::Instant};
+use std::{f32::consts::{PI, TAU}, io::Cursor, path::PathBuf, time::Instant};
 use rodio::{OutputStream, Source};
 
 use glium::{winit::{keyboard::{KeyCode, PhysicalKey}, window::Window}, Surface, uniform};
-use rand::{SeedableRng, Rng};
-use rand::rngs::StdRng;
-use clap::Parser;
+use clap::{CommandFactory, Parser, Subcommand};
 mod teapot;
 mod matrices;
+mod lighting;
+mod colour;
+mod config;
+mod instance;
+mod shadow;
+mod ground;
+mod shader;
+mod background;
+mod skybox;
+mod postprocess;
+mod window;
+mod pacing;
+mod stats;
+mod screensaver;
+mod culling;
+mod mesh;
+mod decimate;
+mod impostor;
+mod lod;
+mod bezier_teapot;
+mod ply;
+mod mesh_file;
+mod positions;
+mod font;
+mod formation;
+mod spin;
+mod growth;
+mod age;
+mod physics;
+mod shoot;
+mod spatial;
+mod field;
+mod update;
+mod timestep;
+mod world;
+mod input;
+mod pick;
+mod hud;
+mod selection;
 
 fn lock_cursor(window: &Window) {
     if window.set_cursor_grab(glium::winit::window::CursorGrabMode::Locked).is_err() {
@@ -23,30 +60,51 @@ fn unlock_cursor(window: &Window) {
     window.set_cursor_visible(true);
 }
 
-fn hex_to_rgb(hex: &str) -> Result<[f32; 3], &str> {
-    if hex.len() != 7 || !hex.starts_with('#') {
-        return Err("Invalid hex format");
+/// Parses `min..max`
+fn parse_range(text: &str) -> Result<(f32, f32), String> {
+    let (min, max) = text.split_once("..").ok_or_else(|| format!("Expected min..max, got \"{}\"", text))?;
+    let min: f32 = min.trim().parse().map_err(|_| format!("Invalid number \"{}\"", min))?;
+    let max: f32 = max.trim().parse().map_err(|_| format!("Invalid number \"{}\"", max))?;
+    if min > max {
+        return Err(format!("{} is bigger than {}", min, max));
     }
-
-    let r = u8::from_str_radix(&hex[1..3], 16).map_err(|_| "Invalid hex value")?;
-    let g = u8::from_str_radix(&hex[3..5], 16).map_err(|_| "Invalid hex value")?;
-    let b = u8::from_str_radix(&hex[5..7], 16).map_err(|_| "Invalid hex value")?;
-
-    Ok([r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0])
+    Ok((min, max))
 }
 
 fn main() {
+    #[derive(Subcommand)]
+    enum Command {
+        /// Write the teapot to a .obj, .gltf or .ply file instead of opening a window
+        Export {
+            path: PathBuf,
+            /// Write every teapot with its position, rotation and colour as a .gltf scene, e.g. to bring a teapot field into Blender
+            #[arg(long)]
+            scene: bool,
+        },
+    }
+
     #[derive(Parser)]
+    #[command(args_override_self = true)]
     struct Args {
+        #[command(subcommand)]
+        command: Option<Command>,
+        /// Path to a TOML file with defaults for any of these options, e.g. `range = 32.0` or `light = ["point:0,0,0", "directional:0,1,0"]`.
+        /// Options on the command line win, and a list like --light given there replaces the file's list
+        #[arg(long)]
+        config: Option<PathBuf>,
         /// Amount of teapots to spawn
         #[arg(short, long, default_value_t = 1000)]
         amount: usize,
         /// Range where teapots should spawn, e.g a value of 64 would spawn in between -64 and 64 on the x, y and z axis.
         #[arg(short, long, default_value_t = 64.0)]
         range: f32,
-        /// Set to a value other than 0 to make the teapots follow you, The value controls how fast they will follow you
+        /// Set to a value other than 0 to make the teapots follow you, The value controls how fast they will follow you. Same as `--field attract:camera:<speed>`
         #[arg(short, long, default_value_t = 0.0)]
         follow_speed: f32,
+        /// Force field that moves the teapots, can be given more than once. The format is `kind:x,y,z:strength[:falloff[:x,y,z]]`,
+        /// e.g. `attract:0,0,0:1:20`, `repel:camera:4:5`, `vortex:0,0,0:0.5:30:0,1,0`, `wind:0,0,0:3::1,0,0` or `turbulence:camera:2:8`
+        #[arg(long, value_parser = field::parse, allow_hyphen_values = true)]
+        field: Vec<field::Field>,
         /// Set to a value other than 0 to control how much will spawn each period, a value of 2 will spawn 2 teapots each period
         #[arg(short, long, default_value_t = 0)]
         spawn_amount: usize,
@@ -66,56 +124,497 @@ fn main() {
         #[arg(long, default_value_t = 4.0)]
         despawn_after: f32,
         /// Set the colour of every teapot, a value of "#00FF00" will make every teapot green
-        #[arg(short, long, default_value_t = format!("#FF0000"))]
-        colour: String,
+        #[arg(short, long, value_parser = colour::hex_to_rgb, default_value = "#FF0000")]
+        colour: [f32; 3],
         /// Seed for the random teapot positions
         #[arg(long, default_value_t = 0)]
         seed: u64,
+        /// Load the teapots from a .csv, .json or .ply point cloud file instead of placing --amount of them randomly.
+        /// Each teapot can also have a colour, scale and rotation, see the README for the columns
+        #[arg(long)]
+        positions: Option<PathBuf>,
+        /// Spell this out with teapots in front of the camera, `\n` starts a new line. Without --positions there is a teapot for each pixel of the text
+        #[arg(long)]
+        text: Option<String>,
+        /// Arrange teapots into this picture in front of the camera, with a teapot coloured like each pixel and none for transparent pixels.
+        /// Without --positions there is a teapot for each pixel
+        #[arg(long)]
+        image: Option<PathBuf>,
+        /// Most teapots across for --image, bigger pictures are shrunk to fit
+        #[arg(long, default_value_t = 64)]
+        image_width: u32,
+        /// Distance between neighbouring teapots in --text and --image formations
+        #[arg(long, default_value_t = 2.0)]
+        formation_spacing: f32,
+        /// Seconds the teapots float around before flying into the --text or --image formation, if there is no --timeline
+        #[arg(long, default_value_t = 2.0)]
+        morph_after: f32,
+        /// Seconds it takes to fly from one formation to the next
+        #[arg(long, default_value_t = 3.0)]
+        morph_duration: f32,
+        /// How teapots speed up and slow down when flying between formations
+        #[arg(long, value_enum, default_value_t = formation::Easing::EaseInOut)]
+        easing: formation::Easing,
+        /// Switch formations at these times in seconds, e.g. `2:text,10:sphere,15:helix,20:cloud`.
+        /// The layouts can also be switched to with the number keys, in the order cloud, cube, sphere, grid, helix, text, image
+        #[arg(long, value_delimiter = ',', value_parser = formation::parse_step)]
+        timeline: Vec<formation::Step>,
+        /// How fast the teapots spin in radians per second
+        #[arg(long, default_value_t = 6.5)]
+        spin_speed: f32,
+        /// Give every teapot a random spin speed in this range instead, e.g. `1..10`
+        #[arg(long, value_parser = parse_range, allow_hyphen_values = true)]
+        spin_speed_range: Option<(f32, f32)>,
+        /// Axis the teapots spin around as x,y,z
+        #[arg(long, value_parser = lighting::parse_vector, default_value = "0,1,0", allow_hyphen_values = true)]
+        spin_axis: [f32; 3],
+        /// Spin every teapot around its own random axis
+        #[arg(long)]
+        tumble: bool,
+        /// Start every teapot at a random angle so they don't all spin in step
+        #[arg(long)]
+        random_phase: bool,
+        /// Turn the teapots to point their spouts at the camera instead of spinning
+        #[arg(long)]
+        face_player: bool,
+        /// Size of the teapots, 2 makes them twice as big. Teapots from --positions keep their own scale
+        #[arg(long, default_value_t = 1.0)]
+        scale: f32,
+        /// Give every teapot a random size in this range instead, e.g. `0.5..2`
+        #[arg(long, value_parser = parse_range)]
+        scale_range: Option<(f32, f32)>,
+        /// Seconds new teapots take to grow in and despawned teapots take to shrink away, 0 makes them pop in and out
+        #[arg(long, default_value_t = 0.5)]
+        grow_duration: f32,
+        /// Seconds each teapot lives for before it despawns on its own
+        #[arg(long)]
+        lifetime: Option<f32>,
+        /// Give every teapot a random lifetime in this range instead, e.g. `2..8`
+        #[arg(long, value_parser = parse_range, conflicts_with = "lifetime")]
+        lifetime_range: Option<(f32, f32)>,
+        /// Colour the teapots turn as they get older, they are this colour at the end of their lifetime
+        #[arg(long, value_parser = colour::hex_to_rgb)]
+        age_colour: Option<[f32; 3]>,
+        /// Size the teapots grow or shrink to as they get older, e.g. 0.2 shrinks them to a fifth by the end of their lifetime
+        #[arg(long, default_value_t = 1.0)]
+        age_scale: f32,
+        /// Seconds counted as a whole life by --age-colour and --age-scale for teapots without a lifetime
+        #[arg(long, default_value_t = 10.0)]
+        age_span: f32,
+        /// Make the teapots fall, bounce off the ground and the walls of the --range and knock into each other
+        #[arg(long)]
+        physics: bool,
+        /// Acceleration of falling teapots as x,y,z with --physics
+        #[arg(long, value_parser = lighting::parse_vector, default_value = "0,-9.81,0", allow_hyphen_values = true)]
+        gravity: [f32; 3],
+        /// How much speed teapots keep when they bounce, from 0 to 1
+        #[arg(long, default_value_t = 0.5)]
+        bounciness: f32,
+        /// How much teapots grip each other and the ground, 0 makes them slide forever
+        #[arg(long, default_value_t = 0.4)]
+        friction: f32,
+        /// Shape the teapots collide as with --physics
+        #[arg(long, value_enum, default_value_t = physics::Shape::Sphere)]
+        collision_shape: physics::Shape,
+        /// Speed of teapots fired from the camera with the left mouse button
+        #[arg(long, default_value_t = 30.0)]
+        shoot_speed: f32,
+        /// Acceleration of fired teapots as x,y,z, they fall with this with or without --physics
+        #[arg(long, value_parser = lighting::parse_vector, default_value = "0,-9.81,0", allow_hyphen_values = true)]
+        shoot_gravity: [f32; 3],
+        /// Seconds before fired teapots despawn, 0 keeps them forever
+        #[arg(long, default_value_t = 5.0)]
+        shoot_lifetime: f32,
+        /// How teapots are found for picking and the nearest neighbours shown in the HUD
+        #[arg(long, value_enum, default_value_t = spatial::Kind::Octree)]
+        spatial_index: spatial::Kind,
+        /// Where P saves the current teapots, as .csv, .json or .ply that can be loaded again with --positions
+        #[arg(long, default_value = "teapots.csv")]
+        dump: PathBuf,
+        /// Add a light in the format kind:x,y,z[:#RRGGBB[:intensity[:range]]], kind is directional (x,y,z points towards the light) or point (x,y,z is the position).
+        /// Can be used multiple times, a white directional light is used if none are given
+        #[arg(short, long, value_parser = lighting::parse_light)]
+        light: Vec<lighting::Light>,
+        /// How bright the teapots are where no light hits them, from 0 to 1
+        #[arg(long, default_value_t = 0.3)]
+        ambient: f32,
+        /// Strength of the shiny highlights, 0 turns them off
+        #[arg(long, default_value_t = 0.5)]
+        specular: f32,
+        /// Higher values make the shiny highlights smaller and sharper
+        #[arg(long, default_value_t = 32.0)]
+        shininess: f32,
+        /// Attach a light to the camera
+        #[arg(long)]
+        headlamp: bool,
+        /// Make the first directional light cast shadows
+        #[arg(long)]
+        shadows: bool,
+        /// Width and height in pixels of each shadow map
+        #[arg(long, default_value_t = 2048)]
+        shadow_resolution: u32,
+        /// How far from the camera shadows reach, each extra cascade doubles this
+        #[arg(long, default_value_t = 32.0)]
+        shadow_distance: f32,
+        /// Amount of shadow cascades from 1 to 4, use more to get shadows further away with big --range values
+        #[arg(long, default_value_t = 1)]
+        shadow_cascades: usize,
+        /// Add a ground plane at this height for the teapots to cast shadows on, e.g. a value of -70 puts it just under the default range
+        #[arg(long, allow_negative_numbers = true)]
+        ground: Option<f32>,
+        /// Set the colour of the ground plane
+        #[arg(long, value_parser = colour::hex_to_rgb, default_value = "#808080")]
+        ground_colour: [f32; 3],
+        /// Set the colour behind the teapots, with a gradient this is the colour at the bottom
+        #[arg(long, value_parser = colour::hex_to_rgb, default_value = "#000000")]
+        background: [f32; 3],
+        /// Colour at the top of the gradient background
+        #[arg(long, value_parser = colour::hex_to_rgb, default_value = "#3366CC")]
+        background_top: [f32; 3],
+        /// What to draw behind the teapots
+        #[arg(long, value_enum, default_value_t = background::SkyMode::Solid)]
+        sky: background::SkyMode,
+        /// Fade teapots into the background with distance, the fog is always the same colour as the background behind it
+        #[arg(long, value_enum, default_value_t = background::FogMode::None)]
+        fog: background::FogMode,
+        /// Distance where linear fog starts
+        #[arg(long, default_value_t = 16.0)]
+        fog_start: f32,
+        /// Distance where linear fog completely hides teapots, keep this below 1024 to stop teapots popping out at the far plane
+        #[arg(long, default_value_t = 128.0)]
+        fog_end: f32,
+        /// Thickness of exp and exp2 fog
+        #[arg(long, default_value_t = 0.02)]
+        fog_density: f32,
+        /// Draw a skybox instead of the --sky, either a directory with six images named px, nx, py, ny, pz and nz
+        /// (or right, left, top, bottom, front and back) or a single equirectangular image such as an HDR panorama
+        #[arg(long)]
+        skybox: Option<PathBuf>,
+        /// How much the teapots reflect the sky or skybox, 0 is plastic and 1 is chrome
+        #[arg(long, default_value_t = 0.0)]
+        reflectivity: f32,
+        /// How much reflections are tinted by the teapot colour, use 1 with a yellow --colour for gold teapots
+        #[arg(long, default_value_t = 0.0)]
+        metallic: f32,
+        /// Post-processing passes to run in order, separated by commas. Either bloom, tonemap, gamma, fxaa, vignette, chromatic-aberration
+        /// or the path to your own fragment shader, e.g. "bloom,tonemap,gamma,fxaa"
+        #[arg(long, value_delimiter = ',', value_parser = postprocess::parse_pass)]
+        post: Vec<postprocess::Pass>,
+        /// Brightness where bloom starts to glow
+        #[arg(long, default_value_t = 1.0)]
+        bloom_threshold: f32,
+        /// How strong the bloom glow is
+        #[arg(long, default_value_t = 0.6)]
+        bloom_intensity: f32,
+        /// Multiplies colours before tonemapping
+        #[arg(long, default_value_t = 1.0)]
+        exposure: f32,
+        /// Gamma used by the gamma pass
+        #[arg(long, default_value_t = 2.2)]
+        gamma: f32,
+        /// How dark the corners get with the vignette pass, from 0 to 1
+        #[arg(long, default_value_t = 0.5)]
+        vignette: f32,
+        /// How many pixels apart red and blue get in the corners with the chromatic-aberration pass
+        #[arg(long, default_value_t = 3.0)]
+        chromatic_aberration: f32,
+        /// Multisample anti-aliasing samples per pixel, e.g. 4. 1 turns it off
+        #[arg(long, default_value_t = 1)]
+        msaa: u32,
+        /// Wait for the monitor before showing each frame
+        #[arg(long, value_enum, default_value_t = window::Vsync::On)]
+        vsync: window::Vsync,
+        /// Highest frame rate to draw at, 0 means no cap
+        #[arg(long, default_value_t = 0.0)]
+        fps_cap: f32,
+        /// Drop to --low-power-fps while the window is unfocused
+        #[arg(long)]
+        low_power: bool,
+        /// Frame rate used by --low-power
+        #[arg(long, default_value_t = 10.0)]
+        low_power_fps: f32,
+        /// Simulation steps per second, the simulation runs at this rate whatever the frame rate and frames are drawn in between steps
+        #[arg(long, default_value_t = 120.0)]
+        sim_rate: f32,
+        /// Longest frame in seconds the simulation catches up on, after a longer hitch it carries on from where it was
+        #[arg(long, default_value_t = 0.25)]
+        max_frame_time: f32,
+        /// Write every key, mouse movement, click and focus change to this file with the simulation step it was applied on
+        #[arg(long)]
+        record: Option<PathBuf>,
+        /// Play back a file written by --record instead of taking input, which plays out the same with the same --seed and settings
+        #[arg(long)]
+        replay: Option<PathBuf>,
+        /// Run the --replay as fast as possible without a window and save the teapots at the end to --dump
+        #[arg(long, requires = "replay")]
+        headless: bool,
+        /// Print the frame rate and render settings every second
+        #[arg(long)]
+        debug: bool,
+        /// Start fullscreen, F11 switches between fullscreen and windowed
+        #[arg(long)]
+        fullscreen: bool,
+        /// Open the window without a title bar and border
+        #[arg(long)]
+        borderless: bool,
+        /// Width of the window in pixels
+        #[arg(long, default_value_t = 800)]
+        width: u32,
+        /// Height of the window in pixels
+        #[arg(long, default_value_t = 480)]
+        height: u32,
+        /// Window title
+        #[arg(long, default_value_t = format!("TEAPOT (but you can wasd to move)"))]
+        title: String,
+        /// Build the teapot from the original Bezier patches with this many quads along each side of each patch,
+        /// e.g. 16 for a smooth teapot up close or 3 for a crowd of teapots. Without this the classic 1024 triangle teapot is used
+        #[arg(long)]
+        tessellation: Option<u32>,
+        /// Draw this .obj, .gltf or .ply model instead of the teapot, scaled to the size of a teapot
+        #[arg(long, conflicts_with = "tessellation")]
+        mesh: Option<PathBuf>,
+        /// Draw simpler teapots when they are small on screen, and flat pictures of teapots when they are tiny
+        #[arg(long)]
+        lod: bool,
+        /// Higher values keep more detail further away with --lod, e.g. 2 switches to simpler teapots at half the size on screen
+        #[arg(long, default_value_t = 1.0)]
+        lod_bias: f32,
+        /// Fly the camera around on its own and exit when a key is pressed or the mouse moves, use with --fullscreen
+        #[arg(long)]
+        screensaver: bool,
     }
 
-    let args = Args::parse();
+    let args = Args::parse_from(config::args(&Args::command()));
+
+    if args.light.len() + args.headlamp as usize > lighting::MAX_LIGHTS {
+        Args::command().error(clap::error::ErrorKind::TooManyValues, format!("At most {} lights are supported, including the headlamp", lighting::MAX_LIGHTS)).exit();
+    }
 
-    let num_positions = args.amount;
     let float_range = args.range;
-    let follow_speed = args.follow_speed;
-    let spawn_period = args.spawn_period;
-    let spawn_amount = args.spawn_amount;
-    let despawn_period = args.despawn_period;
-    let despawn_amount = args.despawn_amount;
-    let spawn_after = args.spawn_after;
-    let despawn_after = args.despawn_after;
-    let teapot_colour = hex_to_rgb(&args.colour).unwrap();
+    let mut fields = args.field.clone();
+    if args.follow_speed != 0.0 {
+        fields.push(field::Field::follow(args.follow_speed));
+    }
+    let teapot_colour = args.colour;
+    let aging = age::Aging {
+        colour: args.age_colour,
+        scale: args.age_scale,
+        span: args.age_span,
+    };
+
+    let lighting = lighting::Lighting {
+        lights: if args.light.is_empty() {
+            vec![lighting::Light::directional([-1.0, 0.4, 0.9], [1.0, 1.0, 1.0])]
+        } else {
+            args.light
+        },
+        ambient: args.ambient,
+        specular: args.specular,
+        shininess: args.shininess,
+        headlamp: args.headlamp,
+    };
+
+    let mut teapot_scale = 0.01;
+    let teapot_mesh = match (&args.mesh, args.tessellation) {
+        (Some(path), _) => {
+            let mesh = mesh_file::load(path).unwrap_or_else(|e| {
+                eprintln!("Failed to load mesh {}", e);
+                std::process::exit(1);
+            });
+            teapot_scale *= teapot::mesh().bounding_radius() / mesh.bounding_radius().max(f32::EPSILON);
+            mesh
+        },
+        (None, Some(tessellation)) => bezier_teapot::mesh(tessellation),
+        (None, None) => teapot::mesh(),
+    };
+    let teapot_radius = teapot_mesh.bounding_radius() * teapot_scale;
+
+    let mut formations = formation::Formations {
+        text: args.text.as_ref().map(|text| formation::text(text, args.formation_spacing)).unwrap_or_default(),
+        image: args.image.as_ref().map(|path| formation::image(path, args.image_width, args.formation_spacing).unwrap_or_else(|e| {
+            eprintln!("Failed to load image {}", e);
+            std::process::exit(1);
+        })).unwrap_or_default(),
+        spacing: args.formation_spacing,
+        range: float_range,
+        screen: (args.width, args.height),
+        duration: args.morph_duration,
+        easing: args.easing,
+    };
+    formation::in_front(&mut formations.text, args.width, args.height);
+    formation::in_front(&mut formations.image, args.width, args.height);
+
+    let spawner = world::Spawner {
+        range: float_range,
+        scale: args.scale,
+        scale_range: args.scale_range,
+        spinner: spin::Spinner {
+            speed: args.spin_speed_range.unwrap_or((args.spin_speed, args.spin_speed)),
+            axis: (!args.tumble).then_some(args.spin_axis),
+            random_phase: args.random_phase,
+        },
+        lifetime: args.lifetime.map(|lifetime| (lifetime, lifetime)).or(args.lifetime_range),
+        colour: teapot_colour,
+        physics: args.physics,
+        next_id: 0,
+    };
+    let gun = shoot::Gun {
+        speed: args.shoot_speed,
+        gravity: args.shoot_gravity,
+        lifetime: (args.shoot_lifetime > 0.0).then_some(args.shoot_lifetime),
+    };
+    let clock = timestep::FixedStep::new(args.sim_rate, args.max_frame_time);
+    let mut world = world::World::new(clock, spawner, formations, growth::Growth { duration: args.grow_duration }, gun, args.seed);
+    world.fields = fields;
+    world.screensaver = args.screensaver;
+    world.spawning = world::Spawning {
+        amount: args.spawn_amount,
+        period: args.spawn_period,
+        after: args.spawn_after,
+        despawn_amount: args.despawn_amount,
+        despawn_period: args.despawn_period,
+        despawn_after: args.despawn_after,
+    };
+    world.physics = args.physics.then(|| physics::Physics::new(physics::Settings {
+        gravity: args.gravity,
+        bounciness: args.bounciness,
+        friction: args.friction,
+        ground: args.ground,
+        range: float_range,
+        shape: args.collision_shape,
+    }, &teapot_mesh, teapot_scale));
+
+    for step in &args.timeline {
+        if (step.layout == formation::Layout::Text && args.text.is_none()) || (step.layout == formation::Layout::Image && args.image.is_none()) {
+            Args::command().error(clap::error::ErrorKind::MissingRequiredArgument,
+                format!("The timeline has a {:?} step at {}s, give one with --text or --image", step.layout, step.time)).exit();
+        }
+    }
+    let mut timeline = args.timeline.clone();
+    if timeline.is_empty() {
+        let first = if args.text.is_some() { Some(formation::Layout::Text) } else { args.image.as_ref().map(|_| formation::Layout::Image) };
+        timeline.extend(first.map(|layout| formation::Step { time: args.morph_after, layout }));
+    }
+    timeline.sort_by(|a, b| a.time.total_cmp(&b.time));
+    world.timeline = timeline.into_iter().peekable();
+
+    if let Some(path) = &args.positions {
+        let mut loaded = positions::load(path, teapot_colour).unwrap_or_else(|e| {
+            eprintln!("Failed to load positions {}", e);
+            std::process::exit(1);
+        });
+        for teapot in &mut loaded {
+            world.spawner.prepare(teapot, &mut world.rng);
+        }
+        world.teapots = loaded.into();
+    } else {
+        // Enough teapots for the first formation, otherwise they are added when it starts
+        let amount = match world.timeline.peek().map(|step| step.layout) {
+            Some(formation::Layout::Text) => world.formations.text.len(),
+            Some(formation::Layout::Image) => world.formations.image.len(),
+            _ => args.amount,
+        };
+        world.spawn(amount, 0.0);
+    }
+
+    let replay = args.replay.as_ref().map(|path| input::Recording::load(path).unwrap_or_else(|e| {
+        eprintln!("Failed to load replay {}", e);
+        std::process::exit(1);
+    }));
+    if let (true, Some(replay)) = (args.headless, &replay) {
+        input::replay(&mut world, replay);
+        match positions::save(&world.teapots.to_vec(), &args.dump) {
+            Ok(()) => println!("Replayed {} steps and saved {} teapots to {}", world.clock.tick, world.teapots.len(), args.dump.display()),
+            Err(e) => {
+                eprintln!("Failed to save teapots {}", e);
+                std::process::exit(1);
+            },
+        }
+        return;
+    }
 
-    let range = -float_range..float_range;
+    if let Some(Command::Export { path, scene }) = &args.command {
+        let mut mesh = teapot_mesh.clone();
+        let result = if *scene {
+            mesh_file::save_scene(&mesh, teapot_scale, &world.teapots.to_vec(), path)
+        } else {
+            // Exported at the size teapots are drawn
+            mesh.positions.iter_mut().flatten().for_each(|v| *v *= teapot_scale);
+            mesh_file::save(&mesh, path)
+        };
+
+        if let Err(e) = result {
+            eprintln!("Failed to export {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
 
     let event_loop = glium::winit::event_loop::EventLoop::builder()
         .build()
         .expect("event loop building");
 
-    let (window, display) = glium::backend::glutin::SimpleWindowBuilder::new()
-        .with_title("TEAPOT (but you can wasd to move)")
-        .build(&event_loop);
-
-    let positions = glium::VertexBuffer::new(&display, &teapot::VERTICES).unwrap();
-        let normals = glium::VertexBuffer::new(&display, &teapot::NORMALS).unwrap();
-        let indices = glium::IndexBuffer::new(&display, glium::index::PrimitiveType::TrianglesList,
-                                            &teapot::INDICES).unwrap();
-
-        let program = glium::Program::from_source(&display, include_str!("../shaders/vertex_shader.vert"), include_str!("../shaders/fragment_shader.frag"),
-                                                None).unwrap();
+    let (window, display) = window::create(&event_loop, &window::WindowOptions {
+        title: args.title,
+        width: args.width,
+        height: args.height,
+        fullscreen: args.fullscreen,
+        borderless: args.borderless,
+        vsync: args.vsync,
+    });
+    if args.screensaver {
+        window.set_cursor_visible(false);
+    }
+    let mut wake = screensaver::Wake::default();
 
-    let mut random_positions = Vec::new();
+    let lod = lod::Lod::new(&display, &teapot_mesh, args.lod, args.lod_bias, teapot_scale);
+    let picker = pick::Picker::new(&teapot_mesh, teapot_scale);
+    let mut spatial = spatial::new(args.spatial_index, 2.0 * teapot_radius);
+    let outline = selection::Outline::new(&display, teapot_radius);
+    let hud = hud::Hud::new(&display);
 
-    let mut rng = StdRng::seed_from_u64(args.seed);
+        let program = shader::program(&display, include_str!("../shaders/vertex_shader.vert"), include_str!("../shaders/fragment_shader.frag"));
 
-    for _ in 0..num_positions {
-        random_positions.push([
-            rng.gen_range(range.clone()),
-            rng.gen_range(range.clone()),
-            rng.gen_range(range.clone()),
-        ]);
+    let mut shadows = shadow::Shadows::new(&display, args.shadows, args.shadow_resolution, args.shadow_cascades, args.shadow_distance);
+    if args.shadows && lighting.sun().is_none() {
+        eprintln!("Shadows need a directional light, none were given");
     }
 
+    let skybox = args.skybox.map(|path| skybox::load(&display, &path).unwrap_or_else(|e| {
+        eprintln!("Failed to load skybox {}", e);
+        std::process::exit(1);
+    }));
+
+    let background = background::Background::new(&display, args.sky, args.background, args.background_top, background::Fog {
+        mode: args.fog,
+        start: args.fog_start,
+        end: args.fog_end,
+        density: args.fog_density,
+    }, skybox);
+
+    let post_settings = postprocess::Settings {
+        bloom_threshold: args.bloom_threshold,
+        bloom_intensity: args.bloom_intensity,
+        exposure: args.exposure,
+        gamma: args.gamma,
+        vignette: args.vignette,
+        chromatic_aberration: args.chromatic_aberration,
+    };
+    let mut post = postprocess::PostProcess::new(&display, &args.post, post_settings, args.msaa).unwrap_or_else(|e| {
+        eprintln!("Failed to create post-processing pass {}", e);
+        std::process::exit(1);
+    });
+
+    let ground = args.ground.map(|height| ground::Ground::new(&display, height, args.ground_colour));
+
+    let mut pacer = pacing::FramePacer::new(args.fps_cap);
+    let mut stats = stats::DebugStats::new(args.debug, stats::RenderSettings {
+        msaa: post.samples(),
+        vsync: args.vsync,
+        fps_cap: args.fps_cap,
+        low_power_fps: args.low_power.then_some(args.low_power_fps),
+    });
+
     // MUSIC!!!
     let (_stream, stream_handle) = OutputStream::try_default().unwrap();
     let source = rodio::Decoder::new(Cursor::new(include_bytes!("../funkytown.mp3"))).unwrap();
@@ -124,17 +623,19 @@ fn main() {
     let mut before = Instant::now(); // used for delta_time
     let start = Instant::now(); // Used to calculate time elapsed since program started
 
-    let mut last_spawn = Instant::now(); // Used for checking spawn_period
-    let mut last_despawn = Instant::now(); // Used for checking despawn_period
-
-
-    let mut pos = [0.0, 0.0, 0.0f32];
-    let mut move_vector = [0.0, 0.0, 0.0];
-    
-    let mut yaw = 0.0f32; // Horizontal rotation (in radians)
-    let mut pitch = 0.0f32; // Vertical rotation (in radians)
-
-    event_loop.run(move |ev, window_target| {
+    let mut recorder = args.record.as_ref().map(|path| {
+        let settings: Vec<String> = config::args(&Args::command()).iter().map(|arg| arg.to_string_lossy().into_owned()).collect();
+        input::Recorder::create(path, &settings.join(" ")).unwrap_or_else(|e| {
+            eprintln!("Failed to start recording {}", e);
+            std::process::exit(1);
+        })
+    });
+    let mut inputs = Vec::new(); // Applied at the start of the next simulation step, so they are applied the same way when replayed
+    let mut cursor_locked = false; // Whether the window has locked the mouse
+    let mut cursor = [0.0f32; 2]; // Mouse position in pixels, for picking teapots when it isn't locked
+    let mut pick_at: Option<[f32; 2]> = None; // Where to pick a teapot on the next frame, from -1 to 1 across the screen
+
+    event_loop.run_app(&mut window::Handler(move |ev, window_target| {
         match ev {
             glium::winit::event::Event::WindowEvent { event, .. } => match event {
                 glium::winit::event::WindowEvent::CloseRequested => {
@@ -142,91 +643,110 @@ fn main() {
                 },
                 glium::winit::event::WindowEvent::RedrawRequested => {
                     let delta_time = Instant::now() - before; // Work out delta time
-                    let delta_secs = delta_time.as_secs_f32();
                     before = Instant::now(); // Set this for next frame
-
-                    let forward = [
-                        yaw.cos(),
-                        0.0,
-                        yaw.sin(),
-                    ];
-
-                    let right = [
-                        yaw.sin(),
-                        0.0,
-                        -yaw.cos(),
-                    ];
-
-                    let world_move_vector = [
-                        forward[0] * move_vector[2] + right[0] * move_vector[0],
-                        move_vector[1],
-                        forward[2] * move_vector[2] + right[2] * move_vector[0],
-                    ];
-
-                    pos[0] += world_move_vector[0] * delta_secs * 3.0;
-                    pos[1] += world_move_vector[1] * delta_secs * 3.0;
-                    pos[2] += world_move_vector[2] * delta_secs * 3.0;
-
-                    if follow_speed != 0.0 {
-                        for position in &mut random_positions {
-                            position[0] += (pos[0] - position[0]) * follow_speed * delta_secs;
-                            position[1] += (pos[1] - position[1]) * follow_speed * delta_secs;
-                            position[2] += (pos[2] - position[2]) * follow_speed * delta_secs;
+                    stats.frame(delta_time);
+
+                    // The simulation catches up in steps of the same length, and the frame is drawn in between the last two
+                    world.clock.add(delta_time.as_secs_f32());
+                    while world.clock.step() {
+                        let tick = world.clock.tick;
+                        // A replay ignores the player until it has finished
+                        let applied = match &replay {
+                            Some(replay) if tick <= replay.end => {
+                                inputs.clear();
+                                replay.inputs(tick)
+                            },
+                            _ => std::mem::take(&mut inputs),
+                        };
+                        if let (Some(recorder), false) = (&mut recorder, applied.is_empty()) {
+                            for &input in &applied {
+                                if let Err(e) = recorder.write(tick, input) {
+                                    eprintln!("Failed to record input {}", e);
+                                }
+                            }
+                            if let Err(e) = recorder.flush() {
+                                eprintln!("Failed to record input {}", e);
+                            }
+                        }
+                        world.step(&applied);
+                        for id in world.removed.drain(..) {
+                            spatial.remove(id);
                         }
                     }
 
-                    if spawn_amount != 0 {
-                        // before = Instant::now()
-                        if (before-start).as_secs_f32() >= spawn_after && (before-last_spawn).as_secs_f32() >= spawn_period {
-                            last_spawn = before; // again, before = Instant::now()
-                            for _ in 0..spawn_amount {
-                                random_positions.push([
-                                    rng.gen_range(range.clone()),
-                                    rng.gen_range(range.clone()),
-                                    rng.gen_range(range.clone()),
-                                ]);
-                            }
+                    // Everything is drawn a step behind, part way to where it is now
+                    let alpha = world.clock.alpha();
+                    let elapsed = world.clock.draw_time();
+                    let eye = [0, 1, 2].map(|i| world.previous_camera[i] + (world.camera[i] - world.previous_camera[i]) * alpha);
+                    // The shorter way round, turning towards the selected teapot can jump the yaw by whole turns
+                    let turned = (world.yaw - world.previous_yaw + PI).rem_euclid(TAU) - PI;
+                    let pitch = world.previous_pitch + (world.pitch - world.previous_pitch) * alpha;
+                    let direction = world::look_direction(world.previous_yaw + turned * alpha, pitch);
+
+                    let teapot_model = matrices::move_and_scale(0.0, 0.0, 0.0, teapot_scale);
+
+                    let instances = update::instances(&world.teapots, |index| {
+                        let teapot = &world.teapots.details[index];
+                        let (position, rotation) = update::between_steps(&world.teapots, index, alpha);
+                        instance::Instance {
+                            world_position: position,
+                            instance_colour: aging.colour(teapot, elapsed),
+                            instance_scale: teapot.scale * world.growth.scale(teapot, elapsed) * aging.scale(teapot, elapsed),
+                            instance_rotation: if args.face_player {
+                                spin::multiply(spin::face(position, eye), rotation)
+                            } else {
+                                spin::multiply(rotation, teapot.spin.rotation(elapsed))
+                            },
                         }
+                    });
+                    for (teapot, instance) in world.teapots.details.iter().zip(&instances) {
+                        spatial.update(teapot.id, instance.world_position, teapot_radius * instance.instance_scale);
                     }
 
-                    if despawn_amount != 0 {
-                        // before = Instant::now()
-                        if (before-start).as_secs_f32() >= despawn_after && (before-last_despawn).as_secs_f32() >= despawn_period {
-                            for _ in 0..despawn_amount {
-                                if  random_positions.len() > 0 {
-                                    last_despawn = before; // again, before = Instant::now()
-                                    random_positions.remove(0);
-                                }
-
+                    // Teapots grouped by level of detail, impostors cast shadows with the simplest mesh
+                    let (width, height) = display.get_framebuffer_dimensions();
+                    let pixels_per_unit = matrices::perspective_for_size(width, height)[1][1] * height as f32 / 2.0;
+                    let groups = lod.group(&instances, eye, pixels_per_unit);
+                    let group_buffers: Vec<_> = groups.iter().map(|group| glium::VertexBuffer::dynamic(&display, group).unwrap()).collect();
+
+                    shadows.render(&display, lighting.sun(), eye, |framebuffer, program, light_matrix| {
+                        for (i, (group, buffer)) in groups.iter().zip(&group_buffers).enumerate() {
+                            if !group.is_empty() {
+                                let level = &lod.levels[i.min(lod.levels.len() - 1)];
+                                framebuffer.draw((&level.vertices, buffer.per_instance().unwrap()), &level.indices, program,
+                                &uniform! { light_matrix: light_matrix, model: teapot_model },
+                                &shadow::Shadows::draw_parameters()).unwrap();
                             }
                         }
-                    }
-
-                    let direction = [
-                        yaw.cos() * pitch.cos(),
-                        pitch.sin(),
-                        yaw.sin() * pitch.cos(),
-                    ];
+                    });
 
-                    let teapot_yaw = (Instant::now()-start).as_secs_f32()*6.5;
+                    let mut frame = display.draw();
+                    post.resize(&display, frame.get_dimensions());
+                    // Everything is drawn to an HDR texture first, then post-processed onto the frame
+                    let mut target = post.scene_target(&display);
 
-                    let yaw_sin = teapot_yaw.sin();
-                    let yaw_cos = teapot_yaw.cos();
-
-                    let yaw_matrix = [
-                        [yaw_cos, 0.0, yaw_sin, 0.0],
-                        [0.0, 1.0, 0.0, 0.0],
-                        [-yaw_sin, 0.0, yaw_cos, 0.0],
-                        [0.0, 0.0, 0.0, 1.0]
-                    ];
+                    let view = matrices::view_matrix(&eye, &direction, &[0.0,1.0,0.0]);
+                    let perspective = matrices::perspective(&target);
+                    let sun = lighting.sun().map(|(_, direction)| direction);
 
+                    if let Some(screen) = pick_at.take() {
+                        let picked = picker.pick(&*spatial, &world.teapots, &instances, eye, pick::ray(&view, &perspective, screen));
+                        inputs.push(input::Input::Select(picked.map(|index| world.teapots.details[index].id)));
+                    }
 
-                    let mut target = display.draw();
-                    target.clear_color_and_depth((0.0, 0.0, 0.0, 1.0), 1.0);
+                    // Only the camera skips off screen teapots, ones outside the view can still cast shadows into it
+                    let frustum = culling::Frustum::new(&view, &perspective);
+                    let visible: Vec<Vec<instance::Instance>> = groups.iter()
+                        .map(|group| group.iter()
+                            .filter(|instance| frustum.contains_sphere(&instance.world_position, teapot_radius * instance.instance_scale))
+                            .copied()
+                            .collect())
+                        .collect();
+                    stats.levels = visible.iter().map(|group| group.len()).collect();
+                    stats.teapots = stats.levels.iter().sum();
+                    stats.culled = instances.len() - stats.teapots;
 
-                    let view = matrices::view_matrix(&pos, &direction, &[0.0,1.0,0.0]);
-                    let perspective = matrices::perspective(&target);
-                    let light = [-1.0, 0.4, 0.9f32];
+                    background.draw(&mut target, view, perspective, sun);
 
                     let params = glium::DrawParameters {
                         depth: glium::Depth {
@@ -239,13 +759,33 @@ fn main() {
                     };
 
 
-                    for pos in &random_positions {
-                        target.draw((&positions, &normals), &indices, &program,
-                        &uniform! { model: matrices::move_and_scale(pos[0], pos[1], pos[2], 0.01), view: view, perspective: perspective, u_light: light, yaw_matrix: yaw_matrix, colour: teapot_colour},
+                    let teapot_uniforms = lighting.uniforms(eye, shadows.uniforms(background.uniforms(sun, uniform! { model: teapot_model, view: view, perspective: perspective, reflectivity: args.reflectivity, metallic: args.metallic})));
+
+                    // One draw call per level of detail
+                    for (i, group) in visible.iter().enumerate().filter(|(_, group)| !group.is_empty()) {
+                        let buffer = glium::VertexBuffer::dynamic(&display, group).unwrap();
+                        match (lod.levels.get(i), &lod.impostor) {
+                            (Some(level), _) => target.draw((&level.vertices, buffer.per_instance().unwrap()), &level.indices, &program, &teapot_uniforms, &params).unwrap(),
+                            (None, Some(impostor)) => impostor.draw(&mut target, &buffer, &teapot_uniforms, &params),
+                            (None, None) => (),
+                        }
+                    }
+
+                    if let Some(instance) = world.selection.index.and_then(|index| instances.get(index)) {
+                        let buffer = glium::VertexBuffer::new(&display, &[*instance]).unwrap();
+                        outline.draw(&mut target, &lod.levels[0], &buffer, teapot_model, view, perspective);
+                    }
+
+                    if let Some(ground) = &ground {
+                        target.draw((&ground.vertices, ground.instance.per_instance().unwrap()), &ground.indices, &program,
+                        &lighting.uniforms(eye, shadows.uniforms(background.uniforms(sun, uniform! { model: ground.model(eye), view: view, perspective: perspective, reflectivity: 0.0f32, metallic: 0.0f32}))),
                         &params).unwrap();
                     }
+                    post.apply(&display, &mut frame, (Instant::now()-start).as_secs_f32());
+                    hud.draw(&display, &mut frame, &world.selection.lines(&world.teapots, &*spatial, elapsed), cursor_locked, selection::COLOUR);
+
                     // Draw to screen
-                    target.finish().unwrap();
+                    frame.finish().unwrap();
 
                 },
                 // Because glium doesn't know about windows we need to resize the display
@@ -253,92 +793,103 @@ fn main() {
                 glium::winit::event::WindowEvent::Resized(window_size) => {
                     display.resize(window_size.into());
                 },
+                glium::winit::event::WindowEvent::KeyboardInput { event, .. } if args.screensaver && event.state == glium::winit::event::ElementState::Pressed => {
+                    window_target.exit();
+                },
+                glium::winit::event::WindowEvent::MouseInput { .. } | glium::winit::event::WindowEvent::MouseWheel { .. } if args.screensaver => {
+                    window_target.exit();
+                },
+                glium::winit::event::WindowEvent::CursorMoved { position, .. } if args.screensaver && wake.cursor_moved(position) => {
+                    window_target.exit();
+                },
                 glium::winit::event::WindowEvent::KeyboardInput { event, .. } => {
-                    if event.state == glium::winit::event::ElementState::Pressed && !event.repeat {
+                    let pressed = event.state == glium::winit::event::ElementState::Pressed;
+                    if pressed && !event.repeat {
                         match event.physical_key {
-                            PhysicalKey::Code(KeyCode::KeyW) => {
-                                move_vector[2] = 1.0;
-                            },
-                            PhysicalKey::Code(KeyCode::KeyS) => {
-                                move_vector[2] = -1.0;
-                            },
-                            PhysicalKey::Code(KeyCode::KeyA) => {
-                                move_vector[0] = -1.0;
-                            },
-                            PhysicalKey::Code(KeyCode::KeyD) => {
-                                move_vector[0] = 1.0;
-                            },
-                            PhysicalKey::Code(KeyCode::KeyE) => {
-                                move_vector[1] = 1.0;
-                            },
-                            PhysicalKey::Code(KeyCode::KeyQ) => {
-                                move_vector[1] = -1.0;
-                            },
                             PhysicalKey::Code(KeyCode::Escape) => {
                                 unlock_cursor(&window);
+                                cursor_locked = false;
                             },
-                            _ => ()
-                        }
-                    } else if event.state == glium::winit::event::ElementState::Released {
-                        match event.physical_key {
-                            PhysicalKey::Code(KeyCode::KeyW) => {
-                                move_vector[2] = 0.0;
-                            },
-                            PhysicalKey::Code(KeyCode::KeyS) => {
-                                move_vector[2] = 0.0;
-                            },
-                            PhysicalKey::Code(KeyCode::KeyA) => {
-                                move_vector[0] = 0.0;
-                            },
-                            PhysicalKey::Code(KeyCode::KeyD) => {
-                                move_vector[0] = 0.0;
+                            PhysicalKey::Code(KeyCode::F11) => {
+                                window::toggle_fullscreen(&window);
                             },
-                            PhysicalKey::Code(KeyCode::KeyE) => {
-                                move_vector[1] = 0.0;
-                            },
-                            PhysicalKey::Code(KeyCode::KeyQ) => {
-                                move_vector[1] = 0.0;
+                            PhysicalKey::Code(KeyCode::KeyP) => {
+                                match positions::save(&world.teapots.to_vec(), &args.dump) {
+                                    Ok(()) => println!("Saved {} teapots to {}", world.teapots.len(), args.dump.display()),
+                                    Err(e) => eprintln!("Failed to save teapots {}", e),
+                                }
                             },
                             _ => ()
                         }
                     }
+                    if let (PhysicalKey::Code(code), false) = (event.physical_key, event.repeat) {
+                        if let Some(key) = input::key(code) {
+                            inputs.push(if pressed { input::Input::Press(key) } else { input::Input::Release(key) });
+                        }
+                    }
                 },
                 glium::winit::event::WindowEvent::Focused(focused) => {
-                    if focused {
-                        lock_cursor(&window);
-                    } else {
-                        unlock_cursor(&window);
+                    stats.focused = focused;
+                    if args.low_power {
+                        pacer.set_cap(if focused { args.fps_cap } else { args.low_power_fps });
+                    }
+                    // The screensaver leaves the mouse alone so moving it can wake things up
+                    if !args.screensaver {
+                        if focused {
+                            lock_cursor(&window);
+                        } else {
+                            unlock_cursor(&window);
+                        }
+                        cursor_locked = focused;
                     }
+                    inputs.push(input::Input::Focus(focused));
+                },
+                glium::winit::event::WindowEvent::CursorMoved { position, .. } => {
+                    cursor = [position.x as f32, position.y as f32];
+                },
+                // Picks the teapot under the crosshair, or under the mouse when it isn't locked
+                glium::winit::event::WindowEvent::MouseInput { state: glium::winit::event::ElementState::Pressed, button: glium::winit::event::MouseButton::Right, .. } => {
+                    let size = window.inner_size();
+                    pick_at = Some(if cursor_locked {
+                        [0.0, 0.0]
+                    } else {
+                        [cursor[0] / size.width as f32 * 2.0 - 1.0, 1.0 - cursor[1] / size.height as f32 * 2.0]
+                    });
                 },
-                glium::winit::event::WindowEvent::MouseInput { state, button, .. } => {
-                    if state == glium::winit::event::ElementState::Pressed && button == glium::winit::event::MouseButton::Left {
+                // Fires once the mouse is locked, the simulation keeps track of the lock too
+                glium::winit::event::WindowEvent::MouseInput { state: glium::winit::event::ElementState::Pressed, button: glium::winit::event::MouseButton::Left, .. } => {
+                    if !cursor_locked {
                         lock_cursor(&window);
+                        cursor_locked = true;
                     }
+                    inputs.push(input::Input::Click);
                 },
                 _ => (),
             },
-            glium::winit::event::Event::DeviceEvent { event, .. } => match event {
-                // delta is a tuple of x and y movement
-                glium::winit::event::DeviceEvent::MouseMotion { delta } => {
-                    // delta x and y
-                    let (dx, dy) = delta;
-                    let sensitivity = 0.001;
-                    // Update yaw and pitch based on mouse movement
-                    yaw -= dx as f32 * sensitivity;
-                    pitch -= dy as f32 * sensitivity;
-
-                    // Clamp pitch to prevent flipping
-                    pitch = pitch.clamp(-std::f32::consts::FRAC_PI_2 + 0.01, std::f32::consts::FRAC_PI_2 - 0.01);
+            // delta is a tuple of x and y movement
+            glium::winit::event::Event::DeviceEvent { event: glium::winit::event::DeviceEvent::MouseMotion { delta: (dx, dy) }, .. } if !args.screensaver => {
+                inputs.push(input::Input::Look { dx, dy });
+            },
+            glium::winit::event::Event::LoopExiting => {
+                if let Some(recorder) = &mut recorder {
+                    if let Err(e) = recorder.finish(world.clock.tick) {
+                        eprintln!("Failed to finish recording {}", e);
+                    }
                 }
-                _ => (),
             },
             // By requesting a redraw in response to a AboutToWait event we get continuous rendering.
-            // For applications that only change due to user input you could remove this handler.
+            // With a frame rate cap we sleep until the next frame is due instead.
             glium::winit::event::Event::AboutToWait => {
-                window.request_redraw();
+                match pacer.wait() {
+                    Some(next_frame) => window_target.set_control_flow(glium::winit::event_loop::ControlFlow::WaitUntil(next_frame)),
+                    None => {
+                        window_target.set_control_flow(glium::winit::event_loop::ControlFlow::Poll);
+                        window.request_redraw();
+                    },
+                }
             },
             _ => (),
         }
-    })
+    }))
     .unwrap();
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use glium::{Frame, Surface};
+use glium::Surface;
 
 
 pub fn view_matrix(position: &[f32; 3], direction: &[f32; 3], up: &[f32; 3]) -> [[f32; 4]; 4] {
@@ -35,11 +35,16 @@ pub fn view_matrix(position: &[f32; 3], direction: &[f32; 3], up: &[f32; 3]) ->
     ]
 }
 
-pub fn perspective(target: &Frame) -> [[f32; 4]; 4] {
+pub fn perspective(target: &impl Surface) -> [[f32; 4]; 4] {
     let (width, height) = target.get_dimensions();
+    perspective_for_size(width, height)
+}
+
+/// Same as perspective, for a target of the given size
+pub fn perspective_for_size(width: u32, height: u32) -> [[f32; 4]; 4] {
     let aspect_ratio = height as f32 / width as f32;
 
-    let fov: f32 = 3.141592 / 3.0;
+    let fov: f32 = std::f32::consts::PI / 3.0;
     let zfar = 1024.0;
     let znear = 0.1;
 
@@ -60,4 +65,41 @@ pub fn move_and_scale(x: f32, y: f32, z: f32, scale: f32) -> [[f32; 4]; 4] {
         [0.0, 0.0, scale, 0.0],
         [x, y, z, 1.0f32]
     ]
-}
\ No newline at end of file
+}
+
+/// Multiplies two column major matrices, the result applies `b` first and then `a` like in GLSL
+pub fn multiply(a: &[[f32; 4]; 4], b: &[[f32; 4]; 4]) -> [[f32; 4]; 4] {
+    let mut result = [[0.0; 4]; 4];
+    for (column, b_column) in b.iter().enumerate() {
+        for row in 0..4 {
+            result[column][row] = (0..4).map(|i| a[i][row] * b_column[i]).sum();
+        }
+    }
+    result
+}
+
+/// Transforms a point by a column major matrix, without the perspective divide
+pub fn transform(matrix: &[[f32; 4]; 4], point: &[f32; 3]) -> [f32; 4] {
+    let mut result = [0.0; 4];
+    for (row, value) in result.iter_mut().enumerate() {
+        *value = matrix[0][row] * point[0] + matrix[1][row] * point[1] + matrix[2][row] * point[2] + matrix[3][row];
+    }
+    result
+}
+
+/// Orthographic projection of a box in view space, used for directional light shadows
+pub fn orthographic(left: f32, right: f32, bottom: f32, top: f32, znear: f32, zfar: f32) -> [[f32; 4]; 4] {
+    [
+        [2.0 / (right - left), 0.0, 0.0, 0.0],
+        [0.0, 2.0 / (top - bottom), 0.0, 0.0],
+        [0.0, 0.0, 2.0 / (zfar - znear), 0.0],
+        [-(right + left) / (right - left), -(top + bottom) / (top - bottom), -(zfar + znear) / (zfar - znear), 1.0],
+    ]
+}
+
+pub const IDENTITY: [[f32; 4]; 4] = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
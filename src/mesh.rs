@@ -0,0 +1,104 @@
+use glium::implement_vertex;
+
+#[derive(Copy, Clone)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+}
+
+implement_vertex!(MeshVertex, position, normal, uv);
+
+/// A triangle mesh on the CPU, for building and simplifying models before they are uploaded
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    /// Texture coordinates, all 0 for meshes that don't have any
+    pub uvs: Vec<[f32; 2]>,
+    /// Three per triangle, counter clockwise when looking at the front
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    pub fn triangles(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// Distance from the origin to the furthest vertex
+    pub fn bounding_radius(&self) -> f32 {
+        self.positions.iter()
+            .map(|[x, y, z]| (x * x + y * y + z * z).sqrt())
+            .fold(0.0, f32::max)
+    }
+
+    /// Joins vertices in the same place with nearly the same normal, so meshes made of separate pieces (like Bezier patches)
+    /// become one connected surface. Hard edges are kept, and the first vertex's UV is kept
+    pub fn welded(&self) -> Mesh {
+        let mut welded = Mesh::default();
+        let mut remap = Vec::with_capacity(self.positions.len());
+        // Vertices by position rounded to a grid, so nearby ones are found without comparing every pair
+        let mut grid: std::collections::HashMap<[i64; 3], Vec<u32>> = std::collections::HashMap::new();
+
+        for ((position, normal), uv) in self.positions.iter().zip(&self.normals).zip(&self.uvs) {
+            let key = position.map(|v| (v * 1e4).round() as i64);
+            let existing = grid.get(&key).and_then(|candidates| candidates.iter().copied().find(|i| {
+                let other = welded.normals[*i as usize];
+                normal[0] * other[0] + normal[1] * other[1] + normal[2] * other[2] > 0.9
+            }));
+
+            let index = existing.unwrap_or_else(|| {
+                let index = welded.positions.len() as u32;
+                welded.positions.push(*position);
+                welded.normals.push(*normal);
+                welded.uvs.push(*uv);
+                grid.entry(key).or_default().push(index);
+                index
+            });
+            remap.push(index);
+        }
+
+        welded.indices = self.indices.iter().map(|index| remap[*index as usize]).collect();
+        // Triangles squashed to a line or point, e.g. where a Bezier patch meets itself at the top of the lid
+        welded.indices = welded.indices.chunks(3)
+            .filter(|t| t[0] != t[1] && t[1] != t[2] && t[0] != t[2])
+            .flatten()
+            .copied()
+            .collect();
+        welded
+    }
+
+    /// Drops vertices that no triangle uses
+    pub fn compact(&mut self) {
+        let mut remap = vec![u32::MAX; self.positions.len()];
+        let mut positions = Vec::new();
+        let mut normals = Vec::new();
+        let mut uvs = Vec::new();
+
+        for index in &mut self.indices {
+            let old = *index as usize;
+            if remap[old] == u32::MAX {
+                remap[old] = positions.len() as u32;
+                positions.push(self.positions[old]);
+                normals.push(self.normals[old]);
+                uvs.push(self.uvs[old]);
+            }
+            *index = remap[old];
+        }
+
+        self.positions = positions;
+        self.normals = normals;
+        self.uvs = uvs;
+    }
+
+    pub fn upload(&self, display: &impl glium::backend::Facade) -> (glium::VertexBuffer<MeshVertex>, glium::IndexBuffer<u32>) {
+        let vertices: Vec<MeshVertex> = self.positions.iter().zip(&self.normals).zip(&self.uvs)
+            .map(|((position, normal), uv)| MeshVertex { position: *position, normal: *normal, uv: *uv })
+            .collect();
+
+        (
+            glium::VertexBuffer::new(display, &vertices).unwrap(),
+            glium::IndexBuffer::new(display, glium::index::PrimitiveType::TrianglesList, &self.indices).unwrap(),
+        )
+    }
+}
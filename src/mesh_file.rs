@@ -0,0 +1,523 @@
+use std::{collections::HashMap, path::Path};
+
+use base64::Engine;
+use serde_json::{json, Value};
+
+use crate::{mesh::Mesh, ply, positions::Teapot};
+
+// Other programs use right handed coordinates with counter clockwise triangles, which is this program's world
+// mirrored along z. Flipping z on the way in and out makes models look the same here as in e.g. Blender
+fn flip([x, y, z]: [f32; 3]) -> [f32; 3] {
+    [x, y, -z]
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Format {
+    Obj,
+    Gltf,
+    Ply,
+}
+
+fn format(path: &Path) -> Result<Format, String> {
+    let extension = path.extension().map(|extension| extension.to_string_lossy().to_lowercase());
+    match extension.as_deref() {
+        Some("obj") => Ok(Format::Obj),
+        Some("gltf") => Ok(Format::Gltf),
+        Some("ply") => Ok(Format::Ply),
+        _ => Err(format!("{}: unknown model format, use .obj, .gltf or .ply", path.display())),
+    }
+}
+
+/// Writes a mesh to an OBJ, glTF or PLY file depending on the extension
+pub fn save(mesh: &Mesh, path: &Path) -> Result<(), String> {
+    let bytes = match format(path)? {
+        Format::Obj => obj(mesh).into_bytes(),
+        Format::Gltf => gltf(mesh, None).into_bytes(),
+        Format::Ply => ply(mesh),
+    };
+    std::fs::write(path, bytes).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Writes a glTF scene with a node for every teapot, all sharing one mesh that is drawn at `mesh_scale`
+pub fn save_scene(mesh: &Mesh, mesh_scale: f32, teapots: &[Teapot], path: &Path) -> Result<(), String> {
+    if format(path)? != Format::Gltf {
+        return Err(format!("{}: scenes can only be exported as .gltf", path.display()));
+    }
+    std::fs::write(path, gltf(mesh, Some((mesh_scale, teapots)))).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Reads a mesh from an OBJ, glTF or PLY file, for glTF the first mesh in the file
+pub fn load(path: &Path) -> Result<Mesh, String> {
+    let error = |e: String| format!("{}: {}", path.display(), e);
+    let mut mesh = match format(path)? {
+        Format::Obj => load_obj(&std::fs::read_to_string(path).map_err(|e| error(e.to_string()))?).map_err(error)?,
+        Format::Gltf => load_gltf(path).map_err(error)?.0,
+        Format::Ply => load_ply(std::fs::File::open(path).map_err(|e| error(e.to_string()))?).map_err(error)?,
+    };
+
+    if mesh.indices.iter().any(|index| *index as usize >= mesh.positions.len()) {
+        return Err(error("triangle uses a vertex that doesn't exist".to_string()));
+    }
+    if mesh.normals.len() != mesh.positions.len() {
+        smooth_normals(&mut mesh);
+    }
+    if mesh.uvs.len() != mesh.positions.len() {
+        mesh.uvs = vec![[0.0, 0.0]; mesh.positions.len()];
+    }
+    Ok(mesh)
+}
+
+/// Averages the normals of the triangles around each vertex, for files without normals
+fn smooth_normals(mesh: &mut Mesh) {
+    let mut normals = vec![[0.0f32; 3]; mesh.positions.len()];
+    for triangle in mesh.indices.chunks(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| mesh.positions[triangle[i] as usize]);
+        let (u, v) = ([0, 1, 2].map(|i| b[i] - a[i]), [0, 1, 2].map(|i| c[i] - a[i]));
+        // Triangles are wound the other way round from the normal, see `flip`
+        let normal = [u[2] * v[1] - u[1] * v[2], u[0] * v[2] - u[2] * v[0], u[1] * v[0] - u[0] * v[1]];
+        for index in triangle {
+            for i in 0..3 {
+                normals[*index as usize][i] += normal[i];
+            }
+        }
+    }
+
+    mesh.normals = normals.into_iter()
+        .map(|[x, y, z]| {
+            let length = (x * x + y * y + z * z).sqrt().max(f32::EPSILON);
+            [x / length, y / length, z / length]
+        })
+        .collect();
+}
+
+fn obj(mesh: &Mesh) -> String {
+    let mut text = String::from("# Teapot\n");
+    for [x, y, z] in mesh.positions.iter().map(|p| flip(*p)) {
+        text += &format!("v {} {} {}\n", x, y, z);
+    }
+    for [u, v] in &mesh.uvs {
+        text += &format!("vt {} {}\n", u, v);
+    }
+    for [x, y, z] in mesh.normals.iter().map(|n| flip(*n)) {
+        text += &format!("vn {} {} {}\n", x, y, z);
+    }
+    for triangle in mesh.indices.chunks(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| triangle[i] + 1);
+        text += &format!("f {a}/{a}/{a} {b}/{b}/{b} {c}/{c}/{c}\n");
+    }
+    text
+}
+
+fn load_obj(text: &str) -> Result<Mesh, String> {
+    let mut positions = Vec::new();
+    let mut uvs = Vec::new();
+    let mut normals = Vec::new();
+    let mut mesh = Mesh::default();
+    // OBJ indexes positions, UVs and normals separately, so each distinct combination becomes a vertex
+    let mut vertices: HashMap<[Option<usize>; 3], u32> = HashMap::new();
+
+    let number = |word: Option<&str>| -> Result<f32, String> {
+        let word = word.ok_or("missing number")?;
+        word.parse().map_err(|_| format!("bad number \"{}\"", word))
+    };
+
+    for (line_number, line) in text.lines().enumerate() {
+        let error = |e: String| format!("line {}: {}", line_number + 1, e);
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("v") => positions.push(flip([number(words.next()).map_err(error)?, number(words.next()).map_err(error)?, number(words.next()).map_err(error)?])),
+            Some("vt") => uvs.push([number(words.next()).map_err(error)?, number(words.next()).unwrap_or(0.0)]),
+            Some("vn") => normals.push(flip([number(words.next()).map_err(error)?, number(words.next()).map_err(error)?, number(words.next()).map_err(error)?])),
+            Some("f") => {
+                let mut face = Vec::new();
+                for word in words {
+                    let mut key = [None; 3];
+                    for (i, part) in word.split('/').enumerate().take(3) {
+                        if part.is_empty() {
+                            continue;
+                        }
+                        let count = [positions.len(), uvs.len(), normals.len()][i];
+                        let index: i64 = part.parse().map_err(|_| error(format!("bad index \"{}\"", word)))?;
+                        // Negative indices count back from the latest one
+                        let index = if index < 0 { count as i64 + index } else { index - 1 };
+                        if index < 0 || index as usize >= count {
+                            return Err(error(format!("index \"{}\" is out of range", word)));
+                        }
+                        key[i] = Some(index as usize);
+                    }
+                    let position = key[0].ok_or_else(|| error(format!("no position in \"{}\"", word)))?;
+
+                    let vertex = *vertices.entry(key).or_insert_with(|| {
+                        mesh.positions.push(positions[position]);
+                        mesh.uvs.push(key[1].map_or([0.0, 0.0], |uv| uvs[uv]));
+                        if let Some(normal) = key[2] {
+                            mesh.normals.push(normals[normal]);
+                        }
+                        mesh.positions.len() as u32 - 1
+                    });
+                    face.push(vertex);
+                }
+
+                // Polygons are split into a fan of triangles
+                for i in 1..face.len().saturating_sub(1) {
+                    mesh.indices.extend([face[0], face[i], face[i + 1]]);
+                }
+            },
+            _ => (), // Groups, materials and so on don't matter here
+        }
+    }
+
+    Ok(mesh)
+}
+
+fn ply(mesh: &Mesh) -> Vec<u8> {
+    let header = format!(
+        "ply\nformat binary_little_endian 1.0\ncomment Teapot\n\
+         element vertex {}\nproperty float x\nproperty float y\nproperty float z\n\
+         property float nx\nproperty float ny\nproperty float nz\nproperty float s\nproperty float t\n\
+         element face {}\nproperty list uchar uint vertex_indices\nend_header\n",
+        mesh.positions.len(), mesh.triangles(),
+    );
+
+    let mut bytes = header.into_bytes();
+    for ((position, normal), uv) in mesh.positions.iter().zip(&mesh.normals).zip(&mesh.uvs) {
+        for value in flip(*position).into_iter().chain(flip(*normal)).chain(*uv) {
+            bytes.extend(value.to_le_bytes());
+        }
+    }
+    for triangle in mesh.indices.chunks(3) {
+        bytes.push(3);
+        for index in triangle {
+            bytes.extend(index.to_le_bytes());
+        }
+    }
+    bytes
+}
+
+fn load_ply(file: impl std::io::Read) -> Result<Mesh, String> {
+    let elements = ply::read(file)?;
+    let vertex = elements.iter().find(|element| element.name == "vertex").ok_or("no vertex element")?;
+    let face = elements.iter().find(|element| element.name == "face").ok_or("no face element")?;
+
+    let columns = |names: &[&str]| -> Option<Vec<Vec<f64>>> {
+        names.iter().map(|name| vertex.column(name)).collect()
+    };
+    let triples = |columns: Vec<Vec<f64>>| -> Vec<[f32; 3]> {
+        (0..vertex.len()).map(|i| flip([0, 1, 2].map(|axis| columns[axis][i] as f32))).collect()
+    };
+
+    let positions = columns(&["x", "y", "z"]).ok_or("vertices need x, y and z")?;
+    let uvs = columns(&["s", "t"]).or_else(|| columns(&["u", "v"])).or_else(|| columns(&["texture_u", "texture_v"]));
+    let faces = face.lists("vertex_indices").or_else(|| face.lists("vertex_index")).ok_or("faces need vertex_indices")?;
+
+    Ok(Mesh {
+        positions: triples(positions),
+        normals: columns(&["nx", "ny", "nz"]).map(triples).unwrap_or_default(),
+        uvs: uvs.map(|uvs| (0..vertex.len()).map(|i| [uvs[0][i] as f32, uvs[1][i] as f32]).collect()).unwrap_or_default(),
+        indices: faces.iter()
+            .flat_map(|face| (1..face.len().saturating_sub(1)).flat_map(move |i| [face[0], face[i], face[i + 1]]))
+            .map(|index| index as u32)
+            .collect(),
+    })
+}
+
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+/// A glTF file with the buffer embedded as base64, so it's a single file. Without teapots it has one node with the mesh
+fn gltf(mesh: &Mesh, teapots: Option<(f32, &[Teapot])>) -> String {
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut views = Vec::new();
+    let mut add_view = |data: Vec<u8>, target: u32| {
+        views.push(json!({ "buffer": 0, "byteOffset": buffer.len(), "byteLength": data.len(), "target": target }));
+        buffer.extend(data);
+        views.len() - 1
+    };
+
+    let floats = |values: &mut dyn Iterator<Item = f32>| values.flat_map(f32::to_le_bytes).collect::<Vec<u8>>();
+    let positions: Vec<[f32; 3]> = mesh.positions.iter().map(|p| flip(*p)).collect();
+    let min = [0, 1, 2].map(|i| positions.iter().map(|p| p[i]).fold(f32::INFINITY, f32::min));
+    let max = [0, 1, 2].map(|i| positions.iter().map(|p| p[i]).fold(f32::NEG_INFINITY, f32::max));
+
+    let position_view = add_view(floats(&mut positions.iter().flatten().copied()), ARRAY_BUFFER);
+    let normal_view = add_view(floats(&mut mesh.normals.iter().flat_map(|n| flip(*n))), ARRAY_BUFFER);
+    let uv_view = add_view(floats(&mut mesh.uvs.iter().flatten().copied()), ARRAY_BUFFER);
+    let index_view = add_view(mesh.indices.iter().flat_map(|i| i.to_le_bytes()).collect(), ELEMENT_ARRAY_BUFFER);
+
+    let count = mesh.positions.len();
+    let accessors = json!([
+        { "bufferView": position_view, "componentType": FLOAT, "count": count, "type": "VEC3", "min": min, "max": max },
+        { "bufferView": normal_view, "componentType": FLOAT, "count": count, "type": "VEC3" },
+        { "bufferView": uv_view, "componentType": FLOAT, "count": count, "type": "VEC2" },
+        { "bufferView": index_view, "componentType": UNSIGNED_INT, "count": mesh.indices.len(), "type": "SCALAR" },
+    ]);
+    let primitive = |material: Option<usize>| {
+        let mut primitive = json!({ "attributes": { "POSITION": 0, "NORMAL": 1, "TEXCOORD_0": 2 }, "indices": 3 });
+        if let Some(material) = material {
+            primitive["material"] = json!(material);
+        }
+        primitive
+    };
+
+    let (meshes, materials, nodes) = match teapots {
+        None => (vec![json!({ "name": "Teapot", "primitives": [primitive(None)] })], Vec::new(), vec![json!({ "name": "Teapot", "mesh": 0 })]),
+        Some((mesh_scale, teapots)) => {
+            // A material and mesh for each colour, the meshes all share the same vertex data
+            let mut colours: Vec<[f32; 3]> = Vec::new();
+            let nodes = teapots.iter().enumerate()
+                .map(|(i, teapot)| {
+                    let colour = colours.iter().position(|colour| *colour == teapot.colour).unwrap_or_else(|| {
+                        colours.push(teapot.colour);
+                        colours.len() - 1
+                    });
+                    let [x, y, z, w] = teapot.rotation;
+                    let scale = teapot.scale * mesh_scale;
+                    json!({
+                        "name": format!("Teapot {}", i),
+                        "mesh": colour,
+                        "translation": flip(teapot.position),
+                        "rotation": [-x, -y, z, w],
+                        "scale": [scale, scale, scale],
+                    })
+                })
+                .collect();
+
+            let materials = colours.iter()
+                .map(|[r, g, b]| json!({ "pbrMetallicRoughness": { "baseColorFactor": [r, g, b, 1.0], "metallicFactor": 0.0 } }))
+                .collect();
+            let meshes = (0..colours.len()).map(|i| json!({ "name": "Teapot", "primitives": [primitive(Some(i))] })).collect();
+            (meshes, materials, nodes)
+        },
+    };
+
+    let mut root = json!({
+        "asset": { "version": "2.0", "generator": "teapot" },
+        "scene": 0,
+        "scenes": [{ "nodes": (0..nodes.len()).collect::<Vec<_>>() }],
+        "nodes": nodes,
+        "meshes": meshes,
+        "accessors": accessors,
+        "bufferViews": views,
+        "buffers": [{
+            "byteLength": buffer.len(),
+            "uri": format!("data:application/octet-stream;base64,{}", base64::engine::general_purpose::STANDARD.encode(&buffer)),
+        }],
+    });
+    if !materials.is_empty() {
+        root["materials"] = Value::Array(materials);
+    }
+    serde_json::to_string_pretty(&root).unwrap()
+}
+
+/// The first mesh and the nodes using any mesh, as teapots with the scale of the node
+fn load_gltf(path: &Path) -> Result<(Mesh, Vec<Teapot>), String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let root: Value = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+    let array = |value: &Value| value.as_array().cloned().unwrap_or_default();
+
+    let buffers = array(&root["buffers"]).iter()
+        .map(|buffer| {
+            let uri = buffer["uri"].as_str().ok_or("only buffers with a uri are supported, not .glb")?;
+            match uri.strip_prefix("data:") {
+                Some(data) => {
+                    let (_, data) = data.split_once(";base64,").ok_or("buffer data isn't base64")?;
+                    base64::engine::general_purpose::STANDARD.decode(data).map_err(|e| e.to_string())
+                },
+                None => {
+                    let file = path.parent().unwrap_or(Path::new(".")).join(uri);
+                    std::fs::read(&file).map_err(|e| format!("{}: {}", file.display(), e))
+                },
+            }
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    // Every value of an accessor as floats, `expected` components per element
+    let accessor = |index: &Value, expected: usize| -> Result<Vec<Vec<f64>>, String> {
+        let accessor = &root["accessors"][index.as_u64().ok_or("missing accessor")? as usize];
+        let view = &root["bufferViews"][accessor["bufferView"].as_u64().ok_or("accessor without a buffer view")? as usize];
+        let buffer = buffers.get(view["buffer"].as_u64().unwrap_or(0) as usize).ok_or("missing buffer")?;
+
+        let components = match accessor["type"].as_str() {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") => 4,
+            other => return Err(format!("unsupported accessor type {:?}", other)),
+        };
+        if components != expected {
+            return Err(format!("expected an accessor with {} components, got {}", expected, components));
+        }
+        let (size, read): (usize, fn(&[u8]) -> f64) = match accessor["componentType"].as_u64() {
+            Some(5126) => (4, |b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64),
+            Some(5125) => (4, |b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64),
+            Some(5123) => (2, |b| u16::from_le_bytes([b[0], b[1]]) as f64),
+            Some(5121) => (1, |b| b[0] as f64),
+            other => return Err(format!("unsupported component type {:?}", other)),
+        };
+
+        let start = view["byteOffset"].as_u64().unwrap_or(0) as usize + accessor["byteOffset"].as_u64().unwrap_or(0) as usize;
+        let stride = view["byteStride"].as_u64().map_or(size * components, |stride| stride as usize);
+        (0..accessor["count"].as_u64().unwrap_or(0) as usize)
+            .map(|i| {
+                let offset = start + i * stride;
+                let bytes = buffer.get(offset..offset + size * components).ok_or("accessor goes past the end of its buffer")?;
+                Ok(bytes.chunks(size).map(read).collect())
+            })
+            .collect()
+    };
+    let triples = |values: Vec<Vec<f64>>| -> Vec<[f32; 3]> { values.iter().map(|v| flip([v[0] as f32, v[1] as f32, v[2] as f32])).collect() };
+
+    let primitive = &root["meshes"][0]["primitives"][0];
+    if primitive.is_null() {
+        return Err("no meshes".to_string());
+    }
+    let attributes = &primitive["attributes"];
+    let positions = triples(accessor(&attributes["POSITION"], 3)?);
+    let mesh = Mesh {
+        normals: if attributes["NORMAL"].is_null() { Vec::new() } else { triples(accessor(&attributes["NORMAL"], 3)?) },
+        uvs: if attributes["TEXCOORD_0"].is_null() {
+            Vec::new()
+        } else {
+            accessor(&attributes["TEXCOORD_0"], 2)?.iter().map(|v| [v[0] as f32, v[1] as f32]).collect()
+        },
+        indices: if primitive["indices"].is_null() {
+            (0..positions.len() as u32).collect()
+        } else {
+            accessor(&primitive["indices"], 1)?.iter().map(|v| v[0] as u32).collect()
+        },
+        positions,
+    };
+    if mesh.indices.iter().any(|&index| index as usize >= mesh.positions.len()) {
+        return Err("index past the last vertex".to_string());
+    }
+
+    // `N` numbers, or `default` when the value isn't there
+    fn floats<const N: usize>(value: &Value, name: &str, default: [f32; N]) -> Result<[f32; N], String> {
+        let Some(values) = value.as_array() else {
+            return Ok(default);
+        };
+        let values: Vec<f32> = values.iter().map(|v| v.as_f64().unwrap_or(0.0) as f32).collect();
+        <[f32; N]>::try_from(values).map_err(|_| format!("{} should be {} numbers", name, N))
+    }
+    let teapots = array(&root["nodes"]).iter()
+        .filter_map(|node| Some((node["mesh"].as_u64()?, node)))
+        .map(|(mesh, node)| {
+            let material = &root["meshes"][mesh as usize]["primitives"][0]["material"];
+            let [r, g, b, _] = floats(&root["materials"][material.as_u64().unwrap_or(u64::MAX) as usize]["pbrMetallicRoughness"]["baseColorFactor"], "baseColorFactor", [1.0; 4])?;
+            let translation = floats(&node["translation"], "translation", [0.0; 3])?;
+            let [x, y, z, w] = floats(&node["rotation"], "rotation", [0.0, 0.0, 0.0, 1.0])?;
+            Ok(Teapot {
+                position: flip(translation),
+                rotation: [-x, -y, z, w],
+                scale: floats(&node["scale"], "scale", [1.0; 3])?[0],
+                colour: [r, g, b],
+                ..Teapot::new([0.0; 3], [0.0; 3])
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    Ok((mesh, teapots))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Corner = ([f32; 3], [f32; 3], [f32; 2]);
+
+    /// Each triangle as its three corners, which stays the same when a loader reorders vertices
+    fn corners(mesh: &Mesh) -> Vec<[Corner; 3]> {
+        mesh.indices.chunks(3)
+            .map(|t| [0, 1, 2].map(|i| {
+                let index = t[i] as usize;
+                (mesh.positions[index], mesh.normals[index], mesh.uvs[index])
+            }))
+            .collect()
+    }
+
+    fn round_trip(name: &str, mesh: &Mesh) -> Mesh {
+        let path = std::env::temp_dir().join(format!("teapot-test-{}-{}", std::process::id(), name));
+        save(mesh, &path).unwrap();
+        let loaded = load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        loaded
+    }
+
+    #[test]
+    fn meshes_survive_a_round_trip() {
+        for mesh in [crate::teapot::mesh(), crate::bezier_teapot::mesh(4)] {
+            for name in ["mesh.obj", "mesh.gltf", "mesh.ply"] {
+                let loaded = round_trip(name, &mesh);
+                assert_eq!(corners(&loaded), corners(&mesh), "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn missing_normals_point_outwards() {
+        let mut mesh = crate::teapot::mesh();
+        let normals = mesh.normals.clone();
+        smooth_normals(&mut mesh);
+        let agreeing = normals.iter().zip(&mesh.normals)
+            .filter(|(a, b)| a[0] * b[0] + a[1] * b[1] + a[2] * b[2] > 0.0)
+            .count();
+        assert!(agreeing > normals.len() * 9 / 10, "{} of {}", agreeing, normals.len());
+    }
+
+    /// Reads the teapots in a glTF scene written by `save_scene`, along with the mesh they use
+    fn load_scene(path: &Path, mesh_scale: f32) -> Result<(Mesh, Vec<Teapot>), String> {
+        let (mesh, mut teapots) = load_gltf(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        teapots.iter_mut().for_each(|teapot| teapot.scale /= mesh_scale);
+        Ok((mesh, teapots))
+    }
+
+    #[test]
+    fn scenes_survive_a_round_trip() {
+        let mesh = crate::teapot::mesh();
+        let teapots: Vec<Teapot> = (0..10)
+            .map(|i| {
+                let angle = i as f32 * 0.3;
+                Teapot {
+                    position: [i as f32, -(i as f32) * 0.5, 2.0 * i as f32],
+                    rotation: [0.0, (angle / 2.0).sin(), 0.0, (angle / 2.0).cos()],
+                    scale: 0.5 * (i + 1) as f32,
+                    colour: if i % 3 == 0 { [1.0, 0.0, 0.0] } else { [0.0, 0.5, 1.0] },
+                    ..Teapot::new([0.0; 3], [0.0; 3])
+                }
+            })
+            .collect();
+
+        let path = std::env::temp_dir().join(format!("teapot-test-{}-scene.gltf", std::process::id()));
+        save_scene(&mesh, 0.25, &teapots, &path).unwrap();
+        let (loaded_mesh, loaded_teapots) = load_scene(&path, 0.25).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(corners(&loaded_mesh), corners(&mesh));
+        assert_eq!(loaded_teapots, teapots);
+    }
+    #[test]
+    fn malformed_scenes_are_errors() {
+        let path = std::env::temp_dir().join(format!("teapot-test-{}-malformed.gltf", std::process::id()));
+        save_scene(&crate::teapot::mesh(), 1.0, &[Teapot::new([1.0, 2.0, 3.0], [1.0; 3])], &path).unwrap();
+        assert!(load_gltf(&path).is_ok());
+        let scene: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let breaks: [fn(&mut Value); 4] = [
+            |scene| scene["nodes"].as_array_mut().unwrap().iter_mut().for_each(|node| node["translation"] = json!([1.0, 2.0])),
+            |scene| scene["materials"][0]["pbrMetallicRoughness"]["baseColorFactor"] = json!([1.0]),
+            |scene| scene["nodes"].as_array_mut().unwrap().iter_mut().for_each(|node| node["scale"] = json!([])),
+            |scene| {
+                let position = scene["meshes"][0]["primitives"][0]["attributes"]["POSITION"].as_u64().unwrap() as usize;
+                scene["accessors"][position]["type"] = "SCALAR".into();
+            },
+        ];
+        for (i, break_scene) in breaks.iter().enumerate() {
+            let mut broken = scene.clone();
+            break_scene(&mut broken);
+            std::fs::write(&path, broken.to_string()).unwrap();
+            assert!(load_gltf(&path).is_err(), "break {} loaded", i);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}
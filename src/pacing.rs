@@ -0,0 +1,48 @@
+use std::time::{Duration, Instant};
+
+/// Sleeping is only accurate to around a millisecond (much worse on some systems), so the last bit of the wait is spent spinning
+const SPIN_TIME: Duration = Duration::from_millis(2);
+
+/// Decides when to draw the next frame so the frame rate stays under a cap
+pub struct FramePacer {
+    period: Option<Duration>,
+    next_frame: Instant,
+}
+
+impl FramePacer {
+    /// A cap of 0 means no cap
+    pub fn new(fps_cap: f32) -> FramePacer {
+        FramePacer {
+            period: Self::period(fps_cap),
+            next_frame: Instant::now(),
+        }
+    }
+
+    fn period(fps_cap: f32) -> Option<Duration> {
+        (fps_cap > 0.0).then(|| Duration::from_secs_f64(1.0 / fps_cap as f64))
+    }
+
+    pub fn set_cap(&mut self, fps_cap: f32) {
+        self.period = Self::period(fps_cap);
+    }
+
+    /// Returns None if a frame should be drawn now, otherwise the time to sleep until before asking again
+    pub fn wait(&mut self) -> Option<Instant> {
+        let period = self.period?;
+
+        let now = Instant::now();
+        if now + SPIN_TIME < self.next_frame {
+            return Some(self.next_frame - SPIN_TIME);
+        }
+        while Instant::now() < self.next_frame {
+            std::hint::spin_loop();
+        }
+
+        // Keep a steady rhythm, unless we fell so far behind that catching up would mean drawing a burst of frames
+        self.next_frame += period;
+        if self.next_frame < now {
+            self.next_frame = now + period;
+        }
+        None
+    }
+}
@@ -0,0 +1,403 @@
+use crate::{mesh::Mesh, positions::{Teapot, Teapots}, spin};
+
+/// What teapots collide with each other as
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Shape {
+    /// The sphere around the whole teapot, fast but teapots bump into each other before they touch
+    Sphere,
+    /// The convex hull of the mesh, which fits snugly around the body but fills in the gap under the handle
+    Hull,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Settings {
+    pub gravity: [f32; 3],
+    /// How much speed is kept after a bounce, from 0 to 1
+    pub bounciness: f32,
+    pub friction: f32,
+    /// Height of the ground plane, if there is one
+    pub ground: Option<f32>,
+    /// Teapots bounce off the walls of the box from -range to range
+    pub range: f32,
+    pub shape: Shape,
+}
+
+/// Teapots falling, bouncing and knocking into each other, moved on a simulation step at a time
+pub struct Physics {
+    settings: Settings,
+    /// Points on the convex hull of the mesh, at the size teapots are drawn with a scale of 1
+    hull: Vec<[f32; 3]>,
+    /// Bounding radius at a scale of 1
+    radius: f32,
+    /// Unit length AXES, each followed by its opposite
+    axes: Vec<[f32; 3]>,
+}
+
+/// Speeds into a surface below this don't bounce, so teapots resting on the ground settle instead of jittering
+const REST_SPEED: f32 = 0.5;
+
+/// How much of the overlap between teapots is pushed apart each step
+const CORRECTION: f32 = 0.8;
+
+/// How quickly teapots stop rolling along the ground and walls, per second
+const ROLLING_RESISTANCE: f32 = 2.0;
+
+/// Directions evenly spread over a sphere
+fn directions(count: usize) -> Vec<[f32; 3]> {
+    let golden = std::f32::consts::PI * (3.0 - 5.0f32.sqrt());
+    (0..count)
+        .map(|i| {
+            let y = 1.0 - 2.0 * (i as f32 + 0.5) / count as f32;
+            let around = (1.0 - y * y).sqrt();
+            let angle = golden * i as f32;
+            [angle.cos() * around, y, angle.sin() * around]
+        })
+        .collect()
+}
+
+/// Towards the faces, edges and corners of a cube. Hulls are tested for collisions along these both ways, and along the line between the teapots
+const AXES: [[f32; 3]; 13] = [
+    [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0],
+    [1.0, 1.0, 0.0], [1.0, -1.0, 0.0], [1.0, 0.0, 1.0], [1.0, 0.0, -1.0], [0.0, 1.0, 1.0], [0.0, 1.0, -1.0],
+    [1.0, 1.0, 1.0], [1.0, 1.0, -1.0], [1.0, -1.0, 1.0], [-1.0, 1.0, 1.0],
+];
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn add(a: [f32; 3], b: [f32; 3], scale: f32) -> [f32; 3] {
+    [a[0] + b[0] * scale, a[1] + b[1] * scale, a[2] + b[2] * scale]
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+/// Where two teapots or a teapot and a wall touch
+struct Contact {
+    point: [f32; 3],
+    /// Unit length, pointing from the first teapot (or the wall) towards the second teapot
+    normal: [f32; 3],
+    depth: f32,
+}
+
+/// What the physics needs of a teapot, copied out of the teapots for a step and back again afterwards
+#[derive(Copy, Clone, Debug)]
+struct Rigid {
+    position: [f32; 3],
+    velocity: [f32; 3],
+    rotation: [f32; 4],
+    angular_velocity: [f32; 3],
+    scale: f32,
+    gravity: Option<[f32; 3]>,
+    /// Not flying into a formation
+    free: bool,
+}
+
+fn rigids(teapots: &Teapots) -> Vec<Rigid> {
+    (0..teapots.len())
+        .map(|i| Rigid {
+            position: teapots.position[i],
+            velocity: teapots.velocity[i],
+            rotation: teapots.rotation[i],
+            angular_velocity: teapots.details[i].angular_velocity,
+            scale: teapots.details[i].scale,
+            gravity: teapots.details[i].gravity,
+            free: teapots.path[i].is_none(),
+        })
+        .collect()
+}
+
+/// The mass and size of a teapot, which grow with its scale
+struct Body {
+    inverse_mass: f32,
+    /// Teapots spin like solid spheres
+    inverse_inertia: f32,
+}
+
+impl Physics {
+    pub fn new(settings: Settings, mesh: &Mesh, mesh_scale: f32) -> Physics {
+        let points: Vec<[f32; 3]> = mesh.positions.iter().map(|p| p.map(|v| v * mesh_scale)).collect();
+        // The furthest point in enough directions is close to the real hull and much quicker to search
+        let mut hull: Vec<[f32; 3]> = directions(64).into_iter()
+            .filter_map(|direction| points.iter().copied().max_by(|a, b| dot(*a, direction).total_cmp(&dot(*b, direction))))
+            .collect();
+        hull.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        hull.dedup();
+
+        let axes = AXES.iter()
+            .map(|axis| axis.map(|v| v / dot(*axis, *axis).sqrt()))
+            .flat_map(|axis| [axis, axis.map(|v| -v)])
+            .collect();
+
+        Physics { settings, hull, radius: mesh.bounding_radius() * mesh_scale, axes }
+    }
+
+    /// Moves every teapot forward by `dt` seconds. Teapots flying into a formation are left alone
+    pub fn step(&self, teapots: &mut Teapots, dt: f32) {
+        let mut bodies = rigids(teapots);
+        self.simulate(&mut bodies, dt);
+        for (i, body) in bodies.iter().enumerate() {
+            teapots.position[i] = body.position;
+            teapots.velocity[i] = body.velocity;
+            teapots.rotation[i] = body.rotation;
+            teapots.details[i].angular_velocity = body.angular_velocity;
+        }
+    }
+
+    fn simulate(&self, teapots: &mut [Rigid], dt: f32) {
+        for teapot in teapots.iter_mut().filter(|teapot| teapot.free) {
+            teapot.velocity = add(teapot.velocity, teapot.gravity.unwrap_or(self.settings.gravity), dt);
+            teapot.position = add(teapot.position, teapot.velocity, dt);
+
+            let [x, y, z] = teapot.angular_velocity;
+            let turn = spin::multiply([x, y, z, 0.0], teapot.rotation);
+            teapot.rotation = spin::normalise([0, 1, 2, 3].map(|i| teapot.rotation[i] + turn[i] * dt / 2.0));
+        }
+
+        self.collide_walls(teapots, dt);
+
+        // How far each hull reaches along each axis, worked out once instead of for every pair. Turning doesn't change during the step
+        let extents: Vec<[f32; 3]> = match self.settings.shape {
+            Shape::Sphere => Vec::new(),
+            Shape::Hull => teapots.iter().flat_map(|teapot| self.extents(teapot)).collect(),
+        };
+        let extent = |i: usize| extents.get(i * self.axes.len()..(i + 1) * self.axes.len()).unwrap_or_default();
+
+        // Sweep along x, so only teapots that overlap on x are checked against each other
+        let mut spans: Vec<(f32, f32, usize)> = teapots.iter().enumerate()
+            .filter(|(_, teapot)| teapot.free)
+            .map(|(i, teapot)| (teapot.position[0] - self.radius * teapot.scale, teapot.position[0] + self.radius * teapot.scale, i))
+            .collect();
+        spans.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for (k, &(_, right, i)) in spans.iter().enumerate() {
+            for &(_, _, j) in spans[k + 1..].iter().take_while(|(left, _, _)| *left <= right) {
+                if let Some(contact) = self.contact(&teapots[i], &teapots[j], extent(i), extent(j)) {
+                    if let Ok([a, b]) = teapots.get_disjoint_mut([i, j]) {
+                        self.resolve(a, b, &contact);
+                    }
+                }
+            }
+        }
+    }
+
+    fn body(&self, teapot: &Rigid) -> Body {
+        let mass = teapot.scale.powi(3);
+        let radius = self.radius * teapot.scale;
+        Body { inverse_mass: 1.0 / mass, inverse_inertia: 1.0 / (0.4 * mass * radius * radius) }
+    }
+
+    /// The point of `teapot` furthest along `direction`
+    fn support(&self, teapot: &Rigid, direction: [f32; 3]) -> [f32; 3] {
+        add(teapot.position, self.offset(teapot, direction), 1.0)
+    }
+
+    /// The point of `teapot` furthest along `direction`, from the centre of the teapot
+    fn offset(&self, teapot: &Rigid, direction: [f32; 3]) -> [f32; 3] {
+        match self.settings.shape {
+            Shape::Sphere => direction.map(|v| v * self.radius * teapot.scale),
+            Shape::Hull => {
+                let local = spin::rotate(spin::conjugate(teapot.rotation), direction);
+                let (furthest, _) = self.hull.iter().fold(([0.0; 3], f32::NEG_INFINITY), |best, &point| {
+                    let distance = dot(point, local);
+                    if distance > best.1 { (point, distance) } else { best }
+                });
+                spin::rotate(teapot.rotation, furthest).map(|v| v * teapot.scale)
+            },
+        }
+    }
+
+    /// The offsets of the hull of `teapot` along each of the axes, in one pass over the hull
+    fn extents(&self, teapot: &Rigid) -> Vec<[f32; 3]> {
+        let local: Vec<[f32; 3]> = self.axes.iter().step_by(2).map(|&axis| spin::rotate(spin::conjugate(teapot.rotation), axis)).collect();
+        let mut furthest = vec![(0, f32::NEG_INFINITY); self.axes.len()];
+        for (index, &point) in self.hull.iter().enumerate() {
+            for (k, &axis) in local.iter().enumerate() {
+                let distance = dot(point, axis);
+                if distance > furthest[2 * k].1 {
+                    furthest[2 * k] = (index, distance);
+                }
+                if -distance > furthest[2 * k + 1].1 {
+                    furthest[2 * k + 1] = (index, -distance);
+                }
+            }
+        }
+        furthest.iter().map(|&(index, _)| spin::rotate(teapot.rotation, self.hull[index]).map(|v| v * teapot.scale)).collect()
+    }
+
+    /// Bounces teapots off the ground and the walls of the range
+    fn collide_walls(&self, teapots: &mut [Rigid], dt: f32) {
+        let range = self.settings.range;
+        for teapot in teapots.iter_mut().filter(|teapot| teapot.free) {
+            // The hull is only searched when the bounding sphere reaches the wall
+            let radius = self.radius * teapot.scale;
+            for axis in 0..3 {
+                for side in [-1.0, 1.0] {
+                    if teapot.position[axis] * side + radius <= range {
+                        continue;
+                    }
+                    // Normals point back into the box
+                    let mut normal = [0.0; 3];
+                    normal[axis] = -side;
+                    let point = self.support(teapot, [0, 1, 2].map(|i| -normal[i]));
+                    let depth = point[axis] * side - range;
+                    if depth > 0.0 {
+                        self.resolve_static(teapot, &Contact { point, normal, depth }, dt);
+                    }
+                }
+            }
+
+            if let Some(ground) = self.settings.ground.filter(|&ground| teapot.position[1] - radius < ground) {
+                let point = self.support(teapot, [0.0, -1.0, 0.0]);
+                if point[1] < ground {
+                    self.resolve_static(teapot, &Contact { point, normal: [0.0, 1.0, 0.0], depth: ground - point[1] }, dt);
+                }
+            }
+        }
+    }
+
+    /// Where `a` and `b` overlap, if they do. `extent_a` and `extent_b` are the offsets of their hulls along each axis
+    fn contact(&self, a: &Rigid, b: &Rigid, extent_a: &[[f32; 3]], extent_b: &[[f32; 3]]) -> Option<Contact> {
+        let between = sub(b.position, a.position);
+        let distance = dot(between, between).sqrt();
+        let depth = self.radius * (a.scale + b.scale) - distance;
+        if depth <= 0.0 {
+            return None;
+        }
+        let centres = if distance > f32::EPSILON { between.map(|v| v / distance) } else { [0.0, 1.0, 0.0] };
+        if self.settings.shape == Shape::Sphere {
+            return Some(Contact { point: add(a.position, centres, self.radius * a.scale - depth / 2.0), normal: centres, depth });
+        }
+
+        // Two convex shapes overlap if they overlap along every direction, and are pushed apart along the one with the least overlap.
+        // Only a few directions are tried, so this is close but not exact
+        // The axes are cheaper so they go first, most pairs that don't touch are ruled out by them
+        let along_centres = [centres, centres.map(|v| -v)].into_iter().map(|normal| (normal, self.support(a, normal), self.support(b, normal.map(|v| -v))));
+        let along_axes = (0..self.axes.len()).map(|k| {
+            // The opposite axis is next to it
+            (self.axes[k], add(a.position, extent_a[k], 1.0), add(b.position, extent_b[k ^ 1], 1.0))
+        });
+
+        let mut best: Option<Contact> = None;
+        for (normal, furthest_a, furthest_b) in along_axes.chain(along_centres) {
+            let depth = dot(sub(furthest_a, furthest_b), normal);
+            if depth <= 0.0 {
+                return None;
+            }
+            if best.as_ref().is_none_or(|best| depth < best.depth) {
+                best = Some(Contact { point: [0, 1, 2].map(|i| (furthest_a[i] + furthest_b[i]) / 2.0), normal, depth });
+            }
+        }
+        best
+    }
+
+    /// Pushes two teapots apart and bounces them off each other
+    fn resolve(&self, a: &mut Rigid, b: &mut Rigid, contact: &Contact) {
+        let (body_a, body_b) = (self.body(a), self.body(b));
+        let normal = contact.normal;
+        let arm_a = sub(contact.point, a.position);
+        let arm_b = sub(contact.point, b.position);
+
+        let share = contact.depth * CORRECTION / (body_a.inverse_mass + body_b.inverse_mass);
+        a.position = add(a.position, normal, -share * body_a.inverse_mass);
+        b.position = add(b.position, normal, share * body_b.inverse_mass);
+
+        let relative = |a: &Rigid, b: &Rigid| sub(add(b.velocity, cross(b.angular_velocity, arm_b), 1.0), add(a.velocity, cross(a.angular_velocity, arm_a), 1.0));
+        let towards = dot(relative(a, b), normal);
+        if towards >= 0.0 {
+            return;
+        }
+        let bounciness = if -towards > REST_SPEED { self.settings.bounciness } else { 0.0 };
+        let resistance = |direction: [f32; 3]| body_a.inverse_mass + body_b.inverse_mass
+            + dot(cross(arm_a, direction), cross(arm_a, direction)) * body_a.inverse_inertia
+            + dot(cross(arm_b, direction), cross(arm_b, direction)) * body_b.inverse_inertia;
+        let impulse = -(1.0 + bounciness) * towards / resistance(normal);
+        let push = |a: &mut Rigid, b: &mut Rigid, direction: [f32; 3], amount: f32| {
+            a.velocity = add(a.velocity, direction, -amount * body_a.inverse_mass);
+            a.angular_velocity = add(a.angular_velocity, cross(arm_a, direction), -amount * body_a.inverse_inertia);
+            b.velocity = add(b.velocity, direction, amount * body_b.inverse_mass);
+            b.angular_velocity = add(b.angular_velocity, cross(arm_b, direction), amount * body_b.inverse_inertia);
+        };
+        push(a, b, normal, impulse);
+
+        // Friction slows the sliding, and can't push harder than the bounce did
+        let velocity = relative(a, b);
+        let sliding = add(velocity, normal, -dot(velocity, normal));
+        let speed = dot(sliding, sliding).sqrt();
+        if speed > f32::EPSILON {
+            let tangent = sliding.map(|v| v / speed);
+            let friction = (speed / resistance(tangent)).min(self.settings.friction * impulse);
+            push(a, b, tangent, -friction);
+        }
+    }
+
+    /// Pushes a teapot out of a wall or the ground and bounces it off
+    fn resolve_static(&self, teapot: &mut Rigid, contact: &Contact, dt: f32) {
+        let body = self.body(teapot);
+        let normal = contact.normal;
+        let arm = sub(contact.point, teapot.position);
+        teapot.position = add(teapot.position, normal, contact.depth);
+        teapot.angular_velocity = teapot.angular_velocity.map(|v| v * (1.0 - ROLLING_RESISTANCE * dt).max(0.0));
+
+        let point_velocity = |teapot: &Rigid| add(teapot.velocity, cross(teapot.angular_velocity, arm), 1.0);
+        let towards = dot(point_velocity(teapot), normal);
+        if towards >= 0.0 {
+            return;
+        }
+        let bounciness = if -towards > REST_SPEED { self.settings.bounciness } else { 0.0 };
+        let resistance = |direction: [f32; 3]| body.inverse_mass + dot(cross(arm, direction), cross(arm, direction)) * body.inverse_inertia;
+        let impulse = -(1.0 + bounciness) * towards / resistance(normal);
+        let push = |teapot: &mut Rigid, direction: [f32; 3], amount: f32| {
+            teapot.velocity = add(teapot.velocity, direction, amount * body.inverse_mass);
+            teapot.angular_velocity = add(teapot.angular_velocity, cross(arm, direction), amount * body.inverse_inertia);
+        };
+        push(teapot, normal, impulse);
+
+        let velocity = point_velocity(teapot);
+        let sliding = add(velocity, normal, -dot(velocity, normal));
+        let speed = dot(sliding, sliding).sqrt();
+        if speed > f32::EPSILON {
+            let tangent = sliding.map(|v| v / speed);
+            let friction = (speed / resistance(tangent)).min(self.settings.friction * impulse);
+            push(teapot, tangent, -friction);
+        }
+    }
+}
+
+/// Turns a teapot's spin into angular velocity, so it keeps turning the same way but can be knocked into a different spin
+pub fn take_spin(teapot: &mut Teapot) {
+    let spin = teapot.spin;
+    // The spin axis turns with the teapot, angular velocity is in the world
+    teapot.angular_velocity = spin::rotate(teapot.rotation, spin.axis).map(|v| -v * spin.speed);
+    teapot.rotation = spin::multiply(teapot.rotation, spin.rotation(0.0));
+    teapot.spin = spin::Spin { speed: 0.0, phase: 0.0, ..spin };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn physics(shape: Shape) -> Physics {
+        let settings = Settings { gravity: [0.0, -9.81, 0.0], bounciness: 0.5, friction: 0.4, ground: Some(0.0), range: 4.0, shape };
+        Physics::new(settings, &crate::teapot::mesh(), 0.01)
+    }
+
+    #[test]
+    fn falling_teapots_come_to_rest_on_the_ground() {
+        for shape in [Shape::Sphere, Shape::Hull] {
+            let physics = physics(shape);
+            let mut teapots = Teapots::from(vec![Teapot { angular_velocity: [0.0, 3.0, 1.0], ..Teapot::new([0.0, 3.0, 0.0], [1.0; 3]) }]);
+            for _ in 0..128 * 8 {
+                physics.step(&mut teapots, 1.0 / 128.0);
+            }
+            let lowest = physics.support(&rigids(&teapots)[0], [0.0, -1.0, 0.0]);
+            assert!(lowest[1].abs() < 0.02, "{:?} teapot stopped at {:?}", shape, lowest);
+            assert!(teapots.velocity[0].iter().all(|v| v.abs() < 0.1), "{:?} teapot still moving at {:?}", shape, teapots.velocity[0]);
+        }
+    }
+}
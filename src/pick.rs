@@ -0,0 +1,82 @@
+use crate::{instance::Instance, mesh::Mesh, positions::{self, Teapots}, spatial::SpatialIndex, spin};
+
+/// Unit direction of the ray from the camera through a point on the screen, given from -1 to 1 like OpenGL with y up
+pub fn ray(view: &[[f32; 4]; 4], perspective: &[[f32; 4]; 4], screen: [f32; 2]) -> [f32; 3] {
+    // The rows of the view matrix are the camera's right, up and forward directions
+    let right = [view[0][0], view[1][0], view[2][0]];
+    let up = [view[0][1], view[1][1], view[2][1]];
+    let forward = [view[0][2], view[1][2], view[2][2]];
+    let x = screen[0] / perspective[0][0];
+    let y = screen[1] / perspective[1][1];
+
+    let direction = [0, 1, 2].map(|i| right[i] * x + up[i] * y + forward[i]);
+    let length = direction.iter().map(|v| v * v).sum::<f32>().sqrt();
+    direction.map(|v| v / length)
+}
+
+/// How far along the ray a triangle is hit, from either side
+fn triangle(origin: [f32; 3], direction: [f32; 3], [a, b, c]: [[f32; 3]; 3]) -> Option<f32> {
+    let sub = |a: [f32; 3], b: [f32; 3]| [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+    let cross = |a: [f32; 3], b: [f32; 3]| [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]];
+    let dot = |a: [f32; 3], b: [f32; 3]| a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+
+    // Möller–Trumbore
+    let (edge1, edge2) = (sub(b, a), sub(c, a));
+    let p = cross(direction, edge2);
+    let determinant = dot(edge1, p);
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+    let to_origin = sub(origin, a);
+    let u = dot(to_origin, p) / determinant;
+    let q = cross(to_origin, edge1);
+    let v = dot(direction, q) / determinant;
+    let distance = dot(edge2, q) / determinant;
+    (u >= 0.0 && v >= 0.0 && u + v <= 1.0 && distance > 0.0).then_some(distance)
+}
+
+/// Finds the teapot under the crosshair or the mouse
+pub struct Picker {
+    /// Triangles of the mesh at the size teapots are drawn with a scale of 1
+    triangles: Vec<[[f32; 3]; 3]>,
+    radius: f32,
+}
+
+impl Picker {
+    pub fn new(mesh: &Mesh, mesh_scale: f32) -> Picker {
+        let triangles = mesh.indices.chunks_exact(3)
+            .map(|triangle| [0, 1, 2].map(|i| mesh.positions[triangle[i] as usize].map(|v| v * mesh_scale)))
+            .collect();
+        Picker { triangles, radius: mesh.bounding_radius() * mesh_scale }
+    }
+
+    /// Index of the nearest teapot hit by the ray from `origin` along the unit `direction`, drawn as `instances`.
+    /// Teapots near the ray are first found by their bounding spheres in `index`, then the ray is tested against their triangles
+    pub fn pick(&self, index: &dyn SpatialIndex, teapots: &Teapots, instances: &[Instance], origin: [f32; 3], direction: [f32; 3]) -> Option<usize> {
+        let (id, _) = index.ray(origin, direction, &mut |id| self.hit(&instances[positions::find(teapots, id)?], origin, direction))?;
+        positions::find(teapots, id)
+    }
+
+    /// How far along the ray `instance` is hit
+    fn hit(&self, instance: &Instance, origin: [f32; 3], direction: [f32; 3]) -> Option<f32> {
+        let scale = instance.instance_scale;
+        if scale <= 0.0 {
+            return None;
+        }
+        // Cheap miss for the bounding sphere first
+        let to = [0, 1, 2].map(|i| instance.world_position[i] - origin[i]);
+        let along: f32 = (0..3).map(|i| to[i] * direction[i]).sum();
+        let away = (0..3).map(|i| to[i] * to[i]).sum::<f32>() - along * along;
+        if away > (self.radius * scale).powi(2) {
+            return None;
+        }
+
+        // Into the space of the mesh, dividing both by the scale keeps distances along the ray the same
+        let inverse = spin::conjugate(instance.instance_rotation);
+        let local_origin = spin::rotate(inverse, to.map(|v| -v)).map(|v| v / scale);
+        let local_direction = spin::rotate(inverse, direction).map(|v| v / scale);
+        self.triangles.iter()
+            .filter_map(|&corners| triangle(local_origin, local_direction, corners))
+            .min_by(|a, b| a.total_cmp(b))
+    }
+}
@@ -0,0 +1,194 @@
+use std::io::{BufRead, Read};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Encoding {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Type {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Type {
+    fn is_float(self) -> bool {
+        matches!(self, Type::F32 | Type::F64)
+    }
+
+    fn parse(name: &str) -> Result<Type, String> {
+        match name {
+            "char" | "int8" => Ok(Type::I8),
+            "uchar" | "uint8" => Ok(Type::U8),
+            "short" | "int16" => Ok(Type::I16),
+            "ushort" | "uint16" => Ok(Type::U16),
+            "int" | "int32" => Ok(Type::I32),
+            "uint" | "uint32" => Ok(Type::U32),
+            "float" | "float32" => Ok(Type::F32),
+            "double" | "float64" => Ok(Type::F64),
+            _ => Err(format!("Unknown PLY type \"{}\"", name)),
+        }
+    }
+
+    fn read(self, reader: &mut impl Read, encoding: Encoding) -> Result<f64, String> {
+        let mut bytes = [0u8; 8];
+        let size = match self {
+            Type::I8 | Type::U8 => 1,
+            Type::I16 | Type::U16 => 2,
+            Type::I32 | Type::U32 | Type::F32 => 4,
+            Type::F64 => 8,
+        };
+        let bytes = &mut bytes[..size];
+        reader.read_exact(bytes).map_err(|e| format!("PLY data ended early: {}", e))?;
+        if encoding == Encoding::BigEndian {
+            bytes.reverse();
+        }
+
+        Ok(match self {
+            Type::I8 => bytes[0] as i8 as f64,
+            Type::U8 => bytes[0] as f64,
+            Type::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            Type::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            Type::I32 => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+            Type::U32 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+            Type::F32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+            Type::F64 => f64::from_le_bytes(bytes.try_into().unwrap()),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Property {
+    name: String,
+    value: Type,
+    /// Type of the length before the values if this property is a list
+    list: Option<Type>,
+}
+
+/// One kind of thing in a PLY file, such as vertices or faces
+#[derive(Debug)]
+pub struct Element {
+    pub name: String,
+    properties: Vec<Property>,
+    /// One row per item, with a list of values for each property (just one value unless it is a list property)
+    rows: Vec<Vec<Vec<f64>>>,
+}
+
+impl Element {
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Every value of a property that isn't a list, None if there is no such property
+    pub fn column(&self, property: &str) -> Option<Vec<f64>> {
+        let index = self.properties.iter().position(|p| p.name == property)?;
+        Some(self.rows.iter().map(|row| row[index].first().copied().unwrap_or(0.0)).collect())
+    }
+
+    /// Whether a property holds floats rather than integers, e.g. colours are usually 0 to 255 but can be floats from 0 to 1
+    pub fn is_float(&self, property: &str) -> bool {
+        self.properties.iter().any(|p| p.name == property && p.value.is_float())
+    }
+
+    /// Every value of a list property
+    pub fn lists(&self, property: &str) -> Option<Vec<Vec<f64>>> {
+        let index = self.properties.iter().position(|p| p.name == property)?;
+        Some(self.rows.iter().map(|row| row[index].clone()).collect())
+    }
+}
+
+/// Reads the elements of an ASCII or binary PLY file
+pub fn read(reader: impl Read) -> Result<Vec<Element>, String> {
+    let mut reader = std::io::BufReader::new(reader);
+    let mut line = String::new();
+    let mut next_line = |reader: &mut std::io::BufReader<_>| -> Result<String, String> {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) => Err("PLY header ended early".to_string()),
+            Ok(_) => Ok(line.trim().to_string()),
+            Err(e) => Err(e.to_string()),
+        }
+    };
+
+    if next_line(&mut reader)? != "ply" {
+        return Err("Not a PLY file".to_string());
+    }
+
+    let mut encoding = Encoding::Ascii;
+    let mut elements: Vec<(Element, usize)> = Vec::new();
+    loop {
+        let line = next_line(&mut reader)?;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["end_header"] => break,
+            ["format", format, _] => {
+                encoding = match *format {
+                    "ascii" => Encoding::Ascii,
+                    "binary_little_endian" => Encoding::LittleEndian,
+                    "binary_big_endian" => Encoding::BigEndian,
+                    _ => return Err(format!("Unknown PLY format \"{}\"", format)),
+                }
+            },
+            ["element", name, count] => {
+                let count = count.parse().map_err(|_| format!("Bad element count \"{}\"", count))?;
+                elements.push((Element { name: name.to_string(), properties: Vec::new(), rows: Vec::new() }, count));
+            },
+            ["property", "list", length, value, name] => {
+                let (element, _) = elements.last_mut().ok_or("PLY property before any element")?;
+                element.properties.push(Property { name: name.to_string(), value: Type::parse(value)?, list: Some(Type::parse(length)?) });
+            },
+            ["property", value, name] => {
+                let (element, _) = elements.last_mut().ok_or("PLY property before any element")?;
+                element.properties.push(Property { name: name.to_string(), value: Type::parse(value)?, list: None });
+            },
+            _ => (), // Comments and anything else
+        }
+    }
+
+    if encoding == Encoding::Ascii {
+        let mut text = String::new();
+        reader.read_to_string(&mut text).map_err(|e| e.to_string())?;
+        let mut values = text.split_whitespace().map(|word| word.parse::<f64>().map_err(|_| format!("Bad PLY value \"{}\"", word)));
+        let mut next = || values.next().unwrap_or(Err("PLY data ended early".to_string()));
+
+        for (element, count) in &mut elements {
+            for _ in 0..*count {
+                let row = element.properties.iter()
+                    .map(|property| match property.list {
+                        Some(_) => {
+                            let length = next()? as usize;
+                            (0..length).map(|_| next()).collect()
+                        },
+                        None => Ok(vec![next()?]),
+                    })
+                    .collect::<Result<_, String>>()?;
+                element.rows.push(row);
+            }
+        }
+    } else {
+        for (element, count) in &mut elements {
+            for _ in 0..*count {
+                let row = element.properties.iter()
+                    .map(|property| match property.list {
+                        Some(length) => {
+                            let length = length.read(&mut reader, encoding)? as usize;
+                            (0..length).map(|_| property.value.read(&mut reader, encoding)).collect()
+                        },
+                        None => Ok(vec![property.value.read(&mut reader, encoding)?]),
+                    })
+                    .collect::<Result<_, String>>()?;
+                element.rows.push(row);
+            }
+        }
+    }
+
+    Ok(elements.into_iter().map(|(element, _)| element).collect())
+}
@@ -0,0 +1,420 @@
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use crate::{formation::Move, ply, spin::Spin};
+
+/// A teapot in the scene
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Teapot {
+    pub position: [f32; 3],
+    pub colour: [f32; 3],
+    /// Multiplies the size of the teapot
+    pub scale: f32,
+    /// Quaternion as x, y, z, w
+    pub rotation: [f32; 4],
+    /// Where the teapot is flying to in a formation, not saved
+    pub path: Option<Move>,
+    /// Not saved either
+    pub spin: Spin,
+    /// Seconds after the start when the teapot appeared, not saved
+    pub spawned: f32,
+    /// When the teapot was despawned, it is removed once it has shrunk away
+    pub dying: Option<f32>,
+    /// Seconds after spawning when the teapot despawns on its own
+    pub lifetime: Option<f32>,
+    /// Units per second, used by --physics
+    pub velocity: [f32; 3],
+    /// Axis times radians per second in the world, used by --physics
+    pub angular_velocity: [f32; 3],
+    /// Pulls this teapot instead of the --gravity, fired teapots have their own
+    pub gravity: Option<[f32; 3]>,
+    /// Stays the same while other teapots come and go, and newer teapots have higher ids
+    pub id: usize,
+    /// Position and rotation before the last simulation step, drawn in between steps. None before its first step
+    pub previous: Option<([f32; 3], [f32; 4])>,
+}
+
+impl Teapot {
+    pub fn new(position: [f32; 3], colour: [f32; 3]) -> Teapot {
+        Teapot {
+            position,
+            colour,
+            scale: 1.0,
+            rotation: [0.0, 0.0, 0.0, 1.0],
+            path: None,
+            spin: Spin::default(),
+            spawned: 0.0,
+            dying: None,
+            lifetime: None,
+            velocity: [0.0; 3],
+            angular_velocity: [0.0; 3],
+            gravity: None,
+            id: 0,
+            previous: None,
+        }
+    }
+}
+
+/// The parts of a teapot that don't change on every step
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Details {
+    pub colour: [f32; 3],
+    pub scale: f32,
+    pub spin: Spin,
+    pub spawned: f32,
+    pub dying: Option<f32>,
+    pub lifetime: Option<f32>,
+    pub angular_velocity: [f32; 3],
+    pub gravity: Option<[f32; 3]>,
+    pub id: usize,
+}
+
+/// Every teapot, with the fields the simulation moves on every step each in an array of its own and the rest in `details`.
+/// A pass over the teapots only reads the arrays it uses, and the arrays split into chunks for threads at the same indices
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Teapots {
+    pub position: Vec<[f32; 3]>,
+    pub velocity: Vec<[f32; 3]>,
+    pub rotation: Vec<[f32; 4]>,
+    pub previous: Vec<Option<([f32; 3], [f32; 4])>>,
+    pub path: Vec<Option<Move>>,
+    pub details: Vec<Details>,
+}
+
+impl Teapots {
+    pub fn len(&self) -> usize {
+        self.details.len()
+    }
+
+    pub fn push(&mut self, teapot: Teapot) {
+        self.position.push(teapot.position);
+        self.velocity.push(teapot.velocity);
+        self.rotation.push(teapot.rotation);
+        self.previous.push(teapot.previous);
+        self.path.push(teapot.path);
+        self.details.push(Details {
+            colour: teapot.colour,
+            scale: teapot.scale,
+            spin: teapot.spin,
+            spawned: teapot.spawned,
+            dying: teapot.dying,
+            lifetime: teapot.lifetime,
+            angular_velocity: teapot.angular_velocity,
+            gravity: teapot.gravity,
+            id: teapot.id,
+        });
+    }
+
+    /// The whole teapot at `index`
+    pub fn get(&self, index: usize) -> Teapot {
+        let details = self.details[index];
+        Teapot {
+            position: self.position[index],
+            colour: details.colour,
+            scale: details.scale,
+            rotation: self.rotation[index],
+            path: self.path[index],
+            spin: details.spin,
+            spawned: details.spawned,
+            dying: details.dying,
+            lifetime: details.lifetime,
+            velocity: self.velocity[index],
+            angular_velocity: details.angular_velocity,
+            gravity: details.gravity,
+            id: details.id,
+            previous: self.previous[index],
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<Teapot> {
+        (0..self.len()).map(|index| self.get(index)).collect()
+    }
+
+    /// Removes the teapots `keep` says no to, keeping the rest in order
+    pub fn retain(&mut self, keep: impl Fn(&Details) -> bool) {
+        let kept: Vec<bool> = self.details.iter().map(keep).collect();
+        fn retain<T>(values: &mut Vec<T>, kept: &[bool]) {
+            let mut kept = kept.iter();
+            values.retain(|_| *kept.next().unwrap());
+        }
+        retain(&mut self.position, &kept);
+        retain(&mut self.velocity, &kept);
+        retain(&mut self.rotation, &kept);
+        retain(&mut self.previous, &kept);
+        retain(&mut self.path, &kept);
+        retain(&mut self.details, &kept);
+    }
+}
+
+impl From<Vec<Teapot>> for Teapots {
+    fn from(teapots: Vec<Teapot>) -> Teapots {
+        let mut split = Teapots::default();
+        teapots.into_iter().for_each(|teapot| split.push(teapot));
+        split
+    }
+}
+
+/// Index of the teapot with `id`. Teapots are only ever added at the end and removed, so they stay sorted by id
+pub fn find(teapots: &Teapots, id: usize) -> Option<usize> {
+    teapots.details.binary_search_by_key(&id, |details| details.id).ok()
+}
+
+/// Columns of a CSV file without a header, and the header written when saving
+const CSV_COLUMNS: [&str; 9] = ["x", "y", "z", "colour", "scale", "qx", "qy", "qz", "qw"];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Format {
+    Csv,
+    Json,
+    Ply,
+}
+
+fn format(path: &Path) -> Result<Format, String> {
+    let extension = path.extension().map(|extension| extension.to_string_lossy().to_lowercase());
+    match extension.as_deref() {
+        Some("csv") => Ok(Format::Csv),
+        Some("json") => Ok(Format::Json),
+        Some("ply") => Ok(Format::Ply),
+        _ => Err(format!("{}: unknown positions format, use .csv, .json or .ply", path.display())),
+    }
+}
+
+/// Reads teapots from a CSV, JSON or PLY file. Teapots without a colour get `colour`
+pub fn load(path: &Path, colour: [f32; 3]) -> Result<Vec<Teapot>, String> {
+    let error = |e: String| format!("{}: {}", path.display(), e);
+    let teapots = match format(path)? {
+        Format::Csv => load_csv(&std::fs::read_to_string(path).map_err(|e| error(e.to_string()))?, colour),
+        Format::Json => load_json(&std::fs::read_to_string(path).map_err(|e| error(e.to_string()))?, colour),
+        Format::Ply => load_ply(std::fs::File::open(path).map_err(|e| error(e.to_string()))?, colour),
+    };
+    let mut teapots = teapots.map_err(error)?;
+
+    for teapot in &mut teapots {
+        let [x, y, z, w] = teapot.rotation;
+        let length = (x * x + y * y + z * z + w * w).sqrt();
+        teapot.rotation = if length > 0.0 { teapot.rotation.map(|v| v / length) } else { [0.0, 0.0, 0.0, 1.0] };
+    }
+    Ok(teapots)
+}
+
+/// Writes teapots to a CSV, JSON or PLY file that `load` can read back
+pub fn save(teapots: &[Teapot], path: &Path) -> Result<(), String> {
+    let bytes = match format(path)? {
+        Format::Csv => csv(teapots).into_bytes(),
+        Format::Json => serde_json::to_string_pretty(&json(teapots)).unwrap().into_bytes(),
+        Format::Ply => ply(teapots),
+    };
+    std::fs::write(path, bytes).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Colours can be 0 to 1 or 0 to 255
+fn colour_from(rgb: [f32; 3]) -> [f32; 3] {
+    if rgb.iter().any(|v| *v > 1.0) { rgb.map(|v| v / 255.0) } else { rgb }
+}
+
+fn hex(colour: [f32; 3]) -> String {
+    let [r, g, b] = colour.map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8);
+    format!("#{:02X}{:02X}{:02X}", r, g, b)
+}
+
+/// The first line is a header if it isn't all numbers. Known columns are x, y, z, colour (#RRGGBB), r, g, b, scale and qx, qy, qz, qw
+/// for rotation, anything else is ignored. Without a header the columns are in the order of CSV_COLUMNS
+fn load_csv(text: &str, colour: [f32; 3]) -> Result<Vec<Teapot>, String> {
+    let mut lines = text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(i, line)| (i + 1, line.split(',').map(str::trim).collect::<Vec<&str>>()))
+        .peekable();
+
+    let is_header = lines.peek().is_some_and(|(_, fields)| fields.iter().any(|field| field.parse::<f32>().is_err() && !field.starts_with('#')));
+    let columns: Vec<String> = match is_header {
+        true => lines.next().unwrap().1.iter().map(|name| name.to_lowercase()).collect(),
+        false => CSV_COLUMNS.iter().map(|name| name.to_string()).collect(),
+    };
+    let column = |names: &[&str]| columns.iter().position(|column| names.contains(&column.as_str()));
+    let (x, y, z) = match (column(&["x"]), column(&["y"]), column(&["z"])) {
+        (Some(x), Some(y), Some(z)) => (x, y, z),
+        _ => return Err("needs x, y and z columns".to_string()),
+    };
+    let hex_colour = column(&["colour", "color"]);
+    let rgb = [column(&["r", "red"]), column(&["g", "green"]), column(&["b", "blue"])];
+    let scale = column(&["scale"]);
+    let rotation = [column(&["qx"]), column(&["qy"]), column(&["qz"]), column(&["qw"])];
+
+    lines
+        .map(|(line_number, fields)| {
+            // Missing and empty fields are None
+            let field = |index: Option<usize>| index.and_then(|i| fields.get(i)).filter(|field| !field.is_empty());
+            let number = |index: Option<usize>| -> Result<Option<f32>, String> {
+                field(index).map(|field| field.parse().map_err(|_| format!("line {}: bad number \"{}\"", line_number, field))).transpose()
+            };
+
+            let required = |index: usize, name: &str| number(Some(index))?.ok_or_else(|| format!("line {}: missing {}", line_number, name));
+
+            let mut teapot = Teapot::new([required(x, "x")?, required(y, "y")?, required(z, "z")?], colour);
+            if let Some(hex) = field(hex_colour) {
+                teapot.colour = crate::colour::hex_to_rgb(hex).map_err(|e| format!("line {}: {} \"{}\"", line_number, e, hex))?;
+            }
+            if let [Some(r), Some(g), Some(b)] = [number(rgb[0])?, number(rgb[1])?, number(rgb[2])?] {
+                teapot.colour = colour_from([r, g, b]);
+            }
+            teapot.scale = number(scale)?.unwrap_or(1.0);
+            if let [Some(qx), Some(qy), Some(qz), Some(qw)] = [number(rotation[0])?, number(rotation[1])?, number(rotation[2])?, number(rotation[3])?] {
+                teapot.rotation = [qx, qy, qz, qw];
+            }
+            Ok(teapot)
+        })
+        .collect()
+}
+
+fn csv(teapots: &[Teapot]) -> String {
+    let mut text = CSV_COLUMNS.join(",") + "\n";
+    for teapot in teapots {
+        let [x, y, z] = teapot.position;
+        let [qx, qy, qz, qw] = teapot.rotation;
+        text += &format!("{},{},{},{},{},{},{},{},{}\n", x, y, z, hex(teapot.colour), teapot.scale, qx, qy, qz, qw);
+    }
+    text
+}
+
+/// Either a list of teapots or an object with a "teapots" list. Each teapot is [x, y, z] or an object with "position" (or "x", "y"
+/// and "z"), and optionally "colour" as "#RRGGBB" or [r, g, b], "scale" and "rotation" as a quaternion [x, y, z, w]
+fn load_json(text: &str, colour: [f32; 3]) -> Result<Vec<Teapot>, String> {
+    let root: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    let list = root.as_array().or_else(|| root["teapots"].as_array()).ok_or("expected a list of teapots")?;
+
+    let numbers = |value: &Value, count: usize| -> Option<Vec<f32>> {
+        let values: Vec<f32> = value.as_array()?.iter().map(|v| v.as_f64().map(|v| v as f32)).collect::<Option<_>>()?;
+        (values.len() == count).then_some(values)
+    };
+
+    list.iter().enumerate()
+        .map(|(i, entry)| {
+            let error = |what: &str| format!("teapot {}: {}", i, what);
+            let position = numbers(entry, 3)
+                .or_else(|| numbers(&entry["position"], 3))
+                .or_else(|| ["x", "y", "z"].iter().map(|axis| entry[axis].as_f64().map(|v| v as f32)).collect())
+                .ok_or_else(|| error("needs a position"))?;
+
+            let mut teapot = Teapot::new([position[0], position[1], position[2]], colour);
+            let colour = if entry["colour"].is_null() { &entry["color"] } else { &entry["colour"] };
+            if let Some(hex) = colour.as_str() {
+                teapot.colour = crate::colour::hex_to_rgb(hex).map_err(error)?;
+            } else if !colour.is_null() {
+                let rgb = numbers(colour, 3).ok_or_else(|| error("colour should be \"#RRGGBB\" or [r, g, b]"))?;
+                teapot.colour = colour_from([rgb[0], rgb[1], rgb[2]]);
+            }
+            if !entry["scale"].is_null() {
+                teapot.scale = entry["scale"].as_f64().ok_or_else(|| error("scale should be a number"))? as f32;
+            }
+            if !entry["rotation"].is_null() {
+                let q = numbers(&entry["rotation"], 4).ok_or_else(|| error("rotation should be a quaternion [x, y, z, w]"))?;
+                teapot.rotation = [q[0], q[1], q[2], q[3]];
+            }
+            Ok(teapot)
+        })
+        .collect()
+}
+
+fn json(teapots: &[Teapot]) -> Value {
+    teapots.iter()
+        .map(|teapot| json!({ "position": teapot.position, "colour": teapot.colour, "scale": teapot.scale, "rotation": teapot.rotation }))
+        .collect()
+}
+
+/// The vertices of a point cloud, with optional red, green and blue, scale and qx, qy, qz, qw properties
+fn load_ply(file: impl std::io::Read, colour: [f32; 3]) -> Result<Vec<Teapot>, String> {
+    let elements = ply::read(file)?;
+    let vertex = elements.iter().find(|element| element.name == "vertex").ok_or("no vertex element")?;
+
+    let columns = |names: &[&str]| -> Option<Vec<Vec<f64>>> { names.iter().map(|name| vertex.column(name)).collect() };
+    let positions = columns(&["x", "y", "z"]).ok_or("vertices need x, y and z")?;
+    let colours = columns(&["red", "green", "blue"]);
+    // Colours are 0 to 255 unless they are floats
+    let colour_scale = if vertex.is_float("red") { 1.0 } else { 1.0 / 255.0 };
+    let scales = vertex.column("scale");
+    let rotations = columns(&["qx", "qy", "qz", "qw"]);
+
+    Ok((0..vertex.len())
+        .map(|i| {
+            let mut teapot = Teapot::new([0, 1, 2].map(|axis| positions[axis][i] as f32), colour);
+            if let Some(colours) = &colours {
+                teapot.colour = [0, 1, 2].map(|c| (colours[c][i] * colour_scale) as f32);
+            }
+            if let Some(scales) = &scales {
+                teapot.scale = scales[i] as f32;
+            }
+            if let Some(rotations) = &rotations {
+                teapot.rotation = [0, 1, 2, 3].map(|c| rotations[c][i] as f32);
+            }
+            teapot
+        })
+        .collect())
+}
+
+fn ply(teapots: &[Teapot]) -> Vec<u8> {
+    let header = format!(
+        "ply\nformat binary_little_endian 1.0\ncomment Teapots\n\
+         element vertex {}\nproperty float x\nproperty float y\nproperty float z\n\
+         property uchar red\nproperty uchar green\nproperty uchar blue\nproperty float scale\n\
+         property float qx\nproperty float qy\nproperty float qz\nproperty float qw\nend_header\n",
+        teapots.len(),
+    );
+
+    let mut bytes = header.into_bytes();
+    for teapot in teapots {
+        bytes.extend(teapot.position.iter().flat_map(|v| v.to_le_bytes()));
+        bytes.extend(teapot.colour.map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8));
+        bytes.extend(teapot.scale.to_le_bytes());
+        bytes.extend(teapot.rotation.iter().flat_map(|v| v.to_le_bytes()));
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn teapots_survive_a_round_trip() {
+        let angle = 0.7f32;
+        // Colours that are exact in 8 bits, which is what CSV and PLY store
+        let teapots: Vec<Teapot> = (0..20)
+            .map(|i| Teapot {
+                position: [i as f32 * 1.5, -(i as f32), 0.25 * i as f32],
+                colour: [i as f32 * 10.0 / 255.0, 1.0, 0.0],
+                scale: 1.0 + i as f32 * 0.1,
+                rotation: [0.0, (angle / 2.0).sin(), 0.0, (angle / 2.0).cos()],
+                ..Teapot::new([0.0; 3], [0.0; 3])
+            })
+            .collect();
+
+        for name in ["teapots.csv", "teapots.json", "teapots.ply"] {
+            let path = std::env::temp_dir().join(format!("teapot-test-{}-{}", std::process::id(), name));
+            save(&teapots, &path).unwrap();
+            let loaded = load(&path, [1.0, 1.0, 1.0]).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(loaded.len(), teapots.len(), "{}", name);
+            for (loaded, teapot) in loaded.iter().zip(&teapots) {
+                assert_eq!(loaded.position, teapot.position, "{}", name);
+                assert_eq!(loaded.scale, teapot.scale, "{}", name);
+                assert!(loaded.colour.iter().zip(teapot.colour).all(|(a, b)| (a - b).abs() < 1e-6), "{}", name);
+                assert!(loaded.rotation.iter().zip(teapot.rotation).all(|(a, b)| (a - b).abs() < 1e-6), "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn csv_columns_come_from_the_header() {
+        let text = "id,Z,y,x,red,green,blue\n7,3,2,1,255,0,0\n8,6,5,4,,,\n";
+        let teapots = load_csv(text, [0.0, 0.0, 1.0]).unwrap();
+        assert_eq!(teapots, vec![
+            Teapot::new([1.0, 2.0, 3.0], [1.0, 0.0, 0.0]),
+            Teapot::new([4.0, 5.0, 6.0], [0.0, 0.0, 1.0]),
+        ]);
+        assert_eq!(load_csv("x,y,z\n1,2,3\n4,,6\n", [1.0; 3]), Err("line 3: missing y".to_string()));
+        assert_eq!(load_csv("x,y,z\n1,2\n", [1.0; 3]), Err("line 2: missing z".to_string()));
+    }
+}
@@ -0,0 +1,276 @@
+use std::path::PathBuf;
+
+use glium::{framebuffer::{DepthRenderBuffer, RenderBuffer, SimpleFrameBuffer}, program::ProgramCreationInput, texture::{DepthFormat, MipmapsOption, TextureFormat, UncompressedFloatFormat}, uniforms::{MagnifySamplerFilter, MinifySamplerFilter, SamplerWrapFunction, UniformValue, Uniforms}, BlitTarget, Surface, Texture2d};
+
+use crate::shader;
+
+/// A full screen pass, run in the order given to --post
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pass {
+    /// Makes bright parts of the image glow
+    Bloom,
+    /// ACES filmic tonemapping, brings HDR colours (e.g. from an HDR skybox) back into 0 to 1
+    Tonemap,
+    Gamma,
+    /// Fast approximate anti-aliasing, best after tonemap and gamma
+    Fxaa,
+    Vignette,
+    ChromaticAberration,
+    /// A fragment shader from a file, see the README for the uniforms it gets
+    Custom(PathBuf),
+}
+
+/// Parses a pass name given to --post, anything that isn't a built in pass is the path to a fragment shader
+pub fn parse_pass(name: &str) -> Result<Pass, String> {
+    match name {
+        "bloom" => Ok(Pass::Bloom),
+        "tonemap" => Ok(Pass::Tonemap),
+        "gamma" => Ok(Pass::Gamma),
+        "fxaa" => Ok(Pass::Fxaa),
+        "vignette" => Ok(Pass::Vignette),
+        "chromatic-aberration" => Ok(Pass::ChromaticAberration),
+        path if std::path::Path::new(path).is_file() => Ok(Pass::Custom(path.into())),
+        _ => Err(format!("Unknown pass \"{}\", expected bloom, tonemap, gamma, fxaa, vignette, chromatic-aberration or the path to a fragment shader", name)),
+    }
+}
+
+/// Values the passes are tuned with, every pass gets all of them
+#[derive(Copy, Clone, Debug)]
+pub struct Settings {
+    pub bloom_threshold: f32,
+    pub bloom_intensity: f32,
+    pub exposure: f32,
+    pub gamma: f32,
+    pub vignette: f32,
+    pub chromatic_aberration: f32,
+}
+
+/// The textures everything is drawn to before the passes, resized along with the window
+struct Targets {
+    /// Two textures to ping-pong between, the scene is drawn to the first one
+    colour: [Texture2d; 2],
+    depth: DepthRenderBuffer,
+    /// Half resolution textures for blurring the bloom
+    bloom: [Texture2d; 2],
+    /// With MSAA the scene is drawn here instead, then resolved into the first colour texture
+    multisample: Option<(RenderBuffer, DepthRenderBuffer)>,
+}
+
+impl Targets {
+    fn new(display: &impl glium::backend::Facade, (width, height): (u32, u32), samples: u32) -> Targets {
+        let (width, height) = (width.max(1), height.max(1));
+        let texture = |width: u32, height: u32| {
+            Texture2d::empty_with_format(display, UncompressedFloatFormat::F16F16F16F16, MipmapsOption::NoMipmap, width.max(1), height.max(1)).unwrap()
+        };
+
+        let multisample = (samples > 1).then(|| (
+            RenderBuffer::new_multisample(display, UncompressedFloatFormat::F16F16F16F16, width, height, samples).unwrap(),
+            DepthRenderBuffer::new_multisample(display, DepthFormat::F32, width, height, samples).unwrap(),
+        ));
+
+        Targets {
+            colour: [texture(width, height), texture(width, height)],
+            depth: DepthRenderBuffer::new(display, DepthFormat::F32, width, height).unwrap(),
+            bloom: [texture(width / 2, height / 2), texture(width / 2, height / 2)],
+            multisample,
+        }
+    }
+
+    fn dimensions(&self) -> (u32, u32) {
+        self.colour[0].dimensions()
+    }
+}
+
+/// Draws the scene into an HDR texture and then runs it through a chain of full screen passes onto the screen
+pub struct PostProcess {
+    passes: Vec<(Pass, glium::Program)>,
+    copy: glium::Program,
+    bloom_extract: glium::Program,
+    blur: glium::Program,
+    pub settings: Settings,
+    /// MSAA samples per pixel, 1 turns it off
+    samples: u32,
+    targets: Targets,
+}
+
+fn pass_program(display: &impl glium::backend::Facade, fragment: &str, outputs_srgb: bool) -> Result<glium::Program, String> {
+    let vertex = shader::preprocess(include_str!("../shaders/post/post.vert"));
+    let fragment = shader::preprocess(fragment);
+    glium::Program::new(display, ProgramCreationInput::SourceCode {
+        vertex_shader: &vertex,
+        tessellation_control_shader: None,
+        tessellation_evaluation_shader: None,
+        geometry_shader: None,
+        fragment_shader: &fragment,
+        transform_feedback_varyings: None,
+        outputs_srgb,
+        uses_point_size: false,
+    }).map_err(|e| format!("{}", e))
+}
+
+/// Draws a triangle covering all of `target`
+fn draw_pass(target: &mut impl Surface, program: &glium::Program, uniforms: &impl Uniforms) {
+    let vertices = glium::vertex::EmptyVertexAttributes { len: 3 };
+    let indices = glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
+    target.draw(vertices, indices, program, uniforms, &Default::default()).unwrap();
+}
+
+impl PostProcess {
+    /// `samples` above 1 turns on MSAA, it is lowered to the most the graphics card supports
+    pub fn new(display: &impl glium::backend::Facade, passes: &[Pass], settings: Settings, samples: u32) -> Result<PostProcess, String> {
+        let mut compiled = Vec::new();
+        // Once gamma has been applied the colours are already sRGB, so glium shouldn't convert them a second time
+        let mut after_gamma = false;
+        for pass in passes {
+            after_gamma |= *pass == Pass::Gamma;
+            let source = match pass {
+                Pass::Bloom => include_str!("../shaders/post/bloom_composite.frag").to_string(),
+                Pass::Tonemap => include_str!("../shaders/post/tonemap.frag").to_string(),
+                Pass::Gamma => include_str!("../shaders/post/gamma.frag").to_string(),
+                Pass::Fxaa => include_str!("../shaders/post/fxaa.frag").to_string(),
+                Pass::Vignette => include_str!("../shaders/post/vignette.frag").to_string(),
+                Pass::ChromaticAberration => include_str!("../shaders/post/chromatic_aberration.frag").to_string(),
+                Pass::Custom(path) => std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?,
+            };
+            let program = pass_program(display, &source, after_gamma).map_err(|e| match pass {
+                Pass::Custom(path) => format!("{}: {}", path.display(), e),
+                _ => e,
+            })?;
+            compiled.push((pass.clone(), program));
+        }
+
+        Ok(PostProcess {
+            passes: compiled,
+            copy: pass_program(display, include_str!("../shaders/post/copy.frag"), false)?,
+            bloom_extract: pass_program(display, include_str!("../shaders/post/bloom_extract.frag"), false)?,
+            blur: pass_program(display, include_str!("../shaders/post/blur.frag"), false)?,
+            settings,
+            samples: samples.clamp(1, max_samples(display)),
+            targets: Targets::new(display, (1, 1), 1),
+        })
+    }
+
+    /// MSAA samples actually used
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// Makes the textures match the size of the window, call before drawing each frame
+    pub fn resize(&mut self, display: &impl glium::backend::Facade, dimensions: (u32, u32)) {
+        if self.targets.dimensions() != dimensions {
+            self.targets = Targets::new(display, dimensions, self.samples);
+        }
+    }
+
+    /// Framebuffer to draw the scene to
+    pub fn scene_target(&self, display: &impl glium::backend::Facade) -> SimpleFrameBuffer<'_> {
+        match &self.targets.multisample {
+            Some((colour, depth)) => SimpleFrameBuffer::with_depth_buffer(display, colour, depth).unwrap(),
+            None => SimpleFrameBuffer::with_depth_buffer(display, &self.targets.colour[0], &self.targets.depth).unwrap(),
+        }
+    }
+
+    /// Runs every pass, the last one draws straight onto `frame`
+    pub fn apply(&self, display: &impl glium::backend::Facade, frame: &mut impl Surface, time: f32) {
+        let targets = &self.targets;
+
+        // Blitting a multisampled buffer averages the samples
+        if let Some((colour, _)) = &targets.multisample {
+            let (width, height) = targets.dimensions();
+            let resolved = SimpleFrameBuffer::new(display, &targets.colour[0]).unwrap();
+            let target = BlitTarget { left: 0, bottom: 0, width: width as i32, height: height as i32 };
+            SimpleFrameBuffer::new(display, colour).unwrap().blit_whole_color_to(&resolved, &target, MagnifySamplerFilter::Nearest);
+        }
+
+        if self.passes.is_empty() {
+            draw_pass(frame, &self.copy, &self.uniforms(&targets.colour[0], time));
+            return;
+        }
+
+        let mut current = 0;
+        for (i, (pass, program)) in self.passes.iter().enumerate() {
+            let input = &targets.colour[current];
+
+            if *pass == Pass::Bloom {
+                self.blur_bloom(display, input, time);
+            }
+
+            let uniforms = self.uniforms(input, time);
+            if i == self.passes.len() - 1 {
+                draw_pass(frame, program, &uniforms);
+            } else {
+                let mut output = SimpleFrameBuffer::new(display, &targets.colour[1 - current]).unwrap();
+                draw_pass(&mut output, program, &uniforms);
+                current = 1 - current;
+            }
+        }
+    }
+
+    /// Extracts the bright parts of `input` into the first bloom texture and blurs them
+    fn blur_bloom(&self, display: &impl glium::backend::Facade, input: &Texture2d, time: f32) {
+        let bloom = &self.targets.bloom;
+
+        let mut first = SimpleFrameBuffer::new(display, &bloom[0]).unwrap();
+        let mut second = SimpleFrameBuffer::new(display, &bloom[1]).unwrap();
+
+        draw_pass(&mut first, &self.bloom_extract, &self.uniforms(input, time));
+        // Blurring twice gives a wider glow than one bigger blur for the same amount of samples
+        for _ in 0..2 {
+            draw_pass(&mut second, &self.blur, &self.uniforms(&bloom[0], time).with_direction([1.0, 0.0]));
+            draw_pass(&mut first, &self.blur, &self.uniforms(&bloom[1], time).with_direction([0.0, 1.0]));
+        }
+    }
+
+    fn uniforms<'a>(&'a self, input: &'a Texture2d, time: f32) -> PassUniforms<'a> {
+        PassUniforms { post: self, input, time, direction: [0.0, 0.0] }
+    }
+}
+
+/// The most MSAA samples the HDR format can be drawn with
+fn max_samples(display: &impl glium::backend::Facade) -> u32 {
+    use glium::CapabilitiesSource;
+
+    let format = TextureFormat::UncompressedFloat(UncompressedFloatFormat::F16F16F16F16);
+    display.get_context().get_capabilities().internal_formats_renderbuffers.get(&format)
+        .and_then(|info| info.multisamples.as_ref())
+        .and_then(|samples| samples.iter().max())
+        .map_or(1, |samples| *samples as u32)
+}
+
+struct PassUniforms<'a> {
+    post: &'a PostProcess,
+    input: &'a Texture2d,
+    time: f32,
+    direction: [f32; 2],
+}
+
+impl PassUniforms<'_> {
+    fn with_direction(self, direction: [f32; 2]) -> Self {
+        PassUniforms { direction, ..self }
+    }
+}
+
+impl Uniforms for PassUniforms<'_> {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut output: F) {
+        let behavior = glium::uniforms::SamplerBehavior {
+            minify_filter: MinifySamplerFilter::Linear,
+            magnify_filter: MagnifySamplerFilter::Linear,
+            wrap_function: (SamplerWrapFunction::Clamp, SamplerWrapFunction::Clamp, SamplerWrapFunction::Clamp),
+            .. Default::default()
+        };
+        let settings = &self.post.settings;
+        let (width, height) = self.input.dimensions();
+
+        output("u_texture", UniformValue::Texture2d(self.input, Some(behavior)));
+        output("u_bloom", UniformValue::Texture2d(&self.post.targets.bloom[0], Some(behavior)));
+        output("u_resolution", UniformValue::Vec2([width as f32, height as f32]));
+        output("u_time", UniformValue::Float(self.time));
+        output("u_direction", UniformValue::Vec2(self.direction));
+        output("u_bloom_threshold", UniformValue::Float(settings.bloom_threshold));
+        output("u_bloom_intensity", UniformValue::Float(settings.bloom_intensity));
+        output("u_exposure", UniformValue::Float(settings.exposure));
+        output("u_gamma", UniformValue::Float(settings.gamma));
+        output("u_vignette", UniformValue::Float(settings.vignette));
+        output("u_chromatic_aberration", UniformValue::Float(settings.chromatic_aberration));
+    }
+}
@@ -0,0 +1,39 @@
+use glium::winit::dpi::PhysicalPosition;
+
+/// How far in pixels the mouse has to move to wake up, small enough to catch a nudge but ignores sensor jitter
+const WAKE_DISTANCE: f64 = 8.0;
+/// Camera speed in units per second
+const SPEED: f32 = 3.0;
+
+/// Where the camera is at `time` seconds, flying a slow loop through the teapots with a gentle rise and fall.
+///
+/// Returns the position, yaw and pitch.
+pub fn camera(time: f32, range: f32) -> ([f32; 3], f32, f32) {
+    let radius = (range * 0.5).max(1.0);
+    let angle = time * SPEED / radius;
+    let height = (time * 0.2).sin() * radius * 0.2;
+
+    let position = [radius * angle.cos(), height, radius * angle.sin()];
+    // Along the loop, turned slightly inwards so the teapots in the middle stay in view
+    let yaw = angle + std::f32::consts::FRAC_PI_2 + 0.2;
+    // Tilted up and down with the rise and fall
+    let climb = (time * 0.2).cos() * 0.2 * radius * 0.2;
+    let pitch = (climb / SPEED).atan();
+    (position, yaw, pitch)
+}
+
+/// Decides when the mouse has moved enough to exit
+#[derive(Default)]
+pub struct Wake {
+    /// The first position seen, windows get a cursor position when they open even if the mouse didn't move
+    start: Option<PhysicalPosition<f64>>,
+}
+
+impl Wake {
+    /// Returns true once the cursor is far enough from where it started
+    pub fn cursor_moved(&mut self, position: PhysicalPosition<f64>) -> bool {
+        let start = *self.start.get_or_insert(position);
+        let (dx, dy) = (position.x - start.x, position.y - start.y);
+        dx * dx + dy * dy > WAKE_DISTANCE * WAKE_DISTANCE
+    }
+}
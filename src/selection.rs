@@ -0,0 +1,103 @@
+use glium::{uniform, Surface};
+
+use crate::{instance::Instance, lod::Level, positions::{self, Details, Teapots}, shader, spatial::SpatialIndex};
+
+/// Colour of the outline and the text about the selected teapot, bright enough to bloom
+pub const COLOUR: [f32; 3] = [1.5, 1.2, 0.2];
+
+/// How far around the selected teapot the HUD counts other teapots
+const NEIGHBOURHOOD: f32 = 10.0;
+
+/// The teapot picked with the right mouse button
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Selection {
+    /// Index into the teapots
+    pub index: Option<usize>,
+    /// Turn the camera to keep looking at the selected teapot
+    pub follow: bool,
+}
+
+impl Selection {
+    pub fn select(&mut self, index: Option<usize>) {
+        self.index = index;
+        self.follow &= index.is_some();
+    }
+
+    /// Removes the teapots `keep` says no to, keeping the selection on the same teapot
+    pub fn retain(&mut self, teapots: &mut Teapots, keep: impl Fn(&Details) -> bool) {
+        if let Some(index) = self.index {
+            let removed_before = teapots.details[..index].iter().filter(|details| !keep(details)).count();
+            self.select(keep(&teapots.details[index]).then(|| index - removed_before));
+        }
+        teapots.retain(keep);
+    }
+
+    /// Yaw and pitch that look at the selected teapot from `camera`, when following it
+    pub fn look_at(&self, teapots: &Teapots, camera: [f32; 3]) -> Option<(f32, f32)> {
+        let position = teapots.position.get(self.index.filter(|_| self.follow)?)?;
+        let [x, y, z] = [0, 1, 2].map(|i| position[i] - camera[i]);
+        Some((z.atan2(x), y.atan2((x * x + z * z).sqrt())))
+    }
+
+    /// What the HUD says about the selected teapot, with its neighbours found in `spatial`
+    pub fn lines(&self, teapots: &Teapots, spatial: &dyn SpatialIndex, time: f32) -> Vec<String> {
+        let Some(index) = self.index.filter(|&index| index < teapots.len()) else {
+            return Vec::new();
+        };
+        let teapot = teapots.get(index);
+        let [x, y, z] = teapot.position;
+        let mut lines = vec![
+            format!("teapot {}", index),
+            format!("position {:.1}, {:.1}, {:.1}", x, y, z),
+            format!("age {:.1}s", time - teapot.spawned),
+        ];
+        if let Some(lifetime) = teapot.lifetime {
+            lines.push(format!("expires in {:.1}s", (teapot.spawned + lifetime - time).max(0.0)));
+        }
+        // The nearest is the selected teapot itself
+        let nearest = spatial.nearest(teapot.position, 2).into_iter().find(|&(id, _)| id != teapot.id);
+        if let Some((nearest, distance)) = nearest.and_then(|(id, distance)| Some((positions::find(teapots, id)?, distance))) {
+            lines.push(format!("nearest teapot {} is {:.1} away", nearest, distance));
+        }
+        let around = spatial.within(teapot.position, NEIGHBOURHOOD).into_iter().filter(|&id| id != teapot.id).count();
+        lines.push(format!("{} teapots within {}", around, NEIGHBOURHOOD));
+        if self.follow {
+            lines.push("following".to_string());
+        }
+        lines
+    }
+}
+
+/// Draws the outline around the selected teapot
+pub struct Outline {
+    /// How far the outline reaches past a teapot with a scale of 1
+    width: f32,
+    program: glium::Program,
+}
+
+impl Outline {
+    pub fn new(display: &impl glium::backend::Facade, teapot_radius: f32) -> Outline {
+        Outline {
+            width: teapot_radius * 0.05,
+            program: shader::program(display, include_str!("../shaders/outline.vert"), include_str!("../shaders/solid.frag")),
+        }
+    }
+
+    /// Draws an outline around the selected teapot, whose instance is in `buffer`, with the same model, view and perspective as the teapots
+    pub fn draw(&self, target: &mut impl Surface, level: &Level, buffer: &glium::VertexBuffer<Instance>,
+                model: [[f32; 4]; 4], view: [[f32; 4]; 4], perspective: [[f32; 4]; 4]) {
+        let params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::draw_parameters::DepthTest::IfLess,
+                write: true,
+                .. Default::default()
+            },
+            // The opposite faces to the teapots, so the front of the teapot hides all but the edge
+            backface_culling: glium::draw_parameters::BackfaceCullingMode::CullCounterClockwise,
+            .. Default::default()
+        };
+        target.draw((&level.vertices, buffer.per_instance().unwrap()), &level.indices, &self.program,
+            &uniform! { model: model, view: view, perspective: perspective, width: self.width, colour: COLOUR },
+            &params).unwrap();
+    }
+}
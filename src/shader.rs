@@ -0,0 +1,30 @@
+/// Files that shaders can include with `#include "name"`
+const INCLUDES: [(&str, &str); 3] = [
+    ("sky.glsl", include_str!("../shaders/sky.glsl")),
+    ("lighting.glsl", include_str!("../shaders/lighting.glsl")),
+    ("instance.glsl", include_str!("../shaders/instance.glsl")),
+];
+
+/// Replaces `#include "name"` lines with the contents of the file, GLSL has no includes of its own.
+/// Included files can include other files
+pub fn preprocess(source: &str) -> String {
+    source.lines()
+        .map(|line| {
+            let included = line.trim()
+                .strip_prefix("#include")
+                .map(|name| name.trim().trim_matches('"'))
+                .and_then(|name| INCLUDES.iter().find(|(include, _)| *include == name));
+
+            match included {
+                Some((_, contents)) => preprocess(contents),
+                None => line.to_string(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Builds a program from shaders that may use `#include`
+pub fn program(display: &impl glium::backend::Facade, vertex: &str, fragment: &str) -> glium::Program {
+    glium::Program::from_source(display, &preprocess(vertex), &preprocess(fragment), None).unwrap()
+}
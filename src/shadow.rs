@@ -0,0 +1,162 @@
+use glium::{framebuffer::SimpleFrameBuffer, texture::DepthTexture2d, uniforms::{DepthTextureComparison, MagnifySamplerFilter, MinifySamplerFilter, SamplerWrapFunction, UniformValue, Uniforms}, Surface};
+
+use crate::{matrices, shader};
+
+/// Has to match MAX_CASCADES in the fragment shader
+pub const MAX_CASCADES: usize = 4;
+
+/// Names of the shadow map samplers, arrays of samplers can't be indexed dynamically in GLSL 1.50
+const MAP_NAMES: [&str; MAX_CASCADES] = ["u_shadow_map_0", "u_shadow_map_1", "u_shadow_map_2", "u_shadow_map_3"];
+
+/// Directional light shadow maps centred on the camera.
+///
+/// With more than one cascade, each cascade covers twice the distance of the one before it, so teapots near the camera
+/// get sharp shadows while teapots far away in a big `--range` still get some shadow.
+pub struct Shadows {
+    maps: Vec<DepthTexture2d>,
+    matrices: [[[f32; 4]; 4]; MAX_CASCADES],
+    radii: [f32; MAX_CASCADES],
+    program: glium::Program,
+    enabled: bool,
+    resolution: u32,
+    distance: f32,
+    /// Index into the lights given to the fragment shader of the light that casts the shadows
+    light_index: i32,
+}
+
+impl Shadows {
+    /// `distance` is how far from the camera the first cascade reaches, `cascades` is clamped to MAX_CASCADES
+    pub fn new(display: &impl glium::backend::Facade, enabled: bool, resolution: u32, cascades: usize, distance: f32) -> Shadows {
+        // Shaders need something bound to every sampler even with shadows turned off
+        let (cascades, resolution) = if enabled {
+            (cascades.clamp(1, MAX_CASCADES), resolution)
+        } else {
+            (0, 1)
+        };
+
+        let maps = (0..MAX_CASCADES)
+            .map(|i| {
+                let size = if i < cascades { resolution } else { 1 };
+                DepthTexture2d::empty(display, size, size).unwrap()
+            })
+            .collect();
+
+        let mut radii = [0.0; MAX_CASCADES];
+        for (i, radius) in radii.iter_mut().enumerate().take(cascades) {
+            *radius = distance * (1 << i) as f32;
+        }
+
+        let program = shader::program(display, include_str!("../shaders/shadow.vert"), include_str!("../shaders/shadow.frag"));
+
+        Shadows {
+            maps,
+            matrices: [[[0.0; 4]; 4]; MAX_CASCADES],
+            radii,
+            program,
+            enabled,
+            resolution,
+            distance,
+            light_index: -1,
+        }
+    }
+
+    pub fn cascades(&self) -> usize {
+        self.radii.iter().filter(|radius| **radius > 0.0).count()
+    }
+
+    /// Renders the shadow maps for this frame.
+    ///
+    /// `light` is the direction towards the light and `light_index` its position in the lights given to the shader, or None if there is no light to cast shadows.
+    /// `draw_casters` is called once per cascade and should draw everything that casts shadows with the given program and `light_matrix` uniform.
+    pub fn render<F>(&mut self, display: &impl glium::backend::Facade, light: Option<(usize, [f32; 3])>, camera: [f32; 3], mut draw_casters: F)
+        where F: FnMut(&mut SimpleFrameBuffer, &glium::Program, [[f32; 4]; 4])
+    {
+        let (light_index, direction) = match light {
+            Some(light) if self.enabled => light,
+            _ => {
+                self.light_index = -1;
+                return;
+            }
+        };
+        self.light_index = light_index as i32;
+
+        let len = (direction[0] * direction[0] + direction[1] * direction[1] + direction[2] * direction[2]).sqrt();
+        let to_light = direction.map(|v| v / len);
+        // Anything up isn't parallel to a light pointing straight down
+        let up = if to_light[1].abs() > 0.99 { [1.0, 0.0, 0.0] } else { [0.0, 1.0, 0.0] };
+        let light_view = matrices::view_matrix(&[0.0, 0.0, 0.0], &to_light.map(|v| -v), &up);
+        let centre = matrices::transform(&light_view, &camera);
+
+        for i in 0..self.cascades() {
+            let radius = self.radii[i];
+
+            // Snap to whole texels so the shadows don't shimmer while moving
+            let texel = 2.0 * radius / self.resolution as f32;
+            let x = (centre[0] / texel).floor() * texel;
+            let y = (centre[1] / texel).floor() * texel;
+            // Reach further towards the light than away from it, so teapots above the visible area still cast shadows into it
+            let depth = radius.max(self.distance) * 4.0;
+            let projection = matrices::orthographic(x - radius, x + radius, y - radius, y + radius, centre[2] - depth, centre[2] + radius);
+
+            self.matrices[i] = matrices::multiply(&projection, &light_view);
+
+            let mut framebuffer = SimpleFrameBuffer::depth_only(display, &self.maps[i]).unwrap();
+            framebuffer.clear_depth(1.0);
+            draw_casters(&mut framebuffer, &self.program, self.matrices[i]);
+        }
+    }
+
+    /// Draw parameters for drawing shadow casters, the polygon offset stops surfaces from shadowing themselves
+    pub fn draw_parameters() -> glium::DrawParameters<'static> {
+        glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::draw_parameters::DepthTest::IfLess,
+                write: true,
+                .. Default::default()
+            },
+            polygon_offset: glium::draw_parameters::PolygonOffset {
+                factor: 2.0,
+                units: 4.0,
+                fill: true,
+                .. Default::default()
+            },
+            .. Default::default()
+        }
+    }
+
+    /// Adds the shadow maps to other uniforms
+    pub fn uniforms<U: Uniforms>(&self, rest: U) -> ShadowUniforms<'_, U> {
+        ShadowUniforms { shadows: self, rest }
+    }
+}
+
+pub struct ShadowUniforms<'a, U> {
+    shadows: &'a Shadows,
+    rest: U,
+}
+
+impl<U: Uniforms> Uniforms for ShadowUniforms<'_, U> {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut output: F) {
+        let shadows = self.shadows;
+        let cascades = if shadows.light_index < 0 { 0 } else { shadows.cascades() };
+
+        output("u_shadow_light", UniformValue::SignedInt(shadows.light_index));
+        output("u_cascade_count", UniformValue::SignedInt(cascades as i32));
+        for (i, name) in MAP_NAMES.iter().enumerate() {
+            output(&format!("u_cascade_radii[{}]", i), UniformValue::Float(shadows.radii[i]));
+            output(&format!("u_shadow_matrices[{}]", i), UniformValue::Mat4(shadows.matrices[i]));
+
+            let behavior = glium::uniforms::SamplerBehavior {
+                minify_filter: MinifySamplerFilter::Linear,
+                magnify_filter: MagnifySamplerFilter::Linear,
+                wrap_function: (SamplerWrapFunction::Clamp, SamplerWrapFunction::Clamp, SamplerWrapFunction::Clamp),
+                depth_texture_comparison: Some(DepthTextureComparison::LessOrEqual),
+                .. Default::default()
+            };
+            output(name, UniformValue::DepthTexture2d(&shadows.maps[i], Some(behavior)));
+        }
+        output("u_shadow_texel", UniformValue::Float(1.0 / shadows.resolution as f32));
+
+        self.rest.visit_values(output);
+    }
+}
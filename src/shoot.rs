@@ -0,0 +1,33 @@
+use crate::positions::Teapot;
+
+/// Fires teapots out of the camera
+#[derive(Copy, Clone, Debug)]
+pub struct Gun {
+    /// Units per second
+    pub speed: f32,
+    /// Pulls fired teapots down, with or without --physics
+    pub gravity: [f32; 3],
+    /// Seconds before fired teapots despawn, None keeps them
+    pub lifetime: Option<f32>,
+}
+
+impl Gun {
+    /// Turns `teapot` into a shot from `position` along the unit `direction`
+    pub fn fire(&self, teapot: Teapot, position: [f32; 3], direction: [f32; 3]) -> Teapot {
+        Teapot {
+            // Far enough in front not to fill the screen
+            position: [0, 1, 2].map(|i| position[i] + direction[i]),
+            velocity: direction.map(|v| v * self.speed),
+            gravity: Some(self.gravity),
+            lifetime: self.lifetime,
+            previous: None,
+            ..teapot
+        }
+    }
+}
+
+/// Moves a shot pulled by its own `gravity` along when --physics isn't doing it
+pub fn fly(position: &mut [f32; 3], velocity: &mut [f32; 3], gravity: [f32; 3], dt: f32) {
+    *velocity = [0, 1, 2].map(|i| velocity[i] + gravity[i] * dt);
+    *position = [0, 1, 2].map(|i| position[i] + velocity[i] * dt);
+}
@@ -0,0 +1,146 @@
+use std::path::Path;
+
+use glium::{framebuffer::SimpleFrameBuffer, texture::{CubeLayer, Cubemap, MipmapsOption, RawImage2d, UncompressedFloatFormat}, uniforms::MagnifySamplerFilter, BlitTarget, Surface};
+
+const LAYERS: [CubeLayer; 6] = [
+    CubeLayer::PositiveX,
+    CubeLayer::NegativeX,
+    CubeLayer::PositiveY,
+    CubeLayer::NegativeY,
+    CubeLayer::PositiveZ,
+    CubeLayer::NegativeZ,
+];
+
+/// Accepted file names (without extension) for each face, in the same order as LAYERS
+const FACE_NAMES: [[&str; 3]; 6] = [
+    ["px", "posx", "right"],
+    ["nx", "negx", "left"],
+    ["py", "posy", "top"],
+    ["ny", "negy", "bottom"],
+    ["pz", "posz", "front"],
+    ["nz", "negz", "back"],
+];
+
+/// A face of the cubemap as linear RGB floats, the first row is the top of the face
+struct Face {
+    size: u32,
+    pixels: Vec<f32>,
+}
+
+/// Loads a skybox from either a directory with six images named px, nx, py, ny, pz and nz (or right, left, top, bottom, front and back),
+/// or from a single equirectangular image such as an HDR panorama.
+pub fn load(display: &impl glium::backend::Facade, path: &Path) -> Result<Cubemap, String> {
+    let faces = if path.is_dir() {
+        load_faces(path)?
+    } else {
+        let image = image::open(path).map_err(|e| format!("{}: {}", path.display(), e))?.into_rgb32f();
+        equirectangular_to_faces(&image)
+    };
+
+    upload(display, &faces)
+}
+
+/// A 1x1 black cubemap, bound when there is no skybox because every sampler in a shader needs a texture
+pub fn empty(display: &impl glium::backend::Facade) -> Cubemap {
+    let faces: Vec<Face> = (0..6).map(|_| Face { size: 1, pixels: vec![0.0; 3] }).collect();
+    upload(display, &faces).unwrap()
+}
+
+fn load_faces(directory: &Path) -> Result<Vec<Face>, String> {
+    let entries: Vec<_> = std::fs::read_dir(directory)
+        .map_err(|e| format!("{}: {}", directory.display(), e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+
+    FACE_NAMES.iter()
+        .map(|names| {
+            let path = entries.iter()
+                .find(|path| {
+                    let stem = path.file_stem().map(|stem| stem.to_string_lossy().to_lowercase());
+                    stem.is_some_and(|stem| names.contains(&stem.as_str()))
+                })
+                .ok_or_else(|| format!("No {} image in {}", names.join("/"), directory.display()))?;
+
+            let image = image::open(path).map_err(|e| format!("{}: {}", path.display(), e))?.into_rgb32f();
+            if image.width() != image.height() {
+                return Err(format!("{} isn't square", path.display()));
+            }
+            Ok(Face { size: image.width(), pixels: image.into_raw() })
+        })
+        .collect()
+}
+
+/// Direction that OpenGL samples for a point on a cubemap face, `s` and `t` go from -1 to 1 with `t` pointing down
+fn face_direction(face: usize, s: f32, t: f32) -> [f32; 3] {
+    match face {
+        0 => [1.0, -t, -s],
+        1 => [-1.0, -t, s],
+        2 => [s, 1.0, t],
+        3 => [s, -1.0, -t],
+        4 => [s, -t, 1.0],
+        _ => [-s, -t, -1.0],
+    }
+}
+
+fn equirectangular_to_faces(image: &image::Rgb32FImage) -> Vec<Face> {
+    let size = (image.width() / 4).max(1);
+    let (width, height) = (image.width() as f32, image.height() as f32);
+
+    // Bilinear filtering, wrapping around horizontally
+    let sample = |u: f32, v: f32| -> [f32; 3] {
+        let x = u * width - 0.5;
+        let y = (v * height - 0.5).clamp(0.0, height - 1.0);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let pixel = |x: f32, y: f32| image.get_pixel(x.rem_euclid(width) as u32, (y as u32).min(image.height() - 1)).0;
+
+        let (a, b, c, d) = (pixel(x0, y0), pixel(x0 + 1.0, y0), pixel(x0, y0 + 1.0), pixel(x0 + 1.0, y0 + 1.0));
+        [0, 1, 2].map(|i| {
+            let top = a[i] + (b[i] - a[i]) * fx;
+            let bottom = c[i] + (d[i] - c[i]) * fx;
+            top + (bottom - top) * fy
+        })
+    };
+
+    (0..6)
+        .map(|face| {
+            let mut pixels = Vec::with_capacity((size * size * 3) as usize);
+            for row in 0..size {
+                for column in 0..size {
+                    let s = (column as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                    let t = (row as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                    let [x, y, z] = face_direction(face, s, t);
+                    let length = (x * x + y * y + z * z).sqrt();
+
+                    // The shaders flip z when sampling so the world isn't mirrored, which is undone here
+                    let longitude = z.atan2(x);
+                    let latitude = (y / length).asin();
+                    let u = 0.5 + longitude / std::f32::consts::TAU;
+                    let v = 0.5 - latitude / std::f32::consts::PI;
+                    pixels.extend(sample(u, v));
+                }
+            }
+            Face { size, pixels }
+        })
+        .collect()
+}
+
+fn upload(display: &impl glium::backend::Facade, faces: &[Face]) -> Result<Cubemap, String> {
+    let size = faces[0].size;
+    let cubemap = Cubemap::empty_with_format(display, UncompressedFloatFormat::F16F16F16, MipmapsOption::NoMipmap, size)
+        .map_err(|e| format!("Failed to create cubemap: {:?}", e))?;
+
+    // Cubemap faces can't be written to directly, so each face goes through a 2D texture that is blitted onto it
+    for (face, layer) in faces.iter().zip(LAYERS) {
+        let image = RawImage2d::from_raw_rgb(face.pixels.clone(), (face.size, face.size));
+        let texture = glium::Texture2d::with_format(display, image, UncompressedFloatFormat::F16F16F16, MipmapsOption::NoMipmap)
+            .map_err(|e| format!("Failed to create cubemap face: {:?}", e))?;
+        let framebuffer = SimpleFrameBuffer::new(display, cubemap.main_level().image(layer))
+            .map_err(|e| format!("Failed to draw to cubemap face: {:?}", e))?;
+
+        let target = BlitTarget { left: 0, bottom: 0, width: size as i32, height: size as i32 };
+        texture.as_surface().blit_whole_color_to(&framebuffer, &target, MagnifySamplerFilter::Linear);
+    }
+
+    Ok(cubemap)
+}
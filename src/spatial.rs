@@ -0,0 +1,824 @@
+use std::{cmp::Ordering, collections::{BinaryHeap, HashMap, HashSet}};
+
+/// Finds teapots near a point or along a ray without checking every one. Teapots are spheres found again by their id,
+/// and can be moved, added and removed a few at a time as the scene changes
+pub trait SpatialIndex {
+    /// Adds a sphere, or moves it if `id` is already in the index
+    fn update(&mut self, id: usize, centre: [f32; 3], radius: f32);
+    /// Does nothing if `id` isn't in the index
+    fn remove(&mut self, id: usize);
+    /// Every sphere that overlaps the sphere at `centre`, in no particular order
+    fn within(&self, centre: [f32; 3], radius: f32) -> Vec<usize>;
+    /// The `count` spheres with their centres nearest to `point` and their distances, nearest first
+    fn nearest(&self, point: [f32; 3], count: usize) -> Vec<(usize, f32)>;
+    /// Nearest hit along a ray from `origin` in the unit `direction`. `hit` says how far along the ray a sphere is hit, if it is,
+    /// and is only asked about spheres near the ray. It can be asked about the same sphere more than once
+    fn ray(&self, origin: [f32; 3], direction: [f32; 3], hit: &mut dyn FnMut(usize) -> Option<f32>) -> Option<(usize, f32)>;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Kind {
+    /// Equal cubes, best when teapots are spread evenly and about the same size
+    Grid,
+    /// Cubes split into eight where there are more teapots, each holding teapots that are about its size
+    Octree,
+    /// A tree of boxes around groups of nearby teapots, which copes best with teapots bunched up in a few places
+    Bvh,
+}
+
+/// An empty index. `size` is about the size of a teapot
+pub fn new(kind: Kind, size: f32) -> Box<dyn SpatialIndex> {
+    match kind {
+        Kind::Grid => Box::new(Grid::new(size)),
+        Kind::Octree => Box::new(Octree::new(size)),
+        Kind::Bvh => Box::new(Bvh::new(size)),
+    }
+}
+
+fn distance_squared(a: [f32; 3], b: [f32; 3]) -> f32 {
+    (0..3).map(|i| (a[i] - b[i]) * (a[i] - b[i])).sum()
+}
+
+/// Squared distance from `point` to the nearest point in a box, 0 inside it
+fn box_distance_squared(min: [f32; 3], max: [f32; 3], point: [f32; 3]) -> f32 {
+    (0..3).map(|i| (min[i] - point[i]).max(point[i] - max[i]).max(0.0).powi(2)).sum()
+}
+
+/// How far along a ray it enters a box, if it hits it at all
+fn ray_box(min: [f32; 3], max: [f32; 3], origin: [f32; 3], direction: [f32; 3]) -> Option<f32> {
+    let (mut enter, mut exit) = (0.0f32, f32::INFINITY);
+    for i in 0..3 {
+        if direction[i] == 0.0 {
+            if origin[i] < min[i] || origin[i] > max[i] {
+                return None;
+            }
+            continue;
+        }
+        let a = (min[i] - origin[i]) / direction[i];
+        let b = (max[i] - origin[i]) / direction[i];
+        enter = enter.max(a.min(b));
+        exit = exit.min(a.max(b));
+    }
+    (enter <= exit).then_some(enter)
+}
+
+/// A node or sphere waiting to be looked at, the nearest comes out of a BinaryHeap first
+struct Candidate {
+    distance: f32,
+    node: Option<usize>,
+    id: usize,
+}
+
+impl Candidate {
+    fn node(distance: f32, node: usize) -> Candidate {
+        Candidate { distance, node: Some(node), id: 0 }
+    }
+
+    fn sphere(distance: f32, id: usize) -> Candidate {
+        Candidate { distance, node: None, id }
+    }
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Candidate) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Candidate) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Candidate) -> Ordering {
+        // Reversed so the heap gives the smallest distance, ties go to spheres and then the lowest id so results don't depend on the order of the heap
+        other.distance.total_cmp(&self.distance)
+            .then(other.node.is_none().cmp(&self.node.is_none()).reverse())
+            .then(other.id.cmp(&self.id))
+    }
+}
+
+/// Walks a tree nearest node first. `open` gives the spheres and child nodes of a node with their distances
+fn nearest_first(root: Option<Candidate>, count: usize, mut open: impl FnMut(usize, &mut BinaryHeap<Candidate>)) -> Vec<(usize, f32)> {
+    let mut heap: BinaryHeap<Candidate> = root.into_iter().collect();
+    let mut found = Vec::new();
+    while found.len() < count {
+        let Some(candidate) = heap.pop() else {
+            break;
+        };
+        match candidate.node {
+            Some(node) => open(node, &mut heap),
+            None => found.push((candidate.id, candidate.distance.sqrt())),
+        }
+    }
+    found
+}
+
+/// Keeps the nearest hit so far
+fn closest(nearest: &mut Option<(usize, f32)>, id: usize, distance: Option<f32>) {
+    if let Some(distance) = distance {
+        if nearest.is_none_or(|(_, nearest)| distance < nearest) {
+            *nearest = Some((id, distance));
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Sphere {
+    centre: [f32; 3],
+    radius: f32,
+}
+
+impl Sphere {
+    fn min(&self) -> [f32; 3] {
+        self.centre.map(|v| v - self.radius)
+    }
+
+    fn max(&self) -> [f32; 3] {
+        self.centre.map(|v| v + self.radius)
+    }
+}
+
+/// Spheres sorted into a grid of equal cubes, each sphere is in every cube it touches
+pub struct Grid {
+    size: f32,
+    cells: HashMap<[i32; 3], Vec<usize>>,
+    /// Spheres and the first and last cells they are in
+    spheres: HashMap<usize, (Sphere, [i32; 3], [i32; 3])>,
+    /// Corners of a box around every sphere, which only ever grows
+    min: [f32; 3],
+    max: [f32; 3],
+}
+
+impl Grid {
+    pub fn new(size: f32) -> Grid {
+        Grid { size, cells: HashMap::new(), spheres: HashMap::new(), min: [f32::INFINITY; 3], max: [f32::NEG_INFINITY; 3] }
+    }
+
+    fn cell(&self, point: [f32; 3]) -> [i32; 3] {
+        point.map(|v| (v / self.size).floor() as i32)
+    }
+
+    fn cells(low: [i32; 3], high: [i32; 3]) -> impl Iterator<Item = [i32; 3]> {
+        (low[0]..=high[0]).flat_map(move |x| (low[1]..=high[1]).flat_map(move |y| (low[2]..=high[2]).map(move |z| [x, y, z])))
+    }
+
+    /// Offsets of the cells on the outside of a cube `ring` cells out from the middle
+    fn ring(ring: i32) -> impl Iterator<Item = [i32; 3]> {
+        (-ring..=ring).flat_map(move |x| (-ring..=ring).flat_map(move |y| {
+            // Only the two ends of each column unless it is on a side of the cube
+            let side = x.abs() == ring || y.abs() == ring;
+            let step = if side || ring == 0 { 1 } else { 2 * ring as usize };
+            (-ring..=ring).step_by(step).map(move |z| [x, y, z])
+        }))
+    }
+}
+
+impl SpatialIndex for Grid {
+    fn update(&mut self, id: usize, centre: [f32; 3], radius: f32) {
+        let sphere = Sphere { centre, radius };
+        let (low, high) = (self.cell(sphere.min()), self.cell(sphere.max()));
+        self.min = [0, 1, 2].map(|i| self.min[i].min(sphere.min()[i]));
+        self.max = [0, 1, 2].map(|i| self.max[i].max(sphere.max()[i]));
+
+        // Spheres that stay in the same cells only need their size and position changed
+        if let Some(entry) = self.spheres.get_mut(&id) {
+            if (entry.1, entry.2) == (low, high) {
+                entry.0 = sphere;
+                return;
+            }
+        }
+        self.remove(id);
+        for cell in Grid::cells(low, high) {
+            self.cells.entry(cell).or_default().push(id);
+        }
+        self.spheres.insert(id, (sphere, low, high));
+    }
+
+    fn remove(&mut self, id: usize) {
+        let Some((_, low, high)) = self.spheres.remove(&id) else {
+            return;
+        };
+        for cell in Grid::cells(low, high) {
+            if let Some(ids) = self.cells.get_mut(&cell) {
+                ids.retain(|&other| other != id);
+                if ids.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+    }
+
+    fn within(&self, centre: [f32; 3], radius: f32) -> Vec<usize> {
+        let query = Sphere { centre, radius };
+        let (low, high) = (self.cell(query.min()), self.cell(query.max()));
+        let mut found = Vec::new();
+        for cell in Grid::cells(low, high) {
+            for &id in self.cells.get(&cell).into_iter().flatten() {
+                let (sphere, first, _) = self.spheres[&id];
+                // Spheres in several cells are only counted in the first one the query looks at
+                let counted = [0, 1, 2].map(|i| first[i].max(low[i]));
+                if counted == cell && distance_squared(sphere.centre, centre) <= (sphere.radius + radius).powi(2) {
+                    found.push(id);
+                }
+            }
+        }
+        found
+    }
+
+    fn nearest(&self, point: [f32; 3], count: usize) -> Vec<(usize, f32)> {
+        if self.spheres.is_empty() || count == 0 {
+            return Vec::new();
+        }
+        // Rings of cells further and further out, until nothing outside the ring can be nearer than what was found
+        let centre = self.cell(point);
+        let (low, high) = (self.cell(self.min), self.cell(self.max));
+        let furthest = (0..3).map(|i| (centre[i] - low[i]).abs().max((high[i] - centre[i]).abs())).max().unwrap();
+        let by_distance = |a: &(usize, f32), b: &(usize, f32)| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0));
+        let mut seen = HashSet::new();
+        let mut found: Vec<(usize, f32)> = Vec::new();
+        for ring in 0..=furthest {
+            // Once more cells have been looked at than have anything in them, it is quicker to go through those nearest first
+            if ((2 * ring + 1).pow(3) as usize) > self.cells.len() {
+                let mut cells: Vec<(f32, &Vec<usize>)> = self.cells.iter()
+                    .filter(|(cell, _)| (0..3).any(|i| (cell[i] - centre[i]).abs() >= ring))
+                    .map(|(cell, ids)| {
+                        let min = cell.map(|v| v as f32 * self.size);
+                        (box_distance_squared(min, min.map(|v| v + self.size), point).sqrt(), ids)
+                    })
+                    .collect();
+                cells.sort_by(|a, b| a.0.total_cmp(&b.0));
+                for (distance, ids) in cells {
+                    if found.len() == count && found[count - 1].1 <= distance {
+                        break;
+                    }
+                    found.extend(ids.iter().filter(|&&id| seen.insert(id)).map(|&id| (id, distance_squared(self.spheres[&id].0.centre, point).sqrt())));
+                    found.sort_by(by_distance);
+                    found.truncate(count);
+                }
+                break;
+            }
+            for offset in Grid::ring(ring) {
+                let cell = [0, 1, 2].map(|i| centre[i] + offset[i]);
+                for &id in self.cells.get(&cell).into_iter().flatten() {
+                    if seen.insert(id) {
+                        found.push((id, distance_squared(self.spheres[&id].0.centre, point).sqrt()));
+                    }
+                }
+            }
+            found.sort_by(by_distance);
+            found.truncate(count);
+            // Centres in the next ring are at least this far away
+            if found.len() == count && found[count - 1].1 <= ring as f32 * self.size {
+                break;
+            }
+        }
+        found
+    }
+
+    fn ray(&self, origin: [f32; 3], direction: [f32; 3], hit: &mut dyn FnMut(usize) -> Option<f32>) -> Option<(usize, f32)> {
+        // Only the part of the ray inside the box around everything is walked
+        let enter = ray_box(self.min, self.max, origin, direction)?;
+        let exit = (0..3)
+            .filter(|&i| direction[i] != 0.0)
+            .map(|i| ((self.min[i] - origin[i]) / direction[i]).max((self.max[i] - origin[i]) / direction[i]))
+            .fold(f32::INFINITY, f32::min);
+
+        let start = [0, 1, 2].map(|i| origin[i] + direction[i] * enter);
+        let mut cell = self.cell(start);
+        let step = direction.map(|v| if v > 0.0 { 1 } else { -1 });
+        // Distance along the ray to the next cell boundary on each axis, and between boundaries
+        let mut next = [0, 1, 2].map(|i| {
+            if direction[i] == 0.0 {
+                return f32::INFINITY;
+            }
+            let boundary = (cell[i] + (step[i] > 0) as i32) as f32 * self.size;
+            (boundary - origin[i]) / direction[i]
+        });
+        let delta = direction.map(|v| if v == 0.0 { f32::INFINITY } else { self.size / v.abs() });
+
+        let mut nearest = None;
+        loop {
+            for &id in self.cells.get(&cell).into_iter().flatten() {
+                closest(&mut nearest, id, hit(id));
+            }
+
+            let axis = (0..3).min_by(|&a, &b| next[a].total_cmp(&next[b])).unwrap();
+            // Nothing in later cells can be closer than a hit before this cell ends
+            if nearest.is_some_and(|(_, nearest)| nearest <= next[axis]) || next[axis] > exit {
+                return nearest;
+            }
+            cell[axis] += step[axis];
+            next[axis] += delta[axis];
+        }
+    }
+}
+
+struct OctreeNode {
+    centre: [f32; 3],
+    /// Half the width of the cube. Spheres in the node have their centres inside the cube but can reach out to twice this
+    half: f32,
+    /// Indices into the nodes, 0 for none because the root is never a child
+    children: [usize; 8],
+    /// 0 for the root
+    parent: usize,
+    ids: Vec<usize>,
+}
+
+impl OctreeNode {
+    fn new(centre: [f32; 3], half: f32, parent: usize) -> OctreeNode {
+        OctreeNode { centre, half, children: [0; 8], parent, ids: Vec::new() }
+    }
+
+    /// The loose cube that every sphere in the node fits inside
+    fn bounds(&self) -> ([f32; 3], [f32; 3]) {
+        (self.centre.map(|v| v - 2.0 * self.half), self.centre.map(|v| v + 2.0 * self.half))
+    }
+
+    /// Which of the eight children `point` is in
+    fn octant(&self, point: [f32; 3]) -> usize {
+        (0..3).filter(|&i| point[i] >= self.centre[i]).map(|i| 1 << i).sum()
+    }
+
+    /// Whether a sphere belongs in this node rather than its parent or one of its children
+    fn holds(&self, sphere: &Sphere, smallest: f32) -> bool {
+        let inside = (0..3).all(|i| (sphere.centre[i] - self.centre[i]).abs() <= self.half);
+        inside && sphere.radius <= self.half && (sphere.radius > self.half / 2.0 || self.half / 2.0 < smallest)
+    }
+}
+
+/// Loose octree, where each sphere is in the smallest cube that holds its centre and is at least as big as the sphere
+pub struct Octree {
+    /// The first node is the root, which grows to fit everything
+    nodes: Vec<OctreeNode>,
+    /// Nodes that emptied and were unlinked from their parents, reused before adding more
+    free: Vec<usize>,
+    spheres: HashMap<usize, (Sphere, usize)>,
+    /// Half the width of the smallest cubes
+    smallest: f32,
+}
+
+impl Octree {
+    pub fn new(size: f32) -> Octree {
+        Octree { nodes: vec![OctreeNode::new([0.0; 3], size, 0)], free: Vec::new(), spheres: HashMap::new(), smallest: size / 2.0 }
+    }
+
+    /// Puts a sphere in the smallest node that holds it, adding nodes on the way
+    fn place(&mut self, id: usize, sphere: Sphere) {
+        let root = &self.nodes[0];
+        if (0..3).any(|i| sphere.centre[i].abs() > root.half) || sphere.radius > root.half {
+            self.grow(sphere);
+        }
+
+        let mut node = 0;
+        loop {
+            let half = self.nodes[node].half / 2.0;
+            if half < self.smallest || sphere.radius > half {
+                break;
+            }
+            let octant = self.nodes[node].octant(sphere.centre);
+            if self.nodes[node].children[octant] == 0 {
+                let parent = self.nodes[node].centre;
+                let centre = [0, 1, 2].map(|i| parent[i] + if octant & (1 << i) != 0 { half } else { -half });
+                let child = OctreeNode::new(centre, half, node);
+                let index = match self.free.pop() {
+                    Some(index) => {
+                        self.nodes[index] = child;
+                        index
+                    },
+                    None => {
+                        self.nodes.push(child);
+                        self.nodes.len() - 1
+                    },
+                };
+                self.nodes[node].children[octant] = index;
+            }
+            node = self.nodes[node].children[octant];
+        }
+        self.nodes[node].ids.push(id);
+        self.spheres.insert(id, (sphere, node));
+    }
+
+    /// Unlinks `node` if nothing is left in or under it, and then its parent in the same way, so moving spheres don't leave empty branches behind
+    fn prune(&mut self, mut node: usize) {
+        while node != 0 && self.nodes[node].ids.is_empty() && self.nodes[node].children.iter().all(|&child| child == 0) {
+            let parent = self.nodes[node].parent;
+            for child in &mut self.nodes[parent].children {
+                if *child == node {
+                    *child = 0;
+                }
+            }
+            self.free.push(node);
+            node = parent;
+        }
+    }
+
+    /// Doubles the root until `sphere` fits, and puts everything back in again
+    fn grow(&mut self, sphere: Sphere) {
+        let mut half = self.nodes[0].half;
+        while (0..3).any(|i| sphere.centre[i].abs() > half) || sphere.radius > half {
+            half *= 2.0;
+        }
+        self.nodes = vec![OctreeNode::new([0.0; 3], half, 0)];
+        self.free.clear();
+        let spheres: Vec<(usize, Sphere)> = self.spheres.drain().map(|(id, (sphere, _))| (id, sphere)).collect();
+        for (id, sphere) in spheres {
+            self.place(id, sphere);
+        }
+    }
+}
+
+impl SpatialIndex for Octree {
+    fn update(&mut self, id: usize, centre: [f32; 3], radius: f32) {
+        let sphere = Sphere { centre, radius };
+        if let Some((entry, node)) = self.spheres.get_mut(&id) {
+            if self.nodes[*node].holds(&sphere, self.smallest) {
+                *entry = sphere;
+                return;
+            }
+        }
+        self.remove(id);
+        self.place(id, sphere);
+    }
+
+    fn remove(&mut self, id: usize) {
+        if let Some((_, node)) = self.spheres.remove(&id) {
+            let ids = &mut self.nodes[node].ids;
+            if let Some(index) = ids.iter().position(|&other| other == id) {
+                ids.swap_remove(index);
+            }
+            self.prune(node);
+        }
+    }
+
+    fn within(&self, centre: [f32; 3], radius: f32) -> Vec<usize> {
+        let mut found = Vec::new();
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            let (min, max) = node.bounds();
+            if box_distance_squared(min, max, centre) > radius * radius {
+                continue;
+            }
+            found.extend(node.ids.iter().filter(|id| {
+                let (sphere, _) = self.spheres[id];
+                distance_squared(sphere.centre, centre) <= (sphere.radius + radius).powi(2)
+            }));
+            stack.extend(node.children.iter().filter(|&&child| child != 0));
+        }
+        found
+    }
+
+    fn nearest(&self, point: [f32; 3], count: usize) -> Vec<(usize, f32)> {
+        nearest_first(Some(Candidate::node(0.0, 0)), count, |node, heap| {
+            let node = &self.nodes[node];
+            heap.extend(node.ids.iter().map(|&id| Candidate::sphere(distance_squared(self.spheres[&id].0.centre, point), id)));
+            for &child in node.children.iter().filter(|&&child| child != 0) {
+                // Centres are inside the tight cube, so that is the nearest they can be
+                let child_node = &self.nodes[child];
+                let (min, max) = (child_node.centre.map(|v| v - child_node.half), child_node.centre.map(|v| v + child_node.half));
+                heap.push(Candidate::node(box_distance_squared(min, max, point), child));
+            }
+        })
+    }
+
+    fn ray(&self, origin: [f32; 3], direction: [f32; 3], hit: &mut dyn FnMut(usize) -> Option<f32>) -> Option<(usize, f32)> {
+        let mut heap = BinaryHeap::from([Candidate::node(0.0, 0)]);
+        let mut nearest = None;
+        while let Some(candidate) = heap.pop() {
+            if nearest.is_some_and(|(_, nearest)| nearest < candidate.distance) {
+                break;
+            }
+            let node = &self.nodes[candidate.node.unwrap()];
+            for &id in &node.ids {
+                closest(&mut nearest, id, hit(id));
+            }
+            for &child in node.children.iter().filter(|&&child| child != 0) {
+                let (min, max) = self.nodes[child].bounds();
+                heap.extend(ray_box(min, max, origin, direction).map(|enter| Candidate::node(enter, child)));
+            }
+        }
+        nearest
+    }
+}
+
+struct BvhNode {
+    min: [f32; 3],
+    max: [f32; 3],
+    parent: Option<usize>,
+    /// None for leaves, which hold one sphere
+    children: Option<[usize; 2]>,
+    /// Levels of boxes below this one, 0 for leaves
+    height: u32,
+    id: usize,
+}
+
+/// Bounding volume hierarchy of boxes, where each box holds two smaller ones and the smallest hold one sphere each.
+/// The tree is kept balanced like an AVL tree so bunched up spheres don't make long chains. Boxes around spheres are made a bit bigger than needed, so spheres can move a little without changing the tree
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    /// Nodes that were removed and can be used again
+    free: Vec<usize>,
+    root: Option<usize>,
+    spheres: HashMap<usize, (Sphere, usize)>,
+    /// How much bigger the boxes around spheres are
+    margin: f32,
+}
+
+fn area(min: [f32; 3], max: [f32; 3]) -> f32 {
+    let [x, y, z] = [0, 1, 2].map(|i| max[i] - min[i]);
+    x * y + y * z + z * x
+}
+
+fn union(a: ([f32; 3], [f32; 3]), b: ([f32; 3], [f32; 3])) -> ([f32; 3], [f32; 3]) {
+    ([0, 1, 2].map(|i| a.0[i].min(b.0[i])), [0, 1, 2].map(|i| a.1[i].max(b.1[i])))
+}
+
+impl Bvh {
+    pub fn new(size: f32) -> Bvh {
+        Bvh { nodes: Vec::new(), free: Vec::new(), root: None, spheres: HashMap::new(), margin: size / 4.0 }
+    }
+
+    fn bounds(&self, node: usize) -> ([f32; 3], [f32; 3]) {
+        (self.nodes[node].min, self.nodes[node].max)
+    }
+
+    fn allocate(&mut self, node: BvhNode) -> usize {
+        match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            },
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            },
+        }
+    }
+
+    /// Puts `new` where `old` was under `parent`, or at the root
+    fn replace(&mut self, parent: Option<usize>, old: usize, new: usize) {
+        self.nodes[new].parent = parent;
+        match parent {
+            Some(parent) => {
+                let children = self.nodes[parent].children.as_mut().unwrap();
+                children[(children[1] == old) as usize] = new;
+            },
+            None => self.root = Some(new),
+        }
+    }
+
+    /// Makes a box fit its children again
+    fn fit(&mut self, node: usize) {
+        if let Some([a, b]) = self.nodes[node].children {
+            (self.nodes[node].min, self.nodes[node].max) = union(self.bounds(a), self.bounds(b));
+            self.nodes[node].height = 1 + self.nodes[a].height.max(self.nodes[b].height);
+        }
+    }
+
+    /// Lifts the taller child up in place of `node` if it is more than one level taller than the other, returning what is there now
+    fn balance(&mut self, node: usize) -> usize {
+        let Some(children) = self.nodes[node].children else {
+            return node;
+        };
+        let [left, right] = children.map(|child| self.nodes[child].height);
+        let tall = match () {
+            _ if right > left + 1 => 1,
+            _ if left > right + 1 => 0,
+            _ => return node,
+        };
+        let lifted = children[tall];
+        let [a, b] = self.nodes[lifted].children.unwrap();
+        // The taller grandchild stays under the lifted box, the other takes its place under `node`
+        let (keep, give) = if self.nodes[a].height > self.nodes[b].height { (a, b) } else { (b, a) };
+        self.replace(self.nodes[node].parent, node, lifted);
+        self.nodes[lifted].children = Some([node, keep]);
+        self.nodes[node].parent = Some(lifted);
+        self.nodes[node].children.as_mut().unwrap()[tall] = give;
+        self.nodes[give].parent = Some(node);
+        self.fit(node);
+        self.fit(lifted);
+        lifted
+    }
+
+    /// Balances and fits the boxes from `node` up to the root
+    fn refit(&mut self, mut node: Option<usize>) {
+        while let Some(index) = node {
+            let index = self.balance(index);
+            self.fit(index);
+            node = self.nodes[index].parent;
+        }
+    }
+
+    fn insert(&mut self, id: usize, sphere: Sphere) {
+        let leaf_bounds = (sphere.min().map(|v| v - self.margin), sphere.max().map(|v| v + self.margin));
+        let leaf = self.allocate(BvhNode { min: leaf_bounds.0, max: leaf_bounds.1, parent: None, children: None, height: 0, id });
+        self.spheres.insert(id, (sphere, leaf));
+
+        let Some(mut sibling) = self.root else {
+            self.root = Some(leaf);
+            return;
+        };
+        // Go down the side where the boxes grow the least, or stop once joining here is cheaper
+        while let Some(children) = self.nodes[sibling].children {
+            let (min, max) = self.bounds(sibling);
+            let (joined_min, joined_max) = union((min, max), leaf_bounds);
+            // A new box here costs its area twice, and everything below here grows at least as much as this box does
+            let joined = 2.0 * area(joined_min, joined_max);
+            let inherited = joined - 2.0 * area(min, max);
+            let cost = children.map(|child| {
+                let (min, max) = self.bounds(child);
+                let (grown_min, grown_max) = union((min, max), leaf_bounds);
+                let grown = area(grown_min, grown_max);
+                inherited + if self.nodes[child].children.is_some() { grown - area(min, max) } else { grown }
+            });
+            if joined <= cost[0] && joined <= cost[1] {
+                break;
+            }
+            sibling = children[(cost[1] < cost[0]) as usize];
+        }
+
+        let parent = self.nodes[sibling].parent;
+        let joined = self.allocate(BvhNode { min: leaf_bounds.0, max: leaf_bounds.1, parent, children: Some([sibling, leaf]), height: 1, id: 0 });
+        self.replace(parent, sibling, joined);
+        self.nodes[sibling].parent = Some(joined);
+        self.nodes[leaf].parent = Some(joined);
+        self.refit(Some(joined));
+    }
+}
+
+impl SpatialIndex for Bvh {
+    fn update(&mut self, id: usize, centre: [f32; 3], radius: f32) {
+        let sphere = Sphere { centre, radius };
+        if let Some((entry, leaf)) = self.spheres.get_mut(&id) {
+            let node = &self.nodes[*leaf];
+            if (0..3).all(|i| sphere.min()[i] >= node.min[i] && sphere.max()[i] <= node.max[i]) {
+                *entry = sphere;
+                return;
+            }
+        }
+        self.remove(id);
+        self.insert(id, sphere);
+    }
+
+    fn remove(&mut self, id: usize) {
+        let Some((_, leaf)) = self.spheres.remove(&id) else {
+            return;
+        };
+        self.free.push(leaf);
+        let Some(parent) = self.nodes[leaf].parent else {
+            self.root = None;
+            return;
+        };
+        // The sibling takes the parent's place
+        let [a, b] = self.nodes[parent].children.unwrap();
+        let sibling = if a == leaf { b } else { a };
+        let grandparent = self.nodes[parent].parent;
+        self.replace(grandparent, parent, sibling);
+        self.free.push(parent);
+        self.refit(grandparent);
+    }
+
+    fn within(&self, centre: [f32; 3], radius: f32) -> Vec<usize> {
+        let mut found = Vec::new();
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+        while let Some(node) = stack.pop() {
+            let (min, max) = self.bounds(node);
+            if box_distance_squared(min, max, centre) > radius * radius {
+                continue;
+            }
+            match self.nodes[node].children {
+                Some(children) => stack.extend(children),
+                None => {
+                    let id = self.nodes[node].id;
+                    let (sphere, _) = self.spheres[&id];
+                    if distance_squared(sphere.centre, centre) <= (sphere.radius + radius).powi(2) {
+                        found.push(id);
+                    }
+                },
+            }
+        }
+        found
+    }
+
+    fn nearest(&self, point: [f32; 3], count: usize) -> Vec<(usize, f32)> {
+        let root = self.root.map(|root| Candidate::node(box_distance_squared(self.nodes[root].min, self.nodes[root].max, point), root));
+        nearest_first(root, count, |node, heap| match self.nodes[node].children {
+            Some(children) => heap.extend(children.map(|child| Candidate::node(box_distance_squared(self.nodes[child].min, self.nodes[child].max, point), child))),
+            None => {
+                let id = self.nodes[node].id;
+                heap.push(Candidate::sphere(distance_squared(self.spheres[&id].0.centre, point), id));
+            },
+        })
+    }
+
+    fn ray(&self, origin: [f32; 3], direction: [f32; 3], hit: &mut dyn FnMut(usize) -> Option<f32>) -> Option<(usize, f32)> {
+        let enter = |node: usize| ray_box(self.nodes[node].min, self.nodes[node].max, origin, direction).map(|enter| Candidate::node(enter, node));
+        let mut heap: BinaryHeap<Candidate> = self.root.and_then(enter).into_iter().collect();
+        let mut nearest = None;
+        while let Some(candidate) = heap.pop() {
+            if nearest.is_some_and(|(_, nearest)| nearest < candidate.distance) {
+                break;
+            }
+            let node = candidate.node.unwrap();
+            match self.nodes[node].children {
+                Some(children) => heap.extend(children.into_iter().filter_map(enter)),
+                None => closest(&mut nearest, self.nodes[node].id, hit(self.nodes[node].id)),
+            }
+        }
+        nearest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    /// How far along the ray a sphere is hit
+    fn sphere(origin: [f32; 3], direction: [f32; 3], (centre, radius): ([f32; 3], f32)) -> Option<f32> {
+        let to = [0, 1, 2].map(|i| centre[i] - origin[i]);
+        let along: f32 = (0..3).map(|i| to[i] * direction[i]).sum();
+        let away = (0..3).map(|i| to[i] * to[i]).sum::<f32>() - along * along;
+        let inside = radius * radius - away;
+        (inside >= 0.0 && along - inside.sqrt() >= 0.0).then(|| along - inside.sqrt())
+    }
+
+    #[test]
+    fn queries_find_the_same_spheres_as_checking_every_one() {
+        for kind in [Kind::Grid, Kind::Octree, Kind::Bvh] {
+            let mut rng = StdRng::seed_from_u64(3);
+            let mut spheres: Vec<Option<([f32; 3], f32)>> = (0..500).map(|_| Some(([0; 3].map(|_| rng.gen_range(-20.0..20.0)), rng.gen_range(0.2..2.0)))).collect();
+            let mut index = new(kind, 2.0);
+            for (id, &(centre, radius)) in spheres.iter().flatten().enumerate() {
+                index.update(id, centre, radius);
+            }
+            // Some move a little, some far away, some grow and some are removed
+            for (id, sphere) in spheres.iter_mut().enumerate() {
+                let Some((centre, radius)) = sphere.as_mut() else { continue };
+                match id % 4 {
+                    0 => *centre = centre.map(|v| v + rng.gen_range(-0.3..0.3)),
+                    1 => *centre = [0; 3].map(|_| rng.gen_range(-60.0..60.0)),
+                    2 => *radius *= 2.0,
+                    _ if id % 8 == 3 => {
+                        index.remove(id);
+                        *sphere = None;
+                        continue;
+                    },
+                    _ => (),
+                }
+                let (centre, radius) = sphere.unwrap();
+                index.update(id, centre, radius);
+            }
+            let live = || spheres.iter().enumerate().filter_map(|(id, sphere)| sphere.map(|sphere| (id, sphere)));
+
+            for _ in 0..100 {
+                let point = [0; 3].map(|_| rng.gen_range(-40.0..40.0));
+                let direction = [0; 3].map(|_| rng.gen_range(-1.0f32..1.0));
+                let length = (0..3).map(|i| direction[i] * direction[i]).sum::<f32>().sqrt();
+                let direction = direction.map(|v| v / length);
+
+                let expected = live()
+                    .filter_map(|(id, s)| sphere(point, direction, s).map(|t| (id, t)))
+                    .min_by(|a, b| a.1.total_cmp(&b.1));
+                let found = index.ray(point, direction, &mut |id| sphere(point, direction, spheres[id].unwrap()));
+                assert_eq!(found.map(|(id, _)| id), expected.map(|(id, _)| id), "{:?} ray", kind);
+
+                let mut expected: Vec<usize> = live()
+                    .filter(|(_, (centre, radius))| distance_squared(*centre, point).sqrt() <= radius + 5.0)
+                    .map(|(id, _)| id)
+                    .collect();
+                let mut found = index.within(point, 5.0);
+                expected.sort();
+                found.sort();
+                assert_eq!(found, expected, "{:?} within", kind);
+
+                let mut expected: Vec<(usize, f32)> = live().map(|(id, (centre, _))| (id, distance_squared(centre, point).sqrt())).collect();
+                expected.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+                expected.truncate(8);
+                let found = index.nearest(point, 8);
+                assert_eq!(found.iter().map(|(id, _)| *id).collect::<Vec<_>>(), expected.iter().map(|(id, _)| *id).collect::<Vec<_>>(), "{:?} nearest", kind);
+            }
+        }
+    }
+
+    #[test]
+    fn octree_nodes_left_empty_are_reused() {
+        let mut octree = Octree::new(2.0);
+        octree.update(0, [50.0, 0.0, 0.0], 0.5);
+        let depth = (octree.nodes[0].half / octree.smallest).log2() as usize + 1;
+        for sweep in 0..4 {
+            for step in 0..=200 {
+                let x = if sweep % 2 == 0 { step as f32 * 0.5 - 50.0 } else { 50.0 - step as f32 * 0.5 };
+                octree.update(0, [x, 0.0, 0.0], 0.5);
+            }
+        }
+        assert!(octree.nodes.len() <= 2 * depth, "{} nodes for a tree {} deep", octree.nodes.len(), depth);
+        assert_eq!(octree.within([-50.0, 0.0, 0.0], 1.0), vec![0]);
+    }
+}
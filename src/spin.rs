@@ -0,0 +1,99 @@
+use rand::{rngs::StdRng, Rng};
+
+/// How a teapot spins on its own
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Spin {
+    /// Unit length
+    pub axis: [f32; 3],
+    /// Radians per second
+    pub speed: f32,
+    /// Angle at the start in radians
+    pub phase: f32,
+}
+
+impl Default for Spin {
+    fn default() -> Spin {
+        Spin { axis: [0.0, 1.0, 0.0], speed: 6.5, phase: 0.0 }
+    }
+}
+
+impl Spin {
+    /// The rotation `time` seconds after the start, as a quaternion
+    pub fn rotation(&self, time: f32) -> [f32; 4] {
+        // Negative so teapots turn anticlockwise looking down the axis, because the world is drawn mirrored
+        axis_angle(self.axis, -(self.phase + self.speed * time))
+    }
+}
+
+/// Picks how each teapot spins from the command line options
+#[derive(Copy, Clone, Debug)]
+pub struct Spinner {
+    /// Speeds are picked evenly between these
+    pub speed: (f32, f32),
+    /// None to tumble around a random axis
+    pub axis: Option<[f32; 3]>,
+    pub random_phase: bool,
+}
+
+impl Spinner {
+    pub fn spin(&self, rng: &mut StdRng) -> Spin {
+        let axis = self.axis.unwrap_or_else(|| {
+            // Uniform on the sphere
+            let y: f32 = rng.gen_range(-1.0..=1.0);
+            let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+            let around = (1.0 - y * y).sqrt();
+            [angle.cos() * around, y, angle.sin() * around]
+        });
+        let length = (axis[0] * axis[0] + axis[1] * axis[1] + axis[2] * axis[2]).sqrt().max(f32::EPSILON);
+
+        Spin {
+            axis: axis.map(|v| v / length),
+            speed: if self.speed.0 < self.speed.1 { rng.gen_range(self.speed.0..self.speed.1) } else { self.speed.0 },
+            phase: if self.random_phase { rng.gen_range(0.0..std::f32::consts::TAU) } else { 0.0 },
+        }
+    }
+}
+
+/// Quaternion rotating by `angle` radians around a unit `axis`
+pub fn axis_angle(axis: [f32; 3], angle: f32) -> [f32; 4] {
+    let (sin, cos) = (angle / 2.0).sin_cos();
+    [axis[0] * sin, axis[1] * sin, axis[2] * sin, cos]
+}
+
+/// Quaternion that applies `b` and then `a`
+pub fn multiply(a: [f32; 4], b: [f32; 4]) -> [f32; 4] {
+    let [ax, ay, az, aw] = a;
+    let [bx, by, bz, bw] = b;
+    [
+        aw * bx + ax * bw + ay * bz - az * by,
+        aw * by - ax * bz + ay * bw + az * bx,
+        aw * bz + ax * by - ay * bx + az * bw,
+        aw * bw - ax * bx - ay * by - az * bz,
+    ]
+}
+
+/// Turns a teapot at `position` around the vertical so its spout points at the camera
+pub fn face(position: [f32; 3], camera: [f32; 3]) -> [f32; 4] {
+    let angle = (position[2] - camera[2]).atan2(camera[0] - position[0]);
+    axis_angle([0.0, 1.0, 0.0], angle)
+}
+
+/// Rotates `v` by the quaternion `q`
+pub fn rotate(q: [f32; 4], v: [f32; 3]) -> [f32; 3] {
+    let [x, y, z, w] = q;
+    let cross = |a: [f32; 3], b: [f32; 3]| [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]];
+    let t = cross([x, y, z], v).map(|c| c * 2.0);
+    let u = cross([x, y, z], t);
+    [0, 1, 2].map(|i| v[i] + w * t[i] + u[i])
+}
+
+/// The opposite rotation of a unit quaternion
+pub fn conjugate(q: [f32; 4]) -> [f32; 4] {
+    [-q[0], -q[1], -q[2], q[3]]
+}
+
+/// Scales a quaternion back to unit length
+pub fn normalise(q: [f32; 4]) -> [f32; 4] {
+    let length = q.iter().map(|v| v * v).sum::<f32>().sqrt();
+    q.map(|v| v / length)
+}
@@ -0,0 +1,79 @@
+use std::time::{Duration, Instant};
+
+/// Settings that change how fast frames are drawn, printed with the stats so benchmark numbers mean something
+pub struct RenderSettings {
+    pub msaa: u32,
+    pub vsync: crate::window::Vsync,
+    pub fps_cap: f32,
+    pub low_power_fps: Option<f32>,
+}
+
+fn vsync_name(vsync: crate::window::Vsync) -> &'static str {
+    match vsync {
+        crate::window::Vsync::On => "on",
+        crate::window::Vsync::Off => "off",
+    }
+}
+
+/// Prints frame timings every second when --debug is used
+pub struct DebugStats {
+    settings: RenderSettings,
+    enabled: bool,
+    last_print: Instant,
+    frame_times: Vec<Duration>,
+    /// How many teapots were drawn this frame
+    pub teapots: usize,
+    /// How many teapots were skipped for being off screen this frame
+    pub culled: usize,
+    /// How many teapots were drawn with each level of detail, the last one is impostors
+    pub levels: Vec<usize>,
+    pub focused: bool,
+}
+
+impl DebugStats {
+    pub fn new(enabled: bool, settings: RenderSettings) -> DebugStats {
+        if enabled {
+            let cap = if settings.fps_cap > 0.0 { format!("{}", settings.fps_cap) } else { "none".to_string() };
+            let low_power = settings.low_power_fps.map(|fps| format!("{} fps when unfocused", fps)).unwrap_or("off".to_string());
+            println!("msaa: {}x, vsync: {}, fps cap: {}, low power: {}", settings.msaa, vsync_name(settings.vsync), cap, low_power);
+        }
+
+        DebugStats {
+            settings,
+            enabled,
+            last_print: Instant::now(),
+            frame_times: Vec::new(),
+            teapots: 0,
+            culled: 0,
+            levels: Vec::new(),
+            focused: true,
+        }
+    }
+
+    /// Call once per frame with the time the frame took
+    pub fn frame(&mut self, frame_time: Duration) {
+        if !self.enabled {
+            return;
+        }
+        self.frame_times.push(frame_time);
+
+        let now = Instant::now();
+        if now - self.last_print < Duration::from_secs(1) {
+            return;
+        }
+
+        let elapsed = (now - self.last_print).as_secs_f32();
+        let fps = self.frame_times.len() as f32 / elapsed;
+        let average = self.frame_times.iter().sum::<Duration>().as_secs_f32() * 1000.0 / self.frame_times.len() as f32;
+        let min = self.frame_times.iter().min().unwrap().as_secs_f32() * 1000.0;
+        let max = self.frame_times.iter().max().unwrap().as_secs_f32() * 1000.0;
+
+        let levels = if self.levels.len() > 2 { format!(", lod: {:?}", self.levels) } else { String::new() };
+        println!("fps: {:.1}, frame: {:.2}ms (min {:.2}ms, max {:.2}ms), teapots: {} ({} culled){}, msaa: {}x, vsync: {}, {}",
+                 fps, average, min, max, self.teapots, self.culled, levels, self.settings.msaa, vsync_name(self.settings.vsync),
+                 if self.focused { "focused" } else { "unfocused" });
+
+        self.frame_times.clear();
+        self.last_print = now;
+    }
+}
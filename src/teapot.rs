@@ -1,5 +1,7 @@
 use glium::implement_vertex;
 
+use crate::mesh::Mesh;
+
 #[derive(Copy, Clone)]
 pub struct Vertex {
     position: (f32, f32, f32)
@@ -7,6 +9,19 @@ pub struct Vertex {
 
 implement_vertex!(Vertex, position);
 
+/// The teapot as a mesh, before the model matrix scales it
+pub fn mesh() -> Mesh {
+    let mut mesh = Mesh {
+        positions: VERTICES.iter().map(|Vertex { position: (x, y, z) }| [*x, *y, *z]).collect(),
+        normals: NORMALS.iter().map(|Normal { normal: (x, y, z) }| [*x, *y, *z]).collect(),
+        uvs: vec![[0.0, 0.0]; VERTICES.len()],
+        indices: INDICES.iter().map(|index| *index as u32).collect(),
+    };
+    // Gets rid of the dummy vertex
+    mesh.compact();
+    mesh
+}
+
 pub const VERTICES: [Vertex; 531] = [
     Vertex { position: (0.0, 0.0, 0.0) },   // dummy vector because in the original model indices
                                             // start at 1
@@ -0,0 +1,74 @@
+/// Runs the simulation in steps of the same length whatever the frame rate, so the same inputs always give the same result
+pub struct FixedStep {
+    /// Seconds per step
+    pub step: f64,
+    /// Longest frame counted, a longer hitch slows the simulation down instead of jumping it forward
+    max_frame: f64,
+    /// Time not yet simulated
+    accumulator: f64,
+    /// Steps run so far
+    pub tick: u64,
+}
+
+impl FixedStep {
+    pub fn new(rate: f32, max_frame: f32) -> FixedStep {
+        FixedStep { step: 1.0 / rate as f64, max_frame: max_frame as f64, accumulator: 0.0, tick: 0 }
+    }
+
+    /// Adds a frame's worth of time to simulate
+    pub fn add(&mut self, seconds: f32) {
+        self.accumulator += (seconds as f64).min(self.max_frame);
+    }
+
+    /// Takes a step if there is enough time for one
+    pub fn step(&mut self) -> bool {
+        if self.accumulator < self.step {
+            return false;
+        }
+        self.accumulator -= self.step;
+        self.tick += 1;
+        true
+    }
+
+    /// Takes a step straight away, to run the simulation as fast as it goes without a window
+    pub fn step_now(&mut self) {
+        self.tick += 1;
+    }
+
+    /// Seconds simulated so far
+    pub fn time(&self) -> f32 {
+        (self.tick as f64 * self.step) as f32
+    }
+
+    /// How far from the state before the last step to the state after it to draw, from 0 to 1
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.step).min(1.0) as f32
+    }
+
+    /// Time to draw things that only depend on the time at, a step behind like everything else
+    pub fn draw_time(&self) -> f32 {
+        ((self.tick as f64 - 1.0 + self.alpha() as f64) * self.step).max(0.0) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hitches_are_clamped_and_leftovers_carried() {
+        let mut clock = FixedStep::new(120.0, 0.25);
+        let steps = |clock: &mut FixedStep, seconds| {
+            clock.add(seconds);
+            std::iter::from_fn(|| clock.step().then_some(())).count()
+        };
+        assert_eq!(steps(&mut clock, 1.0 / 60.0), 2);
+        // Half a step is left over for the next frame
+        assert_eq!(steps(&mut clock, 1.5 / 120.0), 1);
+        assert!((clock.alpha() - 0.5).abs() < 1e-4);
+        assert_eq!(steps(&mut clock, 0.5 / 120.0), 1);
+        // A five second hitch only runs a quarter of a second
+        assert_eq!(steps(&mut clock, 5.0), 30);
+        assert_eq!(clock.tick, 34);
+    }
+}
@@ -0,0 +1,99 @@
+use rayon::prelude::*;
+
+use crate::{field::{self, Field}, formation::Transition, instance::Instance, positions::Teapots, shoot, spin};
+
+/// Fewer teapots than this aren't worth handing to another thread
+const CHUNK: usize = 4096;
+
+/// Moves every teapot on its own for a simulation step: along its formation path, through the force fields and, without physics, along its flight
+/// if it was fired. Only the arrays this needs are read, split into chunks at the same indices for threads. No teapot depends on another,
+/// so this gives the same result however many threads there are
+pub fn advance(teapots: &mut Teapots, transition: Option<&Transition>, fields: &[Field], camera: [f32; 3], time: f32, delta: f32, physics: bool) {
+    let Teapots { position, velocity, rotation, previous, path, details } = teapots;
+    (position.par_chunks_mut(CHUNK), velocity.par_chunks_mut(CHUNK), rotation.par_chunks(CHUNK), previous.par_chunks_mut(CHUNK), path.par_chunks(CHUNK), details.par_chunks_mut(CHUNK))
+        .into_par_iter()
+        .for_each(|(position, velocity, rotation, previous, path, details)| {
+            for i in 0..position.len() {
+                previous[i] = Some((position[i], rotation[i]));
+                // Teapots flying to a formation only follow their path
+                if let Some(path) = &path[i] {
+                    if let Some(transition) = transition {
+                        (position[i], details[i].colour) = transition.place(path, time);
+                    }
+                    continue;
+                }
+                match (details[i].gravity, physics) {
+                    // Without physics fired teapots fly on their own
+                    (Some(gravity), false) => shoot::fly(&mut position[i], &mut velocity[i], gravity, delta),
+                    _ => field::apply(fields, &mut position[i], &mut velocity[i], camera, time, delta, physics),
+                }
+            }
+        });
+}
+
+/// Position and rotation to draw the teapot at `index` at, `alpha` of the way from before the last step to after it
+pub fn between_steps(teapots: &Teapots, index: usize, alpha: f32) -> ([f32; 3], [f32; 4]) {
+    let (now, turned) = (teapots.position[index], teapots.rotation[index]);
+    let Some((position, rotation)) = teapots.previous[index] else {
+        return (now, turned);
+    };
+    let position = [0, 1, 2].map(|i| position[i] + (now[i] - position[i]) * alpha);
+    // The short way round, normalised so it stays a rotation
+    let sign = if (0..4).map(|i| rotation[i] * turned[i]).sum::<f32>() < 0.0 { -1.0 } else { 1.0 };
+    let rotation = [0, 1, 2, 3].map(|i| rotation[i] + (sign * turned[i] - rotation[i]) * alpha);
+    (position, spin::normalise(rotation))
+}
+
+/// How every teapot is drawn this frame, worked out across threads from each teapot's index and in the same order as the teapots
+pub fn instances(teapots: &Teapots, instance: impl Fn(usize) -> Instance + Sync + Send) -> Vec<Instance> {
+    (0..teapots.len()).into_par_iter().with_min_len(CHUNK).map(instance).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::positions::Teapot;
+
+    fn teapots(count: usize, seed: u64) -> Teapots {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..count).map(|id| {
+            let position = [0; 3].map(|_| rng.gen_range(-64.0..64.0));
+            let shot = rng.gen_bool(0.1);
+            Teapot {
+                id,
+                velocity: [0; 3].map(|_| rng.gen_range(-1.0..1.0)),
+                gravity: shot.then_some([0.0, -9.81, 0.0]),
+                ..Teapot::new(position, [1.0; 3])
+            }
+        }).collect::<Vec<_>>().into()
+    }
+
+    fn fields() -> Vec<Field> {
+        ["attract:camera:0.5:20", "vortex:0,0,0:0.3:40", "turbulence:0,0,0:2:8", "wind:0,0,0:1::1,0,0"]
+            .map(|spec| field::parse(spec).unwrap())
+            .to_vec()
+    }
+
+    /// Runs `frames` frames of 60 per second on `threads` threads
+    fn run(teapots: &mut Teapots, frames: usize, threads: usize) {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        let fields = fields();
+        pool.install(|| for frame in 0..frames {
+            let time = frame as f32 / 60.0;
+            advance(teapots, None, &fields, [time, 0.0, 0.0], time, 1.0 / 60.0, false);
+        });
+    }
+
+    #[test]
+    fn threads_dont_change_the_result() {
+        let start = teapots(3 * CHUNK + 100, 5);
+        let mut alone = start.clone();
+        let mut together = start.clone();
+        run(&mut alone, 3, 1);
+        run(&mut together, 3, 4);
+        assert_eq!(alone, together);
+        assert_ne!(alone, start);
+    }
+}
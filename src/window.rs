@@ -0,0 +1,102 @@
+use std::num::NonZeroU32;
+
+use glium::glutin::{config::ConfigTemplateBuilder, context::ContextAttributesBuilder, display::GetGlDisplay, prelude::*, surface::{SurfaceAttributesBuilder, SwapInterval, WindowSurface}};
+use glium::winit::{
+    application::ApplicationHandler, dpi::PhysicalSize, event::{DeviceEvent, DeviceId, Event, WindowEvent},
+    event_loop::{ActiveEventLoop, EventLoop}, window::{Fullscreen, Window, WindowId},
+};
+use raw_window_handle::HasWindowHandle;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Vsync {
+    /// Wait for the monitor before showing each frame
+    On,
+    /// Show frames as soon as they are drawn, may tear
+    Off,
+}
+
+pub type Display = glium::backend::glutin::Display<WindowSurface>;
+
+/// How the window looks when it opens
+pub struct WindowOptions {
+    pub title: String,
+    /// Size in pixels when not fullscreen
+    pub width: u32,
+    pub height: u32,
+    pub fullscreen: bool,
+    /// Without a title bar and border
+    pub borderless: bool,
+    pub vsync: Vsync,
+}
+
+/// Opens the window and its OpenGL context.
+///
+/// This is what glium's SimpleWindowBuilder does, but it doesn't let us pick the swap interval.
+pub fn create(event_loop: &EventLoop<()>, options: &WindowOptions) -> (Window, Display) {
+    let attributes = Window::default_attributes()
+        .with_title(&options.title)
+        .with_inner_size(PhysicalSize::new(options.width.max(1), options.height.max(1)))
+        .with_decorations(!options.borderless)
+        .with_fullscreen(options.fullscreen.then_some(Fullscreen::Borderless(None)));
+
+    let (window, config) = glutin_winit::DisplayBuilder::new()
+        .with_window_attributes(Some(attributes))
+        .build(event_loop, ConfigTemplateBuilder::new(), |mut configs| configs.next().unwrap())
+        .unwrap();
+    let window = window.unwrap();
+
+    let (width, height): (u32, u32) = window.inner_size().into();
+    let handle = window.window_handle().expect("couldn't obtain raw window handle").as_raw();
+    let surface_attributes = SurfaceAttributesBuilder::<WindowSurface>::new()
+        .build(handle, NonZeroU32::new(width.max(1)).unwrap(), NonZeroU32::new(height.max(1)).unwrap());
+    let surface = unsafe { config.display().create_window_surface(&config, &surface_attributes).unwrap() };
+
+    let context_attributes = ContextAttributesBuilder::new().build(Some(handle));
+    let context = unsafe { config.display().create_context(&config, &context_attributes).expect("failed to create context") }
+        .make_current(&surface)
+        .unwrap();
+
+    let interval = match options.vsync {
+        Vsync::On => SwapInterval::Wait(NonZeroU32::new(1).unwrap()),
+        Vsync::Off => SwapInterval::DontWait,
+    };
+    if let Err(e) = surface.set_swap_interval(&context, interval) {
+        eprintln!("Failed to set vsync: {}", e);
+    }
+
+    let display = Display::from_context_surface(context, surface).unwrap();
+    (window, display)
+}
+
+/// Switches between fullscreen on the current monitor and windowed
+pub fn toggle_fullscreen(window: &Window) {
+    if window.fullscreen().is_some() {
+        window.set_fullscreen(None);
+    } else {
+        window.set_fullscreen(Some(Fullscreen::Borderless(None)));
+    }
+}
+
+/// Hands the window, mouse and loop events to one function, so the main loop can keep everything it uses in its closure
+pub struct Handler<F: FnMut(Event<()>, &ActiveEventLoop)>(pub F);
+
+impl<F: FnMut(Event<()>, &ActiveEventLoop)> ApplicationHandler for Handler<F> {
+    // The window is opened before the loop starts
+    fn resumed(&mut self, _: &ActiveEventLoop) {}
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, window_id: WindowId, event: WindowEvent) {
+        (self.0)(Event::WindowEvent { window_id, event }, event_loop);
+    }
+
+    fn device_event(&mut self, event_loop: &ActiveEventLoop, device_id: DeviceId, event: DeviceEvent) {
+        (self.0)(Event::DeviceEvent { device_id, event }, event_loop);
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        (self.0)(Event::AboutToWait, event_loop);
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        (self.0)(Event::LoopExiting, event_loop);
+    }
+}
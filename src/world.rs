@@ -0,0 +1,279 @@
+use std::{iter::Peekable, vec};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    age, field::Field, formation::{self, Formations, Layout, Step, Transition}, growth::{self, Growth}, input::{Input, Key},
+    physics::{self, Physics}, positions::{self, Teapot, Teapots}, screensaver, selection::Selection, shoot::Gun, spin::Spinner, timestep::FixedStep, update,
+};
+
+/// Radians the camera turns for each unit the mouse moves
+const SENSITIVITY: f32 = 0.001;
+/// Keeps the camera from looking straight up or down, where it would flip over
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+/// Units per second the camera flies at
+const CAMERA_SPEED: f32 = 3.0;
+
+/// Unit vector the camera looks along
+pub fn look_direction(yaw: f32, pitch: f32) -> [f32; 3] {
+    [
+        yaw.cos() * pitch.cos(),
+        pitch.sin(),
+        yaw.sin() * pitch.cos(),
+    ]
+}
+
+/// Makes the random teapots added as the simulation goes
+pub struct Spawner {
+    /// Teapots are placed between -range and range
+    pub range: f32,
+    pub scale: f32,
+    /// Gives each teapot its own scale between these instead
+    pub scale_range: Option<(f32, f32)>,
+    pub spinner: Spinner,
+    pub lifetime: Option<(f32, f32)>,
+    pub colour: [f32; 3],
+    /// Leaves the spinning to the physics
+    pub physics: bool,
+    /// Id of the next teapot
+    pub next_id: usize,
+}
+
+impl Spawner {
+    /// Gives a new or loaded teapot its id, spin and lifetime
+    pub fn prepare(&mut self, teapot: &mut Teapot, rng: &mut StdRng) {
+        teapot.spin = self.spinner.spin(rng);
+        teapot.lifetime = age::lifetime(self.lifetime, rng);
+        teapot.id = self.next_id;
+        self.next_id += 1;
+        if self.physics {
+            physics::take_spin(teapot);
+        }
+    }
+
+    /// A random teapot that appeared `time` seconds after the start
+    pub fn teapot(&mut self, rng: &mut StdRng, time: f32) -> Teapot {
+        let position = [0; 3].map(|_| rng.gen_range(-self.range..self.range));
+        let scale = self.scale_range.map_or(self.scale, |(min, max)| rng.gen_range(min..=max));
+        let mut teapot = Teapot {
+            scale,
+            spawned: time,
+            ..Teapot::new(position, self.colour)
+        };
+        self.prepare(&mut teapot, rng);
+        teapot
+    }
+}
+
+/// Adds `amount` teapots every `period` seconds from `after` seconds, and despawns the oldest in the same way
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Spawning {
+    pub amount: usize,
+    pub period: f32,
+    pub after: f32,
+    pub despawn_amount: usize,
+    pub despawn_period: f32,
+    pub despawn_after: f32,
+}
+
+/// Everything the simulation changes, moved on a step at a time with the inputs applied on that step.
+/// Nothing in here depends on the window or the frame rate, so the same settings, seed and inputs always end the same way
+pub struct World {
+    pub teapots: Teapots,
+    pub rng: StdRng,
+    pub clock: FixedStep,
+    pub spawner: Spawner,
+    pub spawning: Spawning,
+    pub formations: Formations,
+    /// Layouts still to switch to on their own, in order
+    pub timeline: Peekable<vec::IntoIter<Step>>,
+    pub transition: Option<Transition>,
+    pub fields: Vec<Field>,
+    pub physics: Option<Physics>,
+    pub growth: Growth,
+    pub gun: Gun,
+    pub selection: Selection,
+    /// Flies the camera around on its own and ignores the mouse
+    pub screensaver: bool,
+    pub camera: [f32; 3],
+    /// Where the camera was before the last step
+    pub previous_camera: [f32; 3],
+    /// Horizontal rotation in radians
+    pub yaw: f32,
+    /// Vertical rotation in radians
+    pub pitch: f32,
+    /// Where the camera was looking before the last step
+    pub previous_yaw: f32,
+    pub previous_pitch: f32,
+    /// Right, up and forward from the movement keys held down
+    move_vector: [f32; 3],
+    /// Clicking fires once the mouse is locked
+    cursor_locked: bool,
+    /// Simulation time of the last spawn, for checking the spawn period
+    last_spawn: f32,
+    /// Simulation time of the last despawn, for checking the despawn period
+    last_despawn: f32,
+    /// Ids of the teapots removed since this was last emptied, for the spatial index to forget
+    pub removed: Vec<usize>,
+}
+
+impl World {
+    /// A world without any teapots, fields, physics, spawning or timeline, which can be filled in afterwards
+    pub fn new(clock: FixedStep, spawner: Spawner, formations: Formations, growth: Growth, gun: Gun, seed: u64) -> World {
+        World {
+            teapots: Teapots::default(),
+            rng: StdRng::seed_from_u64(seed),
+            clock,
+            spawner,
+            spawning: Spawning::default(),
+            formations,
+            timeline: Vec::new().into_iter().peekable(),
+            transition: None,
+            fields: Vec::new(),
+            physics: None,
+            growth,
+            gun,
+            selection: Selection::default(),
+            screensaver: false,
+            camera: [0.0; 3],
+            previous_camera: [0.0; 3],
+            yaw: 0.0,
+            pitch: 0.0,
+            previous_yaw: 0.0,
+            previous_pitch: 0.0,
+            move_vector: [0.0; 3],
+            cursor_locked: false,
+            last_spawn: 0.0,
+            last_despawn: 0.0,
+            removed: Vec::new(),
+        }
+    }
+
+    /// Adds `amount` random teapots that appeared at `time`
+    pub fn spawn(&mut self, amount: usize, time: f32) {
+        for _ in 0..amount {
+            let teapot = self.spawner.teapot(&mut self.rng, time);
+            self.teapots.push(teapot);
+        }
+    }
+
+    /// Starts the teapots flying to `layout`, or says there is no such formation
+    fn morph(&mut self, layout: Layout, time: f32) {
+        let spawner = &mut self.spawner;
+        match self.formations.morph(layout, &mut self.teapots, time, &mut self.rng, |rng: &mut StdRng| spawner.teapot(rng, time)) {
+            Some(started) => self.transition = Some(started),
+            None => eprintln!("No {:?} formation, give one with --text or --image", layout),
+        }
+    }
+
+    fn input(&mut self, input: Input, time: f32) {
+        match input {
+            Input::Press(key) => match key {
+                Key::W => self.move_vector[2] = 1.0,
+                Key::S => self.move_vector[2] = -1.0,
+                Key::A => self.move_vector[0] = -1.0,
+                Key::D => self.move_vector[0] = 1.0,
+                Key::E => self.move_vector[1] = 1.0,
+                Key::Q => self.move_vector[1] = -1.0,
+                Key::Escape => self.cursor_locked = false,
+                Key::Number(number) => self.morph(Layout::ALL[number as usize - 1], time),
+                Key::Delete => {
+                    if let Some(index) = self.selection.index {
+                        self.teapots.details[index].dying = Some(time);
+                        self.selection.select(None);
+                    }
+                },
+                Key::C => {
+                    if let Some(index) = self.selection.index {
+                        self.teapots.details[index].colour = [self.rng.gen(), self.rng.gen(), self.rng.gen()];
+                    }
+                },
+                Key::L => self.selection.follow = !self.selection.follow && self.selection.index.is_some(),
+            },
+            Input::Release(key) => match key {
+                Key::W | Key::S => self.move_vector[2] = 0.0,
+                Key::A | Key::D => self.move_vector[0] = 0.0,
+                Key::E | Key::Q => self.move_vector[1] = 0.0,
+                _ => (),
+            },
+            Input::Look { dx, dy } if !self.screensaver => {
+                self.yaw -= dx as f32 * SENSITIVITY;
+                self.pitch = (self.pitch - dy as f32 * SENSITIVITY).clamp(-MAX_PITCH, MAX_PITCH);
+            },
+            // The screensaver leaves the mouse alone so moving it can wake things up
+            Input::Focus(focused) if !self.screensaver => self.cursor_locked = focused,
+            Input::Look { .. } | Input::Focus(_) => (),
+            Input::Click => {
+                if self.cursor_locked {
+                    let teapot = self.spawner.teapot(&mut self.rng, time);
+                    self.teapots.push(self.gun.fire(teapot, self.camera, look_direction(self.yaw, self.pitch)));
+                } else {
+                    self.cursor_locked = true;
+                }
+            },
+            Input::Select(id) => self.selection.select(id.and_then(|id| positions::find(&self.teapots, id))),
+        }
+    }
+
+    /// Applies `inputs` in order, then moves everything on by the step the clock has just taken
+    pub fn step(&mut self, inputs: &[Input]) {
+        let time = self.clock.time();
+        let step = self.clock.step as f32;
+        // Before the inputs, so turning with the mouse is drawn smoothly too
+        self.previous_camera = self.camera;
+        self.previous_yaw = self.yaw;
+        self.previous_pitch = self.pitch;
+        for &input in inputs {
+            self.input(input, time);
+        }
+
+        let forward = [self.yaw.cos(), 0.0, self.yaw.sin()];
+        let right = [self.yaw.sin(), 0.0, -self.yaw.cos()];
+        let world_move_vector = [
+            forward[0] * self.move_vector[2] + right[0] * self.move_vector[0],
+            self.move_vector[1],
+            forward[2] * self.move_vector[2] + right[2] * self.move_vector[0],
+        ];
+        for (position, moved) in self.camera.iter_mut().zip(world_move_vector) {
+            *position += moved * step * CAMERA_SPEED;
+        }
+        if self.screensaver {
+            (self.camera, self.yaw, self.pitch) = screensaver::camera(time, self.spawner.range);
+        }
+
+        while let Some(next) = self.timeline.next_if(|next| next.time <= time) {
+            self.morph(next.layout, time);
+        }
+
+        update::advance(&mut self.teapots, self.transition.as_ref(), &self.fields, self.camera, time, step, self.physics.is_some());
+        if let Some(physics) = &self.physics {
+            physics.step(&mut self.teapots, step);
+        }
+        if let Some(current) = &self.transition {
+            // Back in the cloud the teapots are free to follow the camera again
+            if current.finished(time) && current.layout == formation::Layout::Cloud {
+                self.teapots.path.iter_mut().for_each(|path| *path = None);
+                self.transition = None;
+            }
+        }
+
+        let spawning = self.spawning;
+        if spawning.amount != 0 && time >= spawning.after && time - self.last_spawn >= spawning.period {
+            self.last_spawn = time;
+            self.spawn(spawning.amount, time);
+        }
+        if spawning.despawn_amount != 0 && time >= spawning.despawn_after && time - self.last_despawn >= spawning.despawn_period
+            && growth::despawn(&mut self.teapots.details, spawning.despawn_amount, time) > 0 {
+            self.last_despawn = time;
+        }
+        age::expire(&mut self.teapots.details, time);
+        let growth = self.growth;
+        self.removed.extend(self.teapots.details.iter().filter(|details| growth.dead(details, time)).map(|details| details.id));
+        self.selection.retain(&mut self.teapots, |details| !growth.dead(details, time));
+
+        if let Some((yaw, pitch)) = self.selection.look_at(&self.teapots, self.camera) {
+            self.yaw = yaw;
+            self.pitch = pitch.clamp(-MAX_PITCH, MAX_PITCH);
+        }
+    }
+}